@@ -3,6 +3,7 @@ use std::convert::Infallible;
 use std::fmt;
 use std::fs::File;
 use std::io::Read;
+use std::net::SocketAddr;
 use std::ops::Deref;
 use std::path::PathBuf;
 use std::str::FromStr;
@@ -11,12 +12,14 @@ use std::time::Duration;
 use fluvio_future::tracing::debug;
 use fluvio_smartengine::transformation::TransformationConfig;
 use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
 
 use bytesize::ByteSize;
 
 use fluvio::Compression;
 
 pub use crate::error::ConnectorLoadError;
+use crate::offset::ConsumerOffset;
 
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
@@ -42,6 +45,15 @@ pub struct ConnectorConfig {
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub consumer: Option<ConsumerParameters>,
 
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub retry: Option<RetryParameters>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub shutdown: Option<ShutdownParameters>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub monitoring: Option<MonitoringParameters>,
+
     #[serde(default, flatten, skip_serializing_if = "Option::is_none")]
     pub transforms: Option<TransformationConfig>,
 }
@@ -51,6 +63,24 @@ pub struct ConnectorConfig {
 pub struct ConsumerParameters {
     #[serde(default, skip_serializing_if = "Option::is_none")]
     partition: Option<i32>,
+
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    partitions: Vec<u32>,
+
+    #[serde(rename = "all-partitions")]
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    all_partitions: bool,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    offset: Option<ConsumerOffset>,
+
+    #[serde(rename = "offset-path")]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    offset_path: Option<String>,
+
+    #[serde(rename = "dead-letter-topic")]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    dead_letter_topic: Option<String>,
 }
 
 #[allow(clippy::derive_partial_eq_without_eq)]
@@ -73,7 +103,91 @@ pub struct ProducerParameters {
     batch_size: Option<ByteSize>,
 }
 
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct RetryParameters {
+    #[serde(rename = "max-attempts")]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    max_attempts: Option<u32>,
+
+    #[serde(rename = "initial-backoff", with = "humantime_serde")]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    initial_backoff: Option<Duration>,
+
+    #[serde(rename = "max-backoff", with = "humantime_serde")]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    max_backoff: Option<Duration>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    jitter: Option<f64>,
+
+    #[serde(rename = "circuit-breaker-threshold")]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    circuit_breaker_threshold: Option<u32>,
+
+    #[serde(rename = "circuit-breaker-reset", with = "humantime_serde")]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    circuit_breaker_reset: Option<Duration>,
+}
+
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct ShutdownParameters {
+    #[serde(rename = "grace-period", with = "humantime_serde")]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    grace_period: Option<Duration>,
+}
+
+impl ShutdownParameters {
+    pub fn grace_period(&self) -> Option<Duration> {
+        self.grace_period
+    }
+}
+
+/// Path of the liveness probe served on `health-addr`
+pub const LIVENESS_PATH: &str = "/live";
+/// Path of the readiness probe served on `health-addr`
+pub const READINESS_PATH: &str = "/ready";
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct MonitoringParameters {
+    #[serde(rename = "metrics-addr")]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    metrics_addr: Option<SocketAddr>,
+
+    #[serde(rename = "health-addr")]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    health_addr: Option<SocketAddr>,
+
+    #[serde(rename = "unhealthy-after", with = "humantime_serde")]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    unhealthy_after: Option<Duration>,
+
+    #[serde(rename = "max-idle", with = "humantime_serde")]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    max_idle: Option<Duration>,
+}
+
+impl MonitoringParameters {
+    pub fn metrics_addr(&self) -> Option<SocketAddr> {
+        self.metrics_addr
+    }
+
+    pub fn health_addr(&self) -> Option<SocketAddr> {
+        self.health_addr
+    }
+}
+
 impl ConnectorConfig {
+    /// Values of all the secrets, read from their files and environment variables.
+    /// Fails on secrets stored in Kubernetes.
+    pub fn resolve_secrets(&self) -> Result<BTreeMap<String, SecretString>, ConnectorLoadError> {
+        self.secrets
+            .iter()
+            .map(|(name, value)| Ok((name.clone(), value.resolve(name)?)))
+            .collect()
+    }
+
     pub fn from_file<P: Into<PathBuf>>(path: P) -> Result<Self, ConnectorLoadError> {
         let mut file = File::open(path.into())?;
         let mut contents = String::new();
@@ -90,10 +204,182 @@ impl ConnectorConfig {
                 producer.batch_size = Some(batch_size);
             }
         }
+        connector_config.validate_secrets()?;
         debug!("Using connector config {connector_config:#?}");
         Ok(connector_config)
     }
 
+    fn validate_secrets(&self) -> Result<(), ConnectorLoadError> {
+        for (name, value) in &self.secrets {
+            if let SecretSource::Kubernetes { name: "", .. }
+            | SecretSource::Kubernetes { key: Some(""), .. } = value.source()
+            {
+                return Err(ConnectorLoadError::SecretInvalid(name.clone()));
+            }
+        }
+        Ok(())
+    }
+
+    /// Command line arguments of the connector binary equivalent to this config
+    pub fn to_args(&self) -> Result<Vec<String>, ConnectorLoadError> {
+        let mut args = vec![format!("--fluvio-topic={}", self.topic)];
+        args.extend(self.parameters_args());
+        args.extend(self.producer_parameters());
+        args.extend(self.consumer_parameters());
+        args.extend(self.retry_parameters());
+        args.extend(self.shutdown_parameters());
+        args.extend(self.monitoring_parameters());
+        if let Some(ref transform_params) = self.transforms {
+            for transform in &transform_params.transforms {
+                args.push(format!("--transform={}", serde_json::to_string(transform)?));
+            }
+        }
+        Ok(args)
+    }
+
+    pub fn parameters_args(&self) -> Vec<String> {
+        let mut args = Vec::new();
+        for (key, value) in &self.parameters {
+            let key = key.replace('_', "-");
+            match value {
+                ManagedConnectorParameterValue::String(value) => {
+                    args.push(format!("--{key}={value}"));
+                }
+                ManagedConnectorParameterValue::Vec(values) => {
+                    for value in values {
+                        args.push(format!("--{key}={value}"));
+                    }
+                }
+                ManagedConnectorParameterValue::Map(map) => {
+                    for (sub_key, value) in map {
+                        let sub_key = sub_key.replace('_', "-");
+                        args.push(format!("--{key}={sub_key}:{value}"));
+                    }
+                }
+                // structured values are passed as JSON, item by item for lists
+                ManagedConnectorParameterValue::Structured(Value::Array(values)) => {
+                    for value in values {
+                        args.push(format!("--{key}={value}"));
+                    }
+                }
+                ManagedConnectorParameterValue::Structured(value) => {
+                    args.push(format!("--{key}={value}"));
+                }
+            }
+        }
+        args
+    }
+
+    /// Values of the connector options set by the config, other than its `parameters`,
+    /// keyed by the name of the option fields
+    pub(crate) fn options(&self) -> Result<Map<String, Value>, ConnectorLoadError> {
+        let mut options = Map::new();
+        options.insert("fluvio_topic".to_string(), self.topic.clone().into());
+        if let Some(producer) = self.producer.as_ref() {
+            if let Some(linger) = producer.linger {
+                options.insert("producer_linger".to_string(), duration(linger));
+            }
+            if let Some(compression) = producer.compression {
+                options.insert(
+                    "producer_compression".to_string(),
+                    compression.to_string().into(),
+                );
+            }
+            if let Some(batch_size) = producer.batch_size_string.as_ref() {
+                options.insert("producer_batch_size".to_string(), batch_size.clone().into());
+            }
+        }
+        if let Some(consumer) = self.consumer.as_ref() {
+            let partitions: Vec<Value> = consumer
+                .partition
+                .map(Value::from)
+                .into_iter()
+                .chain(
+                    consumer
+                        .partitions
+                        .iter()
+                        .map(|&partition| partition.into()),
+                )
+                .collect();
+            if !partitions.is_empty() {
+                options.insert("consumer_partition".to_string(), partitions.into());
+            }
+            if consumer.all_partitions {
+                options.insert("consumer_all_partitions".to_string(), true.into());
+            }
+            if let Some(offset) = consumer.offset {
+                options.insert("consumer_offset".to_string(), offset.to_string().into());
+            }
+            if let Some(offset_path) = consumer.offset_path.as_ref() {
+                options.insert(
+                    "consumer_offset_path".to_string(),
+                    offset_path.clone().into(),
+                );
+            }
+            if let Some(dead_letter_topic) = consumer.dead_letter_topic.as_ref() {
+                options.insert(
+                    "dead_letter_topic".to_string(),
+                    dead_letter_topic.clone().into(),
+                );
+            }
+        }
+        if let Some(retry) = self.retry.as_ref() {
+            if let Some(max_attempts) = retry.max_attempts {
+                options.insert("retry_max_attempts".to_string(), max_attempts.into());
+            }
+            if let Some(initial_backoff) = retry.initial_backoff {
+                options.insert(
+                    "retry_initial_backoff".to_string(),
+                    duration(initial_backoff),
+                );
+            }
+            if let Some(max_backoff) = retry.max_backoff {
+                options.insert("retry_max_backoff".to_string(), duration(max_backoff));
+            }
+            if let Some(jitter) = retry.jitter {
+                options.insert("retry_jitter".to_string(), jitter.into());
+            }
+            if let Some(threshold) = retry.circuit_breaker_threshold {
+                options.insert(
+                    "retry_circuit_breaker_threshold".to_string(),
+                    threshold.into(),
+                );
+            }
+            if let Some(reset) = retry.circuit_breaker_reset {
+                options.insert("retry_circuit_breaker_reset".to_string(), duration(reset));
+            }
+        }
+        if let Some(grace_period) = self.shutdown.as_ref().and_then(|s| s.grace_period) {
+            options.insert("shutdown_grace_period".to_string(), duration(grace_period));
+        }
+        if let Some(monitoring) = self.monitoring.as_ref() {
+            if let Some(metrics_addr) = monitoring.metrics_addr {
+                options.insert("metrics_addr".to_string(), metrics_addr.to_string().into());
+            }
+            if let Some(health_addr) = monitoring.health_addr {
+                options.insert("health_addr".to_string(), health_addr.to_string().into());
+            }
+            if let Some(unhealthy_after) = monitoring.unhealthy_after {
+                options.insert(
+                    "health_unhealthy_after".to_string(),
+                    duration(unhealthy_after),
+                );
+            }
+            if let Some(max_idle) = monitoring.max_idle {
+                options.insert("health_max_idle".to_string(), duration(max_idle));
+            }
+        }
+        if let Some(ref transform_params) = self.transforms {
+            let transforms = transform_params
+                .transforms
+                .iter()
+                .map(serde_json::to_value)
+                .collect::<Result<Vec<_>, _>>()?;
+            options.insert("transform".to_string(), transforms.into());
+        }
+        Ok(options)
+    }
+
     pub fn consumer_parameters(&self) -> Vec<String> {
         let mut params = Vec::new();
         if let Some(consumer) = self.consumer.as_ref() {
@@ -101,9 +387,93 @@ impl ConnectorConfig {
                 params.push("--consumer-partition".to_string());
                 params.push(format!("{partition}"));
             }
+            for partition in consumer.partitions.iter() {
+                params.push("--consumer-partition".to_string());
+                params.push(format!("{partition}"));
+            }
+            if consumer.all_partitions {
+                params.push("--consumer-all-partitions".to_string());
+            }
+            if let Some(offset) = consumer.offset {
+                params.push("--consumer-offset".to_string());
+                params.push(offset.to_string());
+            }
+            if let Some(offset_path) = consumer.offset_path.as_ref() {
+                params.push("--consumer-offset-path".to_string());
+                params.push(offset_path.to_string());
+            }
+            if let Some(dead_letter_topic) = consumer.dead_letter_topic.as_ref() {
+                params.push("--dead-letter-topic".to_string());
+                params.push(dead_letter_topic.to_string());
+            }
+        }
+        params
+    }
+    pub fn retry_parameters(&self) -> Vec<String> {
+        let mut params = Vec::new();
+        if let Some(retry) = self.retry.as_ref() {
+            if let Some(max_attempts) = retry.max_attempts {
+                params.push("--retry-max-attempts".to_string());
+                params.push(format!("{max_attempts}"));
+            }
+            if let Some(initial_backoff) = retry.initial_backoff {
+                params.push("--retry-initial-backoff".to_string());
+                params.push(format!("{}ms", initial_backoff.as_millis()));
+            }
+            if let Some(max_backoff) = retry.max_backoff {
+                params.push("--retry-max-backoff".to_string());
+                params.push(format!("{}ms", max_backoff.as_millis()));
+            }
+            if let Some(jitter) = retry.jitter {
+                params.push("--retry-jitter".to_string());
+                params.push(format!("{jitter}"));
+            }
+            if let Some(threshold) = retry.circuit_breaker_threshold {
+                params.push("--retry-circuit-breaker-threshold".to_string());
+                params.push(format!("{threshold}"));
+            }
+            if let Some(reset) = retry.circuit_breaker_reset {
+                params.push("--retry-circuit-breaker-reset".to_string());
+                params.push(format!("{}ms", reset.as_millis()));
+            }
+        }
+        params
+    }
+
+    pub fn shutdown_parameters(&self) -> Vec<String> {
+        let mut params = Vec::new();
+        if let Some(shutdown) = self.shutdown.as_ref() {
+            if let Some(grace_period) = shutdown.grace_period {
+                params.push("--shutdown-grace-period".to_string());
+                params.push(format!("{}ms", grace_period.as_millis()));
+            }
+        }
+        params
+    }
+
+    pub fn monitoring_parameters(&self) -> Vec<String> {
+        let mut params = Vec::new();
+        if let Some(monitoring) = self.monitoring.as_ref() {
+            if let Some(metrics_addr) = monitoring.metrics_addr {
+                params.push("--metrics-addr".to_string());
+                params.push(metrics_addr.to_string());
+            }
+            if let Some(health_addr) = monitoring.health_addr {
+                params.push("--health-addr".to_string());
+                params.push(health_addr.to_string());
+            }
+            if let Some(unhealthy_after) = monitoring.unhealthy_after {
+                params.push("--health-unhealthy-after".to_string());
+                params.push(format!("{}ms", unhealthy_after.as_millis()));
+            }
+            if let Some(max_idle) = monitoring.max_idle {
+                params.push("--health-max-idle".to_string());
+                params.push(format!("{}ms", max_idle.as_millis()));
+            }
         }
         params
     }
+
     pub fn producer_parameters(&self) -> Vec<String> {
         let mut params = Vec::new();
         if let Some(producer) = self.producer.as_ref() {
@@ -125,6 +495,10 @@ impl ConnectorConfig {
     }
 }
 
+fn duration(duration: Duration) -> Value {
+    humantime::format_duration(duration).to_string().into()
+}
+
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Default, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -165,6 +539,77 @@ impl Deref for SecretString {
     }
 }
 
+const SECRET_FILE_PREFIX: &str = "file:";
+const SECRET_ENV_PREFIX: &str = "env:";
+const SECRET_KUBERNETES_PREFIX: &str = "secret:";
+const SECRET_LITERAL_PREFIX: &str = "literal:";
+
+/// Where the value of a secret comes from, depending on the prefix of its value
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SecretSource<'a> {
+    /// The value itself, without any known prefix or after the `literal:` one
+    Inline(&'a str),
+    /// `file:<path>`, the content of a file
+    File(&'a str),
+    /// `env:<NAME>`, an environment variable
+    Env(&'a str),
+    /// `secret:<name>` or `secret:<name>/<key>`, a key of a Kubernetes Secret.
+    /// The key defaults to the name of the secret in the connector config.
+    Kubernetes { name: &'a str, key: Option<&'a str> },
+}
+
+impl SecretString {
+    pub fn source(&self) -> SecretSource<'_> {
+        if let Some(value) = self.0.strip_prefix(SECRET_LITERAL_PREFIX) {
+            SecretSource::Inline(value)
+        } else if let Some(path) = self.0.strip_prefix(SECRET_FILE_PREFIX) {
+            SecretSource::File(path)
+        } else if let Some(var) = self.0.strip_prefix(SECRET_ENV_PREFIX) {
+            SecretSource::Env(var)
+        } else if let Some(reference) = self.0.strip_prefix(SECRET_KUBERNETES_PREFIX) {
+            match reference.split_once('/') {
+                Some((name, key)) => SecretSource::Kubernetes {
+                    name,
+                    key: Some(key),
+                },
+                None => SecretSource::Kubernetes {
+                    name: reference,
+                    key: None,
+                },
+            }
+        } else {
+            SecretSource::Inline(&self.0)
+        }
+    }
+
+    /// Reads the value of the secret named `name`. Kubernetes secrets can't be read
+    /// from here, they are resolved by Kubernetes when the connector is deployed.
+    pub fn resolve(&self, name: &str) -> Result<SecretString, ConnectorLoadError> {
+        match self.source() {
+            SecretSource::Inline(value) => Ok(Self(value.to_string())),
+            SecretSource::File(path) => std::fs::read_to_string(path)
+                // files written by editors and `echo` end with a newline
+                .map(|value| Self(value.trim_end_matches(['\r', '\n']).to_string()))
+                .map_err(|source| ConnectorLoadError::SecretFile {
+                    name: name.to_string(),
+                    path: path.to_string(),
+                    source,
+                }),
+            SecretSource::Env(var) => {
+                std::env::var(var)
+                    .map(Self)
+                    .map_err(|_| ConnectorLoadError::SecretEnv {
+                        name: name.to_string(),
+                        var: var.to_string(),
+                    })
+            }
+            SecretSource::Kubernetes { .. } => {
+                Err(ConnectorLoadError::SecretKubernetes(name.to_string()))
+            }
+        }
+    }
+}
+
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, Debug, PartialEq, Serialize)]
 #[serde(rename_all = "camelCase", untagged)]
@@ -172,6 +617,33 @@ pub enum ManagedConnectorParameterValue {
     Vec(Vec<String>),
     Map(BTreeMap<String, String>),
     String(String),
+    /// Lists and maps holding other lists or maps, e.g. a list of objects
+    Structured(Value),
+}
+
+impl ManagedConnectorParameterValue {
+    /// The parameter as JSON, with its structure
+    pub fn to_value(&self) -> Value {
+        match self {
+            Self::Vec(values) => values.clone().into(),
+            Self::Map(map) => map
+                .iter()
+                .map(|(key, value)| (key.clone(), Value::from(value.clone())))
+                .collect(),
+            Self::String(value) => value.clone().into(),
+            Self::Structured(value) => value.clone(),
+        }
+    }
+}
+
+/// Text of a scalar, in the form the parameters keep them
+fn scalar_text(value: &Value) -> Option<String> {
+    match value {
+        Value::String(text) => Some(text.clone()),
+        Value::Null => Some("null".to_string()),
+        Value::Bool(_) | Value::Number(_) => Some(value.to_string()),
+        Value::Array(_) | Value::Object(_) => None,
+    }
 }
 
 impl Default for ManagedConnectorParameterValue {
@@ -274,12 +746,21 @@ impl<'de> Visitor<'de> for ParameterValueVisitor {
     where
         M: MapAccess<'de>,
     {
-        let mut inner = BTreeMap::new();
-        while let Some((key, value)) = map.next_entry::<String, String>()? {
-            inner.insert(key.clone(), value.clone());
+        let mut inner = Map::new();
+        while let Some((key, value)) = map.next_entry::<String, Value>()? {
+            inner.insert(key, value);
         }
 
-        Ok(ManagedConnectorParameterValue::Map(inner))
+        let texts: Option<BTreeMap<String, String>> = inner
+            .iter()
+            .map(|(key, value)| Some((key.clone(), scalar_text(value)?)))
+            .collect();
+        match texts {
+            Some(texts) => Ok(ManagedConnectorParameterValue::Map(texts)),
+            None => Ok(ManagedConnectorParameterValue::Structured(Value::Object(
+                inner,
+            ))),
+        }
     }
 
     fn visit_seq<A>(self, mut seq: A) -> Result<Self::Value, A::Error>
@@ -287,10 +768,16 @@ impl<'de> Visitor<'de> for ParameterValueVisitor {
         A: SeqAccess<'de>,
     {
         let mut vec_inner = Vec::new();
-        while let Some(param) = seq.next_element::<String>()? {
+        while let Some(param) = seq.next_element::<Value>()? {
             vec_inner.push(param);
         }
-        Ok(ManagedConnectorParameterValue::Vec(vec_inner))
+
+        match vec_inner.iter().map(scalar_text).collect() {
+            Some(texts) => Ok(ManagedConnectorParameterValue::Vec(texts)),
+            None => Ok(ManagedConnectorParameterValue::Structured(Value::Array(
+                vec_inner,
+            ))),
+        }
     }
 }
 
@@ -339,6 +826,28 @@ mod tests {
             }),
             consumer: Some(ConsumerParameters {
                 partition: Some(10),
+                partitions: vec![11, 12],
+                all_partitions: false,
+                offset: Some(ConsumerOffset::Checkpoint),
+                offset_path: Some("/var/lib/fluvio/offsets.json".to_string()),
+                dead_letter_topic: Some("my-mqtt-dead-letter".to_string()),
+            }),
+            retry: Some(RetryParameters {
+                max_attempts: Some(10),
+                initial_backoff: Some(Duration::from_millis(200)),
+                max_backoff: Some(Duration::from_secs(60)),
+                jitter: Some(0.5),
+                circuit_breaker_threshold: Some(3),
+                circuit_breaker_reset: Some(Duration::from_secs(120)),
+            }),
+            shutdown: Some(ShutdownParameters {
+                grace_period: Some(Duration::from_secs(15)),
+            }),
+            monitoring: Some(MonitoringParameters {
+                metrics_addr: Some(SocketAddr::from(([0, 0, 0, 0], 9090))),
+                health_addr: Some(SocketAddr::from(([0, 0, 0, 0], 8080))),
+                unhealthy_after: Some(Duration::from_secs(120)),
+                max_idle: None,
             }),
             transforms: Some(
                 TransformationStep {
@@ -374,6 +883,9 @@ mod tests {
             secrets: BTreeMap::new(),
             producer: None,
             consumer: None,
+            retry: None,
+            shutdown: None,
+            monitoring: None,
             transforms: None,
         };
 
@@ -501,6 +1013,9 @@ mod tests {
             secrets: BTreeMap::new(),
             producer: None,
             consumer: None,
+            retry: None,
+            shutdown: None,
+            monitoring: None,
             transforms: None,
         };
 
@@ -562,4 +1077,88 @@ mod tests {
             BTreeMap::from([("regex".to_string(), "\\w".into())])
         );
     }
+
+    #[test]
+    fn test_secret_sources() {
+        //given
+        let secret = |value: &str| SecretString(value.to_string());
+
+        //then
+        assert_eq!(secret("plain").source(), SecretSource::Inline("plain"));
+        assert_eq!(
+            secret("literal:env:not-a-variable").source(),
+            SecretSource::Inline("env:not-a-variable")
+        );
+        assert_eq!(
+            secret("file:/run/secrets/token").source(),
+            SecretSource::File("/run/secrets/token")
+        );
+        assert_eq!(secret("env:TOKEN").source(), SecretSource::Env("TOKEN"));
+        assert_eq!(
+            secret("secret:mqtt").source(),
+            SecretSource::Kubernetes {
+                name: "mqtt",
+                key: None
+            }
+        );
+        assert_eq!(
+            secret("secret:mqtt/url").source(),
+            SecretSource::Kubernetes {
+                name: "mqtt",
+                key: Some("url")
+            }
+        );
+    }
+
+    #[test]
+    fn test_resolve_secrets() {
+        //given
+        let path = std::env::temp_dir().join("fluvio-connector-secret-test");
+        std::fs::write(&path, "from-file\n").unwrap();
+        std::env::set_var("FLUVIO_CONNECTOR_SECRET_TEST", "from-env");
+        let mut config = ConnectorConfig {
+            secrets: BTreeMap::from([
+                ("INLINE".to_string(), SecretString("inline".to_string())),
+                (
+                    "FILE".to_string(),
+                    SecretString(format!("file:{}", path.display())),
+                ),
+                (
+                    "ENV".to_string(),
+                    SecretString("env:FLUVIO_CONNECTOR_SECRET_TEST".to_string()),
+                ),
+            ]),
+            ..Default::default()
+        };
+
+        //when
+        let resolved = config.resolve_secrets().expect("resolved secrets");
+        config.secrets.insert(
+            "K8S".to_string(),
+            SecretString("secret:my-secret".to_string()),
+        );
+        let k8s_err = config.resolve_secrets().unwrap_err();
+
+        //then
+        assert_eq!(&*resolved["INLINE"], "inline");
+        assert_eq!(&*resolved["FILE"], "from-file");
+        assert_eq!(&*resolved["ENV"], "from-env");
+        assert!(matches!(k8s_err, ConnectorLoadError::SecretKubernetes(name) if name == "K8S"));
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_invalid_kubernetes_secret() {
+        //given
+        let config = ConnectorConfig {
+            secrets: BTreeMap::from([("K8S".to_string(), SecretString("secret:/key".to_string()))]),
+            ..Default::default()
+        };
+
+        //when
+        let res = config.validate_secrets();
+
+        //then
+        assert!(matches!(res, Err(ConnectorLoadError::SecretInvalid(name)) if name == "K8S"));
+    }
 }
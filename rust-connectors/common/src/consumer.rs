@@ -0,0 +1,80 @@
+use std::sync::Arc;
+
+use fluvio::metadata::topic::TopicSpec;
+use fluvio::metrics::ClientMetrics;
+use fluvio::{Fluvio, FluvioAdmin, PartitionConsumer};
+use tracing::{info, warn};
+
+/// Consumes a set of partitions of the same topic.
+///
+/// Every partition gets its own `PartitionConsumer`, so records keep their order
+/// within a partition while partitions are consumed concurrently.
+pub struct ConnectorConsumer {
+    topic: String,
+    consumers: Vec<(u32, PartitionConsumer)>,
+    metrics: Arc<ClientMetrics>,
+}
+
+impl ConnectorConsumer {
+    pub async fn connect(topic: &str, partitions: &[u32]) -> anyhow::Result<Self> {
+        let fluvio = Fluvio::connect().await?;
+        let mut consumers = Vec::with_capacity(partitions.len());
+        for partition in partitions {
+            consumers.push((
+                *partition,
+                fluvio.partition_consumer(topic, *partition).await?,
+            ));
+        }
+        info!(topic, ?partitions, "created partition consumers");
+        Ok(Self {
+            topic: topic.to_string(),
+            consumers,
+            metrics: fluvio.metrics(),
+        })
+    }
+
+    pub fn topic(&self) -> &str {
+        &self.topic
+    }
+
+    pub fn partitions(&self) -> Vec<u32> {
+        self.consumers
+            .iter()
+            .map(|(partition, _)| *partition)
+            .collect()
+    }
+
+    pub fn metrics(&self) -> Arc<ClientMetrics> {
+        self.metrics.clone()
+    }
+
+    /// Consumer of every partition, along with its partition
+    pub fn consumers(&self) -> &[(u32, PartitionConsumer)] {
+        &self.consumers
+    }
+
+    pub(crate) fn into_consumers(self) -> Vec<(u32, PartitionConsumer)> {
+        self.consumers
+    }
+}
+
+/// Returns every partition currently provisioned for the topic
+pub async fn topic_partitions(topic: &str) -> anyhow::Result<Vec<u32>> {
+    let admin = FluvioAdmin::connect().await?;
+    let topics = admin
+        .list::<TopicSpec, String>(vec![topic.to_string()])
+        .await?;
+    let partitions: Vec<u32> = topics
+        .iter()
+        .filter(|t| t.name == topic)
+        .flat_map(|t| t.status.replica_map.keys().copied())
+        .collect();
+    if partitions.is_empty() {
+        warn!(
+            topic,
+            "no partitions provisioned yet, falling back to partition 0"
+        );
+        return Ok(vec![0]);
+    }
+    Ok(partitions)
+}
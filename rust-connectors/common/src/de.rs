@@ -0,0 +1,184 @@
+//! Deserialization helpers for the connector options, to read the values of types that
+//! are parsed from their text form on the command line.
+//!
+//! They are used with `#[serde(deserialize_with = "...")]` on the fields of the
+//! options, and accept numbers and booleans as well as strings.
+
+use std::fmt::Display;
+use std::str::FromStr;
+use std::time::Duration;
+
+use serde::de::{self, Deserialize, Deserializer};
+use serde_json::Value;
+
+/// Parses the value with `FromStr`, like clap does for the command line
+pub fn from_str<'de, D, T>(deserializer: D) -> Result<T, D::Error>
+where
+    D: Deserializer<'de>,
+    T: FromStr,
+    T::Err: Display,
+{
+    let text = text(Value::deserialize(deserializer)?)?;
+    text.parse().map_err(de::Error::custom)
+}
+
+/// [`from_str`] for optional values, to be used along with `#[serde(default)]`
+pub fn option_from_str<'de, D, T>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: FromStr,
+    T::Err: Display,
+{
+    match Value::deserialize(deserializer)? {
+        Value::Null => Ok(None),
+        value => text(value)?.parse().map(Some).map_err(de::Error::custom),
+    }
+}
+
+/// [`from_str`] for repeatable values
+pub fn vec_from_str<'de, D, T>(deserializer: D) -> Result<Vec<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: FromStr,
+    T::Err: Display,
+{
+    Vec::<Value>::deserialize(deserializer)?
+        .into_iter()
+        .map(|value| text(value)?.parse().map_err(de::Error::custom))
+        .collect()
+}
+
+/// Repeatable values given as JSON objects on the command line, read from objects as
+/// well as from their text
+pub fn vec_json<'de, D, T>(deserializer: D) -> Result<Vec<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: FromStr,
+    T::Err: Display,
+{
+    Vec::<Value>::deserialize(deserializer)?
+        .into_iter()
+        .map(|value| {
+            match value {
+                Value::String(text) => text.parse(),
+                value => value.to_string().parse(),
+            }
+            .map_err(de::Error::custom)
+        })
+        .collect()
+}
+
+/// A duration such as `150ms` or `20s`
+pub fn duration<'de, D>(deserializer: D) -> Result<Duration, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let text = text(Value::deserialize(deserializer)?)?;
+    humantime::parse_duration(&text).map_err(de::Error::custom)
+}
+
+/// [`duration`] for optional values, to be used along with `#[serde(default)]`
+pub fn option_duration<'de, D>(deserializer: D) -> Result<Option<Duration>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    match Value::deserialize(deserializer)? {
+        Value::Null => Ok(None),
+        value => humantime::parse_duration(&text(value)?)
+            .map(Some)
+            .map_err(de::Error::custom),
+    }
+}
+
+/// Repeatable `key:value` options, given as `key:value` strings, maps or a list of both.
+/// Keys are kept as they are.
+pub fn key_values<'de, D>(deserializer: D) -> Result<Vec<String>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let mut key_values = Vec::new();
+    push_key_values(Value::deserialize(deserializer)?, &mut key_values)?;
+    Ok(key_values)
+}
+
+fn push_key_values<E: de::Error>(value: Value, key_values: &mut Vec<String>) -> Result<(), E> {
+    match value {
+        Value::Array(values) => {
+            for value in values {
+                push_key_values(value, key_values)?;
+            }
+        }
+        Value::Object(map) => {
+            for (key, value) in map {
+                key_values.push(format!("{key}:{}", text::<E>(value)?));
+            }
+        }
+        value => key_values.push(text(value)?),
+    }
+    Ok(())
+}
+
+fn text<E: de::Error>(value: Value) -> Result<String, E> {
+    match value {
+        Value::String(text) => Ok(text),
+        Value::Number(number) => Ok(number.to_string()),
+        Value::Bool(boolean) => Ok(boolean.to_string()),
+        value => Err(E::custom(format!("expected a string, found {value}"))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use serde::Deserialize;
+    use serde_json::json;
+
+    #[derive(Debug, Deserialize)]
+    struct Opts {
+        #[serde(deserialize_with = "from_str")]
+        port: u16,
+        #[serde(default, deserialize_with = "option_duration")]
+        timeout: Option<Duration>,
+        #[serde(default, deserialize_with = "key_values")]
+        options: Vec<String>,
+        #[serde(default, deserialize_with = "vec_json")]
+        endpoints: Vec<Value>,
+    }
+
+    #[test]
+    fn test_text_values() -> anyhow::Result<()> {
+        //given
+        let from_list = json!({"port": "8080", "timeout": "1m", "options": ["a.b:c:d"]});
+        let from_map = json!({
+            "port": 8080,
+            "options": [{"a.b": "c:d", "e_f": 1}, "g:h"],
+            "endpoints": [{"name": "a"}, r#"{"name": "b"}"#],
+        });
+
+        //when
+        let from_list: Opts = serde_json::from_value(from_list)?;
+        let from_map: Opts = serde_json::from_value(from_map)?;
+
+        //then
+        assert_eq!(from_list.port, 8080);
+        assert_eq!(from_list.timeout, Some(Duration::from_secs(60)));
+        assert_eq!(from_list.options, vec!["a.b:c:d".to_string()]);
+        assert_eq!(from_map.port, 8080);
+        assert_eq!(from_map.timeout, None);
+        assert_eq!(
+            from_map.options,
+            vec![
+                "a.b:c:d".to_string(),
+                "e_f:1".to_string(),
+                "g:h".to_string()
+            ]
+        );
+        assert!(from_list.endpoints.is_empty());
+        assert_eq!(
+            from_map.endpoints,
+            vec![json!({"name": "a"}), json!({"name": "b"})]
+        );
+        Ok(())
+    }
+}
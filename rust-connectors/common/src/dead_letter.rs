@@ -0,0 +1,199 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use base64::Engine;
+use fluvio::consumer::Record;
+use fluvio::{RecordKey, TopicProducer};
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+/// Envelope produced to the dead-letter topic for every record a sink failed to process,
+/// or every response a source could not produce
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DeadLetterRecord {
+    /// Why the record could not be processed
+    pub error: String,
+    /// Topic the record was consumed from, or for a source the topic it was meant for
+    pub topic: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub partition: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub offset: Option<i64>,
+    /// Timestamp of the original record
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timestamp: Option<i64>,
+    /// URL the response of a source was fetched from
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    /// Status of the response of a source
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub status: Option<u16>,
+    /// Milliseconds since UNIX epoch when the record was dead-lettered
+    pub failed_at: u64,
+    pub key: Option<DeadLetterBytes>,
+    pub value: DeadLetterBytes,
+}
+
+/// Key or value of the original record, as text when it is valid UTF-8 and base64
+/// encoded otherwise
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DeadLetterBytes {
+    pub encoding: DeadLetterEncoding,
+    pub data: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum DeadLetterEncoding {
+    Utf8,
+    Base64,
+}
+
+impl DeadLetterBytes {
+    pub fn new(bytes: &[u8]) -> Self {
+        match std::str::from_utf8(bytes) {
+            Ok(text) => Self {
+                encoding: DeadLetterEncoding::Utf8,
+                data: text.to_string(),
+            },
+            Err(_) => Self {
+                encoding: DeadLetterEncoding::Base64,
+                data: base64::engine::general_purpose::STANDARD.encode(bytes),
+            },
+        }
+    }
+
+    /// Bytes of the original record, to reprocess it
+    pub fn to_bytes(&self) -> anyhow::Result<Vec<u8>> {
+        match self.encoding {
+            DeadLetterEncoding::Utf8 => Ok(self.data.clone().into_bytes()),
+            DeadLetterEncoding::Base64 => {
+                Ok(base64::engine::general_purpose::STANDARD.decode(&self.data)?)
+            }
+        }
+    }
+}
+
+/// Milliseconds since UNIX epoch, for `failed_at`
+pub fn now_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_millis() as u64)
+}
+
+/// Produces records which failed to be processed to a separate Fluvio topic
+pub struct DeadLetterProducer {
+    source_topic: String,
+    dead_letter_topic: String,
+    producer: TopicProducer,
+}
+
+impl DeadLetterProducer {
+    pub fn new(source_topic: String, dead_letter_topic: String, producer: TopicProducer) -> Self {
+        Self {
+            source_topic,
+            dead_letter_topic,
+            producer,
+        }
+    }
+
+    /// Sends the original record along with the error reason to the dead-letter topic.
+    ///
+    /// The producer is flushed before returning so that the record is never lost,
+    /// even if the connector stops right after.
+    pub async fn send(&self, record: &Record, error: &anyhow::Error) -> anyhow::Result<()> {
+        warn!(
+            offset = record.offset,
+            partition = record.partition,
+            dead_letter_topic = %self.dead_letter_topic,
+            "sending record to dead-letter topic: {:?}",
+            error
+        );
+        let dead_letter = DeadLetterRecord {
+            error: format!("{error:#}"),
+            topic: self.source_topic.clone(),
+            partition: Some(record.partition),
+            offset: Some(record.offset),
+            timestamp: Some(record.timestamp()),
+            url: None,
+            status: None,
+            failed_at: now_millis(),
+            key: record.key().map(DeadLetterBytes::new),
+            value: DeadLetterBytes::new(record.value()),
+        };
+        self.send_record(&dead_letter).await
+    }
+
+    /// Sends `dead_letter` to the dead-letter topic, flushing the producer
+    pub async fn send_record(&self, dead_letter: &DeadLetterRecord) -> anyhow::Result<()> {
+        let value = serde_json::to_vec(dead_letter)?;
+        self.producer.send(RecordKey::NULL, value).await?;
+        self.producer.flush().await?;
+        Ok(())
+    }
+
+    /// Topic the records come from, or are meant for
+    pub fn source_topic(&self) -> &str {
+        &self.source_topic
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use pretty_assertions::assert_eq;
+
+    fn dead_letter(key: Option<&[u8]>, value: &[u8]) -> DeadLetterRecord {
+        DeadLetterRecord {
+            error: "payload is not valid JSON".to_string(),
+            topic: "my-topic".to_string(),
+            partition: Some(0),
+            offset: Some(42),
+            timestamp: Some(1668000000000),
+            url: None,
+            status: None,
+            failed_at: 1668000000123,
+            key: key.map(DeadLetterBytes::new),
+            value: DeadLetterBytes::new(value),
+        }
+    }
+
+    #[test]
+    fn test_text_record_serialization() -> anyhow::Result<()> {
+        //given
+        let record = dead_letter(Some(b"device-1"), br#"{"a":1"#);
+
+        //when
+        let json = serde_json::to_string(&record)?;
+        let deserialized: DeadLetterRecord = serde_json::from_str(&json)?;
+
+        //then
+        assert_eq!(
+            json,
+            r#"{"error":"payload is not valid JSON","topic":"my-topic","partition":0,"offset":42,"timestamp":1668000000000,"failed_at":1668000000123,"key":{"encoding":"utf8","data":"device-1"},"value":{"encoding":"utf8","data":"{\"a\":1"}}"#
+        );
+        assert_eq!(deserialized, record);
+        assert_eq!(deserialized.value.to_bytes()?, br#"{"a":1"#);
+        Ok(())
+    }
+
+    #[test]
+    fn test_binary_record_serialization() -> anyhow::Result<()> {
+        //given
+        let value = [0x89, b'P', b'N', b'G', 0xff];
+        let record = dead_letter(None, &value);
+
+        //when
+        let json = serde_json::to_value(&record)?;
+        let deserialized: DeadLetterRecord = serde_json::from_value(json.clone())?;
+
+        //then
+        assert_eq!(
+            json["value"],
+            serde_json::json!({"encoding": "base64", "data": "iVBOR/8="})
+        );
+        assert_eq!(json["key"], serde_json::Value::Null);
+        assert_eq!(deserialized.value.to_bytes()?, value);
+        Ok(())
+    }
+}
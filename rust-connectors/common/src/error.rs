@@ -4,6 +4,22 @@ pub enum ConnectorLoadError {
     Yaml(#[from] serde_yaml::Error),
     #[error("IO Error: {0}")]
     IoError(#[from] std::io::Error),
+    #[error("Invalid transform: {0}")]
+    Transform(#[from] serde_json::Error),
     #[error("ByteSize: {0}")]
     ByteSizeParse(String),
+    #[error("Secret {name}: unable to read {path}: {source}")]
+    SecretFile {
+        name: String,
+        path: String,
+        source: std::io::Error,
+    },
+    #[error("Secret {name}: environment variable {var} is not set")]
+    SecretEnv { name: String, var: String },
+    #[error("Secret {0}: Kubernetes secrets are only resolved when deployed to Kubernetes")]
+    SecretKubernetes(String),
+    #[error("Secret {0}: expected secret:<name> or secret:<name>/<key>")]
+    SecretInvalid(String),
+    #[error("Unknown option {0}")]
+    UnknownOption(String),
 }
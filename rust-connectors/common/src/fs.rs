@@ -0,0 +1,91 @@
+//! Files connectors keep their state in across restarts
+
+use std::fs::File;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+/// Replaces the contents of `path` with `contents` atomically: they are written to a
+/// temporary file next to it, synced to disk, then renamed over it. A crash leaves
+/// either the previous contents or the new ones, never a truncated file.
+pub async fn write_atomic(path: &Path, contents: Vec<u8>) -> io::Result<()> {
+    let path = path.to_path_buf();
+    tokio::task::spawn_blocking(move || write_atomic_blocking(&path, &contents))
+        .await
+        .map_err(io::Error::other)?
+}
+
+fn write_atomic_blocking(path: &Path, contents: &[u8]) -> io::Result<()> {
+    let tmp_path = tmp_path(path)?;
+    let written = File::create(&tmp_path).and_then(|mut file| {
+        file.write_all(contents)?;
+        file.sync_all()
+    });
+    if let Err(err) = written.and_then(|_| std::fs::rename(&tmp_path, path)) {
+        let _ = std::fs::remove_file(&tmp_path);
+        return Err(err);
+    }
+    sync_dir(path)
+}
+
+/// Unique temporary file in the directory of `path`, so files sharing a stem or
+/// connectors sharing a directory never write to the same one
+fn tmp_path(path: &Path) -> io::Result<PathBuf> {
+    let file_name = path.file_name().ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("{} is not a file path", path.display()),
+        )
+    })?;
+    let mut tmp_name = std::ffi::OsString::from(".");
+    tmp_name.push(file_name);
+    tmp_name.push(format!(
+        ".{}.{:016x}.tmp",
+        std::process::id(),
+        fastrand::u64(..)
+    ));
+    Ok(path.with_file_name(tmp_name))
+}
+
+/// Syncs the directory of `path`, so the rename survives a crash
+#[cfg(unix)]
+fn sync_dir(path: &Path) -> io::Result<()> {
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    File::open(dir)?.sync_all()
+}
+
+#[cfg(not(unix))]
+fn sync_dir(_path: &Path) -> io::Result<()> {
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_write_atomic() -> io::Result<()> {
+        //given
+        let dir = std::env::temp_dir().join(format!("fluvio-connector-fs-{}", std::process::id()));
+        std::fs::create_dir_all(&dir)?;
+        let state = dir.join("state.json");
+        let other_state = dir.join("state.tmp");
+
+        //when
+        write_atomic(&state, b"old".to_vec()).await?;
+        write_atomic(&state, b"new".to_vec()).await?;
+        write_atomic(&other_state, b"other".to_vec()).await?;
+        let missing_dir = write_atomic(&dir.join("missing/state.json"), Vec::new()).await;
+
+        //then
+        assert_eq!(std::fs::read(&state)?, b"new");
+        assert_eq!(std::fs::read(&other_state)?, b"other");
+        assert!(missing_dir.is_err());
+        assert_eq!(std::fs::read_dir(&dir)?.count(), 2);
+
+        std::fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+}
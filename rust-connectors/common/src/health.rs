@@ -0,0 +1,276 @@
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+
+use crate::retry::is_retryable;
+
+/// Tracks whether the connector is able to move records, to answer liveness and
+/// readiness probes.
+///
+/// The connector is ready once both its upstream and downstream systems are
+/// connected. It is no longer live when one of them stayed disconnected for longer
+/// than `unhealthy_after`, or when no record went through for longer than `max_idle`.
+///
+/// A connector reading from several endpoints tracks each of them on its own, see
+/// [`ConnectorHealth::endpoint`].
+#[derive(Debug)]
+pub struct ConnectorHealth {
+    unhealthy_after: Duration,
+    max_idle: Option<Duration>,
+    state: Mutex<HealthState>,
+    endpoints: Mutex<BTreeMap<String, Arc<ConnectorHealth>>>,
+}
+
+#[derive(Debug)]
+struct HealthState {
+    started_at: Instant,
+    upstream: Link,
+    downstream: Link,
+    last_record_at: Option<Instant>,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Link {
+    connected: bool,
+    since: Instant,
+}
+
+impl Link {
+    fn new(now: Instant) -> Self {
+        Self {
+            connected: false,
+            since: now,
+        }
+    }
+
+    fn set(&mut self, connected: bool) {
+        if self.connected != connected {
+            self.connected = connected;
+            self.since = Instant::now();
+        }
+    }
+
+    fn report(&self, now: Instant) -> LinkReport {
+        LinkReport {
+            connected: self.connected,
+            since_seconds: now.duration_since(self.since).as_secs(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct HealthReport {
+    pub live: bool,
+    pub ready: bool,
+    pub upstream: LinkReport,
+    pub downstream: LinkReport,
+    /// Seconds since the last record went through, if any did
+    pub last_record_seconds: Option<u64>,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub endpoints: BTreeMap<String, HealthReport>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct LinkReport {
+    pub connected: bool,
+    /// Seconds since the link got connected or disconnected
+    pub since_seconds: u64,
+}
+
+impl ConnectorHealth {
+    pub fn new(unhealthy_after: Duration, max_idle: Option<Duration>) -> Self {
+        let now = Instant::now();
+        Self {
+            unhealthy_after,
+            max_idle,
+            state: Mutex::new(HealthState {
+                started_at: now,
+                upstream: Link::new(now),
+                downstream: Link::new(now),
+                last_record_at: None,
+            }),
+            endpoints: Mutex::default(),
+        }
+    }
+
+    /// Health of one of the endpoints of a connector reading from several of them. The
+    /// connector is live and ready while one of its endpoints is, so an endpoint failing
+    /// doesn't take the others down.
+    pub fn endpoint(&self, endpoint: &str) -> Arc<ConnectorHealth> {
+        self.endpoints
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .entry(endpoint.to_string())
+            .or_insert_with(|| Arc::new(Self::new(self.unhealthy_after, self.max_idle)))
+            .clone()
+    }
+
+    fn state(&self) -> MutexGuard<'_, HealthState> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// The system records are read from: the external system for source connectors,
+    /// Fluvio for sink connectors.
+    pub fn set_upstream_connected(&self, connected: bool) {
+        self.state().upstream.set(connected);
+    }
+
+    /// The system records are written to: Fluvio for source connectors, the
+    /// external system for sink connectors.
+    pub fn set_downstream_connected(&self, connected: bool) {
+        self.state().downstream.set(connected);
+    }
+
+    /// A record went all the way through, so both sides are connected
+    pub fn record_succeeded(&self) {
+        let mut state = self.state();
+        state.upstream.set(true);
+        state.downstream.set(true);
+        state.last_record_at = Some(Instant::now());
+    }
+
+    /// A record could not be written. Errors marked as permanent are caused by the
+    /// record itself and leave the downstream system connected.
+    pub fn record_failed(&self, err: &anyhow::Error) {
+        if is_retryable(err) {
+            self.set_downstream_connected(false);
+        }
+    }
+
+    pub fn report(&self) -> HealthReport {
+        let now = Instant::now();
+        let state = self.state();
+        let stalled =
+            |link: &Link| !link.connected && now.duration_since(link.since) > self.unhealthy_after;
+        let idle = match self.max_idle {
+            Some(max_idle) => {
+                let last_record_at = state.last_record_at.unwrap_or(state.started_at);
+                now.duration_since(last_record_at) > max_idle
+            }
+            None => false,
+        };
+        let mut report = HealthReport {
+            live: !stalled(&state.upstream) && !stalled(&state.downstream) && !idle,
+            ready: state.upstream.connected && state.downstream.connected,
+            upstream: state.upstream.report(now),
+            downstream: state.downstream.report(now),
+            last_record_seconds: state
+                .last_record_at
+                .map(|last_record_at| now.duration_since(last_record_at).as_secs()),
+            endpoints: BTreeMap::new(),
+        };
+        drop(state);
+
+        report.endpoints = self
+            .endpoints
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .iter()
+            .map(|(endpoint, health)| (endpoint.clone(), health.report()))
+            .collect();
+        if !report.endpoints.is_empty() {
+            report.live = report.endpoints.values().any(|endpoint| endpoint.live);
+            report.ready = report.endpoints.values().any(|endpoint| endpoint.ready);
+        }
+        report
+    }
+}
+
+impl Default for ConnectorHealth {
+    fn default() -> Self {
+        Self::new(Duration::from_secs(5 * 60), None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ready_once_connected() {
+        //given
+        let health = ConnectorHealth::new(Duration::from_secs(60), None);
+        assert!(!health.report().ready);
+        assert!(health.report().live);
+
+        //when
+        health.set_upstream_connected(true);
+        let upstream_only = health.report();
+        health.set_downstream_connected(true);
+
+        //then
+        assert!(!upstream_only.ready);
+        let report = health.report();
+        assert!(report.ready);
+        assert!(report.live);
+        assert_eq!(report.last_record_seconds, None);
+    }
+
+    #[test]
+    fn test_not_live_when_disconnected_too_long() {
+        //given
+        let health = ConnectorHealth::new(Duration::from_millis(10), None);
+        health.record_succeeded();
+
+        //when
+        health.record_failed(&crate::retry::permanent(anyhow::anyhow!("malformed")));
+        std::thread::sleep(Duration::from_millis(20));
+        let after_permanent_error = health.report();
+        health.record_failed(&anyhow::anyhow!("unavailable"));
+        let just_disconnected = health.report();
+        std::thread::sleep(Duration::from_millis(20));
+
+        //then
+        assert!(after_permanent_error.live && after_permanent_error.ready);
+        assert!(just_disconnected.live && !just_disconnected.ready);
+        assert!(!health.report().live);
+        health.record_succeeded();
+        assert!(health.report().live);
+    }
+
+    #[test]
+    fn test_not_live_when_idle() {
+        //given
+        let health = ConnectorHealth::new(Duration::from_secs(60), Some(Duration::from_millis(10)));
+        health.record_succeeded();
+        assert!(health.report().live);
+
+        //when
+        std::thread::sleep(Duration::from_millis(20));
+
+        //then
+        let report = health.report();
+        assert!(!report.live);
+        assert!(report.ready);
+        assert_eq!(report.last_record_seconds, Some(0));
+    }
+
+    #[test]
+    fn test_endpoints_fail_independently() {
+        //given
+        let health = ConnectorHealth::new(Duration::from_millis(10), None);
+        let github = health.endpoint("github");
+        let status = health.endpoint("status");
+        github.record_succeeded();
+        status.record_succeeded();
+
+        //when
+        status.set_upstream_connected(false);
+        let one_disconnected = health.report();
+        std::thread::sleep(Duration::from_millis(20));
+        let one_stalled = health.report();
+        github.set_upstream_connected(false);
+        std::thread::sleep(Duration::from_millis(20));
+
+        //then
+        assert!(one_disconnected.live && one_disconnected.ready);
+        assert!(one_stalled.live && one_stalled.ready);
+        assert!(!one_stalled.endpoints["status"].live);
+        assert!(one_stalled.endpoints["github"].ready);
+        let report = health.report();
+        assert!(!report.live && !report.ready);
+        assert!(Arc::ptr_eq(&github, &health.endpoint("github")));
+    }
+}
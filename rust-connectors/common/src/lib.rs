@@ -6,13 +6,27 @@ pub mod fluvio {
 }
 
 pub mod config;
+#[cfg(feature = "sink")]
+pub mod consumer;
+pub mod de;
+#[cfg(any(feature = "source", feature = "sink"))]
+pub mod dead_letter;
 pub(crate) mod error;
+pub mod fs;
+#[cfg(any(feature = "source", feature = "sink"))]
+pub mod health;
+#[cfg(any(feature = "source", feature = "sink"))]
+mod loader;
 #[cfg(any(feature = "source", feature = "sink"))]
 pub mod metrics;
 #[cfg(all(any(feature = "source", feature = "sink"), not(target_os = "windows")))]
 pub mod monitoring;
+pub mod offset;
 #[cfg(any(feature = "source", feature = "sink"))]
 pub mod opt;
+pub mod retry;
+#[cfg(any(feature = "source", feature = "sink"))]
+pub mod shutdown;
 
 pub fn git_hash_version() -> &'static str {
     env!("GIT_HASH")
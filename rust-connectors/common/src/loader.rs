@@ -0,0 +1,490 @@
+//! Loading of the connector options from the command line, along with the connector
+//! config given with `--config`.
+//!
+//! The options of the config and of the command line are merged into a single JSON
+//! object keyed by the names of the option fields, which is deserialized into the
+//! options of the connector. Options given on the command line take precedence over
+//! the ones set through their environment variable, which take precedence over the
+//! ones of the config, while repeatable options accumulate the values of both.
+
+use std::ffi::OsStr;
+
+use clap::{Arg, ArgMatches, Command, ErrorKind, Parser, ValueSource};
+use schemars::schema::{InstanceType, Schema, SchemaObject, SingleOrVec};
+use schemars::{schema_for, JsonSchema};
+use serde::de::DeserializeOwned;
+use serde_json::{Map, Value};
+
+use crate::config::{ConnectorConfig, ConnectorLoadError, SecretSource};
+
+/// Field of the common options the resolved secrets of the config are kept in
+const SECRETS_FIELD: &str = "secrets";
+
+/// Parses the options of a connector from `args`, reading the connector config first
+/// if `--config` is given.
+pub(crate) fn load_opts<T>(args: Vec<String>) -> Result<T, clap::Error>
+where
+    T: Parser + DeserializeOwned + JsonSchema,
+{
+    let mut command = T::command();
+    let matches = match raw_command(&command).try_get_matches_from(&args) {
+        Ok(matches) => matches,
+        // help, version and invalid command lines are reported by clap as usual
+        Err(_) => return T::try_parse_from(args),
+    };
+    let config_path = match matches.get_raw("config").and_then(|mut path| path.next()) {
+        Some(path) => path.to_string_lossy().into_owned(),
+        None => return T::try_parse_from(args),
+    };
+    let options = ConnectorConfig::from_file(config_path)
+        .and_then(|config| merge_options(&command, &matches, &config))
+        .map_err(|err| command.error(ErrorKind::Io, err))?;
+    let schema = schema_for!(T);
+    let options = coerce(
+        Value::Object(options),
+        &Schema::Object(schema.schema),
+        &schema.definitions,
+    );
+    serde_json::from_value(options).map_err(|err| command.error(ErrorKind::ValueValidation, err))
+}
+
+/// Same options as `command`, without any validation, to read the command line before
+/// it is merged with the config.
+fn raw_command<'a>(command: &'a Command) -> Command<'a> {
+    let args = command.get_arguments().map(|arg| {
+        let mut raw = Arg::new(arg.get_id())
+            .takes_value(arg.is_takes_value_set())
+            .multiple_occurrences(arg.is_multiple_occurrences_set())
+            .allow_hyphen_values(arg.is_allow_hyphen_values_set())
+            .use_value_delimiter(arg.is_use_value_delimiter_set());
+        if let Some(long) = arg.get_long() {
+            raw = raw.long(long);
+        }
+        if let Some(short) = arg.get_short() {
+            raw = raw.short(short);
+        }
+        if let Some(aliases) = arg.get_all_aliases() {
+            raw = raw.aliases(&aliases);
+        }
+        if let Some(env) = arg.get_env().and_then(|env| env.to_str()) {
+            raw = raw.env(env);
+        }
+        raw
+    });
+    Command::new(command.get_name()).args(args)
+}
+
+/// Merges, for every option, the command line and environment values of `matches`,
+/// the values of the `config` and the defaults of the `command`.
+fn merge_options(
+    command: &Command,
+    matches: &ArgMatches,
+    config: &ConnectorConfig,
+) -> Result<Map<String, Value>, ConnectorLoadError> {
+    let mut config_options = config.options()?;
+    for (name, value) in &config.parameters {
+        let arg = find_arg(command, name)
+            .ok_or_else(|| ConnectorLoadError::UnknownOption(name.clone()))?;
+        config_options.insert(field_name(arg), value.to_value());
+    }
+
+    let mut secrets = Map::new();
+    for (name, value) in &config.secrets {
+        // Kubernetes secrets are exported to the connector by the deployment
+        if let SecretSource::Kubernetes { .. } = value.source() {
+            continue;
+        }
+        let value = value.resolve(name)?;
+        // secrets named after the environment variable of an option set it
+        if let Some(arg) = command
+            .get_arguments()
+            .find(|arg| arg.get_env() == Some(OsStr::new(name)))
+        {
+            config_options.insert(field_name(arg), Value::from(&*value));
+        }
+        secrets.insert(name.clone(), Value::from(&*value));
+    }
+
+    let mut options = Map::new();
+    for arg in command.get_arguments() {
+        let name = field_name(arg);
+        let command_line = given_value(matches, arg, ValueSource::CommandLine);
+        let config = config_options.remove(&name);
+        let value =
+            if arg.is_multiple_occurrences_set() && (command_line.is_some() || config.is_some()) {
+                let mut values = match config {
+                    Some(Value::Array(values)) => values,
+                    Some(value) => vec![value],
+                    None => Vec::new(),
+                };
+                if let Some(Value::Array(command_line)) = command_line {
+                    values.extend(command_line);
+                }
+                Some(Value::Array(values))
+            } else {
+                command_line
+                    .or_else(|| given_value(matches, arg, ValueSource::EnvVariable))
+                    .or(config)
+                    .or_else(|| default_value(arg))
+            };
+        if let Some(value) = value {
+            options.insert(name, value);
+        }
+    }
+    if let Some(name) = config_options.keys().next() {
+        return Err(ConnectorLoadError::UnknownOption(name.clone()));
+    }
+    options.insert(SECRETS_FIELD.to_string(), secrets.into());
+    Ok(options)
+}
+
+/// The option a parameter of the config stands for, by name or alias. Dashes and
+/// underscores are interchangeable.
+fn find_arg<'a, 'help>(command: &'a Command<'help>, name: &str) -> Option<&'a Arg<'help>> {
+    let name = name.replace('_', "-");
+    command.get_arguments().find(|arg| {
+        arg.get_id() == name
+            || arg.get_long() == Some(name.as_str())
+            || arg
+                .get_all_aliases()
+                .into_iter()
+                .flatten()
+                .any(|alias| alias == name)
+    })
+}
+
+fn field_name(arg: &Arg) -> String {
+    arg.get_id().replace('-', "_")
+}
+
+/// Value of `arg` in `matches` if it comes from `source`, as a list for repeatable options
+fn given_value(matches: &ArgMatches, arg: &Arg, source: ValueSource) -> Option<Value> {
+    if matches.value_source(arg.get_id()) != Some(source) {
+        return None;
+    }
+    if !arg.is_takes_value_set() {
+        return Some(Value::Bool(true));
+    }
+    let mut values = matches
+        .get_raw(arg.get_id())?
+        .map(|value| Value::String(value.to_string_lossy().into_owned()));
+    if arg.is_multiple_occurrences_set() {
+        Some(Value::Array(values.collect()))
+    } else {
+        values.next_back()
+    }
+}
+
+fn default_value(arg: &Arg) -> Option<Value> {
+    let mut defaults = arg
+        .get_default_values()
+        .iter()
+        .map(|value| Value::String(value.to_string_lossy().into_owned()));
+    if !arg.is_takes_value_set() {
+        Some(Value::Bool(false))
+    } else if arg.is_multiple_occurrences_set() {
+        Some(Value::Array(defaults.collect()))
+    } else {
+        defaults.next()
+    }
+}
+
+/// Converts the text of the command line and of the config into the JSON types the
+/// `schema` of the options expects, e.g. `"10"` into `10` for an integer option or
+/// a JSON object given on the command line into the object.
+fn coerce(value: Value, schema: &Schema, definitions: &schemars::Map<String, Schema>) -> Value {
+    let schemas = alternatives(schema, definitions);
+    let accepts = |instance_type: InstanceType| {
+        schemas.iter().any(|schema| match schema.instance_type {
+            Some(SingleOrVec::Single(ref single)) => **single == instance_type,
+            Some(SingleOrVec::Vec(ref types)) => types.contains(&instance_type),
+            None => false,
+        })
+    };
+    match value {
+        Value::String(text) if !accepts(InstanceType::String) => {
+            if accepts(InstanceType::Integer) {
+                if let Ok(integer) = text.parse::<i64>() {
+                    return integer.into();
+                }
+                if let Ok(integer) = text.parse::<u64>() {
+                    return integer.into();
+                }
+            }
+            if accepts(InstanceType::Number) {
+                if let Some(number) = text.parse().ok().and_then(serde_json::Number::from_f64) {
+                    return Value::Number(number);
+                }
+            }
+            if accepts(InstanceType::Boolean) {
+                if let Ok(boolean) = text.parse::<bool>() {
+                    return boolean.into();
+                }
+            }
+            if accepts(InstanceType::Object) || accepts(InstanceType::Array) {
+                if let Ok(value @ (Value::Object(_) | Value::Array(_))) =
+                    serde_json::from_str(&text)
+                {
+                    return coerce(value, schema, definitions);
+                }
+            }
+            Value::String(text)
+        }
+        Value::Array(values) => {
+            let items = schemas.iter().find_map(|schema| match schema.array {
+                Some(ref array) => match array.items {
+                    Some(SingleOrVec::Single(ref items)) => Some(&**items),
+                    _ => None,
+                },
+                None => None,
+            });
+            match items {
+                Some(items) => values
+                    .into_iter()
+                    .map(|value| coerce(value, items, definitions))
+                    .collect(),
+                None => Value::Array(values),
+            }
+        }
+        Value::Object(map) => {
+            let properties = schemas
+                .iter()
+                .filter_map(|schema| schema.object.as_ref())
+                .flat_map(|object| object.properties.iter())
+                .collect::<Vec<_>>();
+            map.into_iter()
+                .map(|(name, value)| {
+                    let value = match properties.iter().find(|(property, _)| **property == name) {
+                        Some((_, schema)) => coerce(value, schema, definitions),
+                        None => value,
+                    };
+                    (name, value)
+                })
+                .collect()
+        }
+        value => value,
+    }
+}
+
+/// Schemas a value of `schema` may follow, with references and combinations resolved
+fn alternatives<'a>(
+    schema: &'a Schema,
+    definitions: &'a schemars::Map<String, Schema>,
+) -> Vec<&'a SchemaObject> {
+    let schema = match schema {
+        Schema::Object(schema) => schema,
+        Schema::Bool(_) => return Vec::new(),
+    };
+    if let Some(ref reference) = schema.reference {
+        let name = reference.trim_start_matches("#/definitions/");
+        return match definitions.get(name) {
+            Some(definition) => alternatives(definition, definitions),
+            None => Vec::new(),
+        };
+    }
+    let mut schemas = vec![schema];
+    if let Some(ref subschemas) = schema.subschemas {
+        let combined = [&subschemas.all_of, &subschemas.any_of, &subschemas.one_of];
+        for subschema in combined.into_iter().flatten().flatten() {
+            schemas.extend(alternatives(subschema, definitions));
+        }
+    }
+    schemas
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::time::Duration;
+
+    use clap::CommandFactory;
+    use serde::Deserialize;
+
+    use crate::opt::CommonConnectorOpt;
+
+    /// Options of a sink connector, with the kinds of values connectors take
+    #[derive(Parser, Debug, JsonSchema, Deserialize)]
+    struct TestOpt {
+        #[clap(long)]
+        url: String,
+
+        #[clap(long, env = "FLUVIO_LOADER_TEST_TOKEN", hide_env_values = true)]
+        token: Option<String>,
+
+        #[clap(long, env = "FLUVIO_LOADER_TEST_REGION")]
+        region: Option<String>,
+
+        #[clap(long, default_value = "10")]
+        batch: u32,
+
+        #[clap(long)]
+        #[serde(deserialize_with = "crate::de::key_values")]
+        option: Vec<String>,
+
+        #[clap(long)]
+        mapping: Vec<Mapping>,
+
+        #[clap(flatten)]
+        #[schemars(flatten)]
+        #[serde(flatten)]
+        common: CommonConnectorOpt,
+    }
+
+    #[derive(Debug, JsonSchema, Deserialize, PartialEq, Eq)]
+    struct Mapping {
+        column: String,
+        pointer: String,
+        required: bool,
+    }
+
+    impl std::str::FromStr for Mapping {
+        type Err = serde_json::Error;
+
+        fn from_str(s: &str) -> Result<Self, Self::Err> {
+            serde_json::from_str(s)
+        }
+    }
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn test_load_config_file() -> anyhow::Result<()> {
+        //given
+        std::env::set_var("FLUVIO_LOADER_TEST_SECRET", "s3cr3t");
+        let args = args(&[
+            "test-sink",
+            "--config",
+            "test-data/connectors/test-sink.yaml",
+            "--batch=20",
+            "--option=socket.timeout.ms:1000",
+            "--mapping",
+            r#"{"column": "name", "pointer": "/name", "required": false}"#,
+        ]);
+
+        //when
+        let opts: TestOpt = load_opts(args)?;
+
+        //then
+        assert_eq!(opts.url, "https://example.com/api");
+        assert_eq!(opts.token.as_deref(), Some("s3cr3t"));
+        assert_eq!(opts.batch, 20);
+        assert_eq!(
+            opts.option,
+            vec![
+                "bootstrap.servers:localhost:9092".to_string(),
+                "linger_ms:10".to_string(),
+                "socket.timeout.ms:1000".to_string(),
+            ]
+        );
+        assert_eq!(
+            opts.mapping,
+            vec![
+                Mapping {
+                    column: "id".to_string(),
+                    pointer: "/id".to_string(),
+                    required: true,
+                },
+                Mapping {
+                    column: "name".to_string(),
+                    pointer: "/name".to_string(),
+                    required: false,
+                },
+            ]
+        );
+        assert_eq!(opts.common.fluvio_topic, "test-topic");
+        assert_eq!(
+            opts.common.retry_common.retry_initial_backoff,
+            Duration::from_millis(500)
+        );
+        assert_eq!(opts.common.retry_common.retry_max_attempts, 5);
+        assert_eq!(opts.common.shutdown_grace_period, Duration::from_secs(20));
+        assert_eq!(opts.common.transform_common.transform.len(), 1);
+        assert_eq!(
+            opts.common.secrets.get("API_TOKEN").map(|token| &**token),
+            Some("s3cr3t")
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_environment_over_config() -> anyhow::Result<()> {
+        //given
+        std::env::set_var("FLUVIO_LOADER_TEST_REGION", "eu-west-1");
+        let config = ConnectorConfig {
+            topic: "topic".to_string(),
+            parameters: [
+                ("url".to_string(), "https://example.com".into()),
+                ("region".to_string(), "us-east-1".into()),
+            ]
+            .into(),
+            ..Default::default()
+        };
+        let options = |args: &[&str]| {
+            merge_options(
+                &TestOpt::command(),
+                &raw_command(&TestOpt::command()).get_matches_from(args),
+                &config,
+            )
+        };
+
+        //when
+        let from_env = options(&["test-sink"])?;
+        let from_command_line = options(&["test-sink", "--region=ap-south-1"])?;
+
+        //then
+        assert_eq!(from_env["region"], "eu-west-1");
+        assert_eq!(from_env["url"], "https://example.com");
+        assert_eq!(from_command_line["region"], "ap-south-1");
+        Ok(())
+    }
+
+    #[test]
+    fn test_unknown_parameter() {
+        //given
+        let args = args(&[
+            "test-sink",
+            "--config=test-data/connectors/simple.yaml",
+            "--url=https://example.com",
+        ]);
+
+        //when
+        let opts = load_opts::<TestOpt>(args.clone());
+        let unknown = merge_options(
+            &TestOpt::command(),
+            &raw_command(&TestOpt::command()).get_matches_from(&args),
+            &ConnectorConfig {
+                parameters: [("not_an_option".to_string(), "value".into())].into(),
+                ..Default::default()
+            },
+        );
+
+        //then
+        assert_eq!(opts.expect("options").common.fluvio_topic, "my-mqtt");
+        assert!(
+            matches!(unknown, Err(ConnectorLoadError::UnknownOption(name)) if name == "not_an_option")
+        );
+    }
+
+    #[test]
+    fn test_no_config() -> anyhow::Result<()> {
+        //given
+        let args = args(&[
+            "test-sink",
+            "--url=https://example.com",
+            "--fluvio-topic=topic",
+        ]);
+
+        //when
+        let opts: TestOpt = load_opts(args.clone())?;
+        let missing_url = load_opts::<TestOpt>(args[..1].to_vec());
+
+        //then
+        assert_eq!(opts.url, "https://example.com");
+        assert_eq!(opts.batch, 10);
+        assert!(missing_url.is_err());
+        Ok(())
+    }
+}
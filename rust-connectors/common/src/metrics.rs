@@ -1,17 +1,661 @@
-use std::sync::Arc;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::atomic::{AtomicI64, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 use fluvio::metrics::ClientMetrics;
-use serde::Serialize;
+use serde::ser::SerializeMap;
+use serde::{Serialize, Serializer};
+use serde_json::Value;
 
-#[derive(Default, Debug, Serialize)]
+use crate::retry::is_retryable;
+
+/// Prefix of every exported metric name
+const METRIC_PREFIX: &str = "fluvio";
+
+/// Error kind of a failure to read from the source of the connector
+pub const UPSTREAM_ERROR: &str = "upstream";
+/// Error kind of a failure to write to the destination of the connector
+pub const DOWNSTREAM_ERROR: &str = "downstream";
+/// Error kind of a record which can't be parsed or converted
+pub const FORMAT_ERROR: &str = "format";
+
+/// Upper bounds, in seconds, of the latency histogram buckets
+const LATENCY_BUCKETS: [f64; 12] = [
+    0.001, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+];
+
+#[derive(Debug, Serialize)]
 pub struct ConnectorMetrics {
     #[serde(flatten)]
     fluvio_metrics: Arc<ClientMetrics>,
-    // We can add here more metrics specific to the connector
+    connector: Arc<ConnectorStats>,
+    #[serde(skip_serializing_if = "EndpointStats::is_empty")]
+    endpoints: Arc<EndpointStats>,
+    /// Stats of the endpoint these metrics are scoped to, see [`ConnectorMetrics::endpoint`]
+    #[serde(skip)]
+    endpoint: Option<Arc<ConnectorStats>>,
+    #[serde(skip)]
+    started_at: SystemTime,
+}
+
+impl Default for ConnectorMetrics {
+    fn default() -> Self {
+        Self::new(Arc::default())
+    }
 }
 
 impl ConnectorMetrics {
     pub fn new(fluvio_metrics: Arc<ClientMetrics>) -> Self {
-        Self { fluvio_metrics }
+        Self {
+            fluvio_metrics,
+            connector: Arc::default(),
+            endpoints: Arc::default(),
+            endpoint: None,
+            started_at: SystemTime::now(),
+        }
+    }
+
+    /// Metrics of one of the endpoints of a connector reading from several of them.
+    /// What is recorded through them is counted both for the endpoint and in the
+    /// connector totals.
+    pub fn endpoint(&self, endpoint: &str) -> Self {
+        Self {
+            fluvio_metrics: self.fluvio_metrics.clone(),
+            connector: self.connector.clone(),
+            endpoints: self.endpoints.clone(),
+            endpoint: Some(self.endpoints.get_or_insert(endpoint)),
+            started_at: self.started_at,
+        }
+    }
+
+    /// Totals of the connector, over all of its endpoints
+    pub fn connector(&self) -> &ConnectorStats {
+        &self.connector
+    }
+
+    pub fn endpoints(&self) -> &EndpointStats {
+        &self.endpoints
+    }
+
+    /// Stats the recorded values go to: the totals, and the endpoint if scoped to one
+    fn stats(&self) -> impl Iterator<Item = &ConnectorStats> {
+        std::iter::once(self.connector.as_ref()).chain(self.endpoint.as_deref())
+    }
+
+    /// A record was read from the source of the connector: the external system for
+    /// source connectors, the Fluvio topic for sink connectors.
+    pub fn record_read(&self, bytes: usize) {
+        for stats in self.stats() {
+            stats.records_read.inc();
+            stats.bytes_read.add(bytes as u64);
+        }
+    }
+
+    /// A record was written to the destination of the connector, `latency` being the
+    /// time it took including retries.
+    pub fn record_written(&self, bytes: usize, latency: Duration) {
+        for stats in self.stats() {
+            stats.records_written.inc();
+            stats.bytes_written.add(bytes as u64);
+            stats.write_latency.observe(latency);
+        }
+    }
+
+    /// A record was discarded without being written, e.g. because a queue was full
+    pub fn record_dropped(&self) {
+        for stats in self.stats() {
+            stats.records_dropped.inc();
+        }
+    }
+
+    /// Counts an error by kind, e.g. [`UPSTREAM_ERROR`]
+    pub fn error(&self, kind: &str) {
+        for stats in self.stats() {
+            stats.errors.inc(kind);
+        }
+    }
+
+    /// A record could not be written. Errors marked as permanent are caused by the
+    /// record itself and counted as [`FORMAT_ERROR`], the others as [`DOWNSTREAM_ERROR`].
+    pub fn record_failed(&self, err: &anyhow::Error) {
+        if is_retryable(err) {
+            self.error(DOWNSTREAM_ERROR);
+        } else {
+            self.error(FORMAT_ERROR);
+        }
+    }
+
+    /// Number of records read but not written yet, by the endpoint if scoped to one
+    pub fn set_queued_records(&self, queued: usize) {
+        let stats = self.endpoint.as_deref().unwrap_or(&self.connector);
+        stats.queued_records.set(queued as i64);
+    }
+
+    /// Encodes the metrics in the OpenMetrics text format.
+    ///
+    /// Every `ClientMetrics` value is exported as a counter named after its path,
+    /// e.g. `fluvio_producer_records_total`. The stats of each endpoint are exported
+    /// with an `endpoint` label, e.g. `fluvio_connector_endpoint_records_read_total`.
+    pub fn encode_openmetrics(&self) -> Result<String, serde_json::Error> {
+        let mut out = String::new();
+        let client_metrics = serde_json::to_value(self.fluvio_metrics.as_ref())?;
+        encode_counters(&mut out, METRIC_PREFIX, &client_metrics);
+        let prefix = format!("{METRIC_PREFIX}_connector");
+        ConnectorStats::encode(&mut out, &prefix, &[(String::new(), &self.connector)]);
+
+        let endpoints = self.endpoints.snapshot();
+        if !endpoints.is_empty() {
+            let series: Vec<_> = endpoints
+                .iter()
+                .map(|(endpoint, stats)| {
+                    let label = format!("endpoint=\"{}\"", escape_label_value(endpoint));
+                    (label, stats.as_ref())
+                })
+                .collect();
+            ConnectorStats::encode(&mut out, &format!("{prefix}_endpoint"), &series);
+        }
+
+        let started_at = self
+            .started_at
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs_f64();
+        let name = format!("{METRIC_PREFIX}_connector_start_time_seconds");
+        let _ = writeln!(out, "# TYPE {name} gauge");
+        let _ = writeln!(out, "# HELP {name} Time the connector was started at");
+        let _ = writeln!(out, "{name} {started_at}");
+
+        out.push_str("# EOF\n");
+        Ok(out)
+    }
+}
+
+/// One of the counters of [`ConnectorStats`]
+type CounterOf = fn(&ConnectorStats) -> &Counter;
+
+/// Metrics updated by the connector itself, as opposed to the ones of the Fluvio client
+#[derive(Debug, Default, Serialize)]
+pub struct ConnectorStats {
+    records_read: Counter,
+    records_written: Counter,
+    bytes_read: Counter,
+    bytes_written: Counter,
+    records_dropped: Counter,
+    queued_records: Gauge,
+    errors: LabeledCounter,
+    write_latency: Histogram,
+}
+
+impl ConnectorStats {
+    pub fn records_read(&self) -> u64 {
+        self.records_read.get()
+    }
+
+    pub fn records_written(&self) -> u64 {
+        self.records_written.get()
+    }
+
+    pub fn records_dropped(&self) -> u64 {
+        self.records_dropped.get()
+    }
+
+    pub fn errors(&self, kind: &str) -> u64 {
+        self.errors.get(kind)
+    }
+
+    /// Encodes each metric once for all the `series`, told apart by their labels,
+    /// e.g. `endpoint="github"`
+    fn encode(out: &mut String, prefix: &str, series: &[(String, &ConnectorStats)]) {
+        let name = |metric: &str| format!("{prefix}_{metric}");
+        let family = |out: &mut String, name: &str, kind: &str, help: &str| {
+            let _ = writeln!(out, "# TYPE {name} {kind}");
+            let _ = writeln!(out, "# HELP {name} {help}");
+        };
+
+        let counter = |out: &mut String, metric: &str, help: &str, counter: CounterOf| {
+            let name = name(metric);
+            family(out, &name, "counter", help);
+            for (labels, stats) in series {
+                counter(stats).encode(out, &name, labels);
+            }
+        };
+
+        counter(
+            out,
+            "records_read",
+            "Records read from the source of the connector",
+            |stats| &stats.records_read,
+        );
+        counter(
+            out,
+            "records_written",
+            "Records written to the destination of the connector",
+            |stats| &stats.records_written,
+        );
+        counter(out, "read_bytes", "Bytes of the records read", |stats| {
+            &stats.bytes_read
+        });
+        counter(
+            out,
+            "written_bytes",
+            "Bytes of the records written",
+            |stats| &stats.bytes_written,
+        );
+        counter(
+            out,
+            "records_dropped",
+            "Records discarded without being written",
+            |stats| &stats.records_dropped,
+        );
+
+        let queued_records = name("queued_records");
+        family(
+            out,
+            &queued_records,
+            "gauge",
+            "Records read but not written yet",
+        );
+        for (labels, stats) in series {
+            stats.queued_records.encode(out, &queued_records, labels);
+        }
+
+        let errors = name("errors");
+        family(out, &errors, "counter", "Errors by kind");
+        for (labels, stats) in series {
+            stats.errors.encode(out, &errors, labels, "kind");
+        }
+
+        let write_latency = name("write_latency_seconds");
+        family(
+            out,
+            &write_latency,
+            "histogram",
+            "Time taken to write a record, including retries",
+        );
+        for (labels, stats) in series {
+            stats.write_latency.encode(out, &write_latency, labels);
+        }
+    }
+}
+
+/// Stats of each endpoint of a connector reading from several of them
+#[derive(Debug, Default)]
+pub struct EndpointStats(Mutex<BTreeMap<String, Arc<ConnectorStats>>>);
+
+impl EndpointStats {
+    fn get_or_insert(&self, endpoint: &str) -> Arc<ConnectorStats> {
+        let mut endpoints = self.0.lock().unwrap_or_else(PoisonError::into_inner);
+        endpoints.entry(endpoint.to_string()).or_default().clone()
+    }
+
+    pub fn get(&self, endpoint: &str) -> Option<Arc<ConnectorStats>> {
+        let endpoints = self.0.lock().unwrap_or_else(PoisonError::into_inner);
+        endpoints.get(endpoint).cloned()
+    }
+
+    fn is_empty(&self) -> bool {
+        self.0
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .is_empty()
+    }
+
+    fn snapshot(&self) -> BTreeMap<String, Arc<ConnectorStats>> {
+        self.0
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+}
+
+impl Serialize for EndpointStats {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.snapshot().serialize(serializer)
+    }
+}
+
+/// Labels of a series, `{}` wrapped, or nothing if there are none
+fn labels(labels: &[&str]) -> String {
+    let labels: Vec<&str> = labels
+        .iter()
+        .copied()
+        .filter(|label| !label.is_empty())
+        .collect();
+    if labels.is_empty() {
+        String::new()
+    } else {
+        format!("{{{}}}", labels.join(","))
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct Counter(AtomicU64);
+
+impl Counter {
+    pub fn inc(&self) {
+        self.add(1);
+    }
+
+    pub fn add(&self, value: u64) {
+        self.0.fetch_add(value, Ordering::Relaxed);
+    }
+
+    pub fn get(&self) -> u64 {
+        self.0.load(Ordering::Relaxed)
+    }
+
+    fn encode(&self, out: &mut String, name: &str, series_labels: &str) {
+        let labels = labels(&[series_labels]);
+        let _ = writeln!(out, "{name}_total{labels} {}", self.get());
+    }
+}
+
+impl Serialize for Counter {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_u64(self.get())
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct Gauge(AtomicI64);
+
+impl Gauge {
+    pub fn set(&self, value: i64) {
+        self.0.store(value, Ordering::Relaxed);
+    }
+
+    pub fn get(&self) -> i64 {
+        self.0.load(Ordering::Relaxed)
+    }
+
+    fn encode(&self, out: &mut String, name: &str, series_labels: &str) {
+        let labels = labels(&[series_labels]);
+        let _ = writeln!(out, "{name}{labels} {}", self.get());
+    }
+}
+
+impl Serialize for Gauge {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_i64(self.get())
+    }
+}
+
+/// Counters sharing a name, told apart by the value of a label
+#[derive(Debug, Default)]
+pub struct LabeledCounter(Mutex<BTreeMap<String, u64>>);
+
+impl LabeledCounter {
+    pub fn inc(&self, label: &str) {
+        let mut counters = self.0.lock().unwrap_or_else(PoisonError::into_inner);
+        match counters.get_mut(label) {
+            Some(counter) => *counter += 1,
+            None => {
+                counters.insert(label.to_string(), 1);
+            }
+        }
+    }
+
+    pub fn get(&self, label: &str) -> u64 {
+        let counters = self.0.lock().unwrap_or_else(PoisonError::into_inner);
+        counters.get(label).copied().unwrap_or_default()
+    }
+
+    fn snapshot(&self) -> BTreeMap<String, u64> {
+        self.0
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+
+    fn encode(&self, out: &mut String, name: &str, series_labels: &str, label: &str) {
+        for (value, count) in self.snapshot() {
+            let value = format!("{label}=\"{}\"", escape_label_value(&value));
+            let labels = labels(&[series_labels, &value]);
+            let _ = writeln!(out, "{name}_total{labels} {count}");
+        }
+    }
+}
+
+impl Serialize for LabeledCounter {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.snapshot().serialize(serializer)
+    }
+}
+
+/// Distribution of durations over [`LATENCY_BUCKETS`]
+#[derive(Debug, Default)]
+pub struct Histogram {
+    /// Observations per bucket, the last one being for values above every bound
+    buckets: [AtomicU64; LATENCY_BUCKETS.len() + 1],
+    sum_micros: AtomicU64,
+    count: AtomicU64,
+}
+
+impl Histogram {
+    pub fn observe(&self, value: Duration) {
+        let seconds = value.as_secs_f64();
+        let bucket = LATENCY_BUCKETS
+            .iter()
+            .position(|bound| seconds <= *bound)
+            .unwrap_or(LATENCY_BUCKETS.len());
+        self.buckets[bucket].fetch_add(1, Ordering::Relaxed);
+        self.sum_micros
+            .fetch_add(value.as_micros() as u64, Ordering::Relaxed);
+        self.count.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn count(&self) -> u64 {
+        self.count.load(Ordering::Relaxed)
+    }
+
+    pub fn sum(&self) -> Duration {
+        Duration::from_micros(self.sum_micros.load(Ordering::Relaxed))
+    }
+
+    /// Cumulative count of observations less than or equal to each bound
+    fn cumulative_buckets(&self) -> Vec<(Option<f64>, u64)> {
+        let mut total = 0;
+        self.buckets
+            .iter()
+            .enumerate()
+            .map(|(i, bucket)| {
+                total += bucket.load(Ordering::Relaxed);
+                (LATENCY_BUCKETS.get(i).copied(), total)
+            })
+            .collect()
+    }
+
+    fn encode(&self, out: &mut String, name: &str, series_labels: &str) {
+        let buckets = self.cumulative_buckets();
+        for (bound, count) in buckets.iter() {
+            let bound = match bound {
+                Some(bound) => format!("le=\"{bound}\""),
+                None => "le=\"+Inf\"".to_string(),
+            };
+            let labels = labels(&[series_labels, &bound]);
+            let _ = writeln!(out, "{name}_bucket{labels} {count}");
+        }
+        let labels = labels(&[series_labels]);
+        let _ = writeln!(out, "{name}_sum{labels} {}", self.sum().as_secs_f64());
+        // read from the buckets so the count always matches the `+Inf` bucket
+        let count = buckets.last().map(|(_, count)| *count).unwrap_or_default();
+        let _ = writeln!(out, "{name}_count{labels} {count}");
+    }
+}
+
+impl Serialize for Histogram {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(Some(3))?;
+        let buckets: BTreeMap<String, u64> = self
+            .cumulative_buckets()
+            .into_iter()
+            .map(|(bound, count)| {
+                let bound = bound.map_or_else(|| "+Inf".to_string(), |bound| bound.to_string());
+                (bound, count)
+            })
+            .collect();
+        map.serialize_entry("buckets", &buckets)?;
+        map.serialize_entry("sum_seconds", &self.sum().as_secs_f64())?;
+        map.serialize_entry("count", &self.count())?;
+        map.end()
+    }
+}
+
+fn encode_counters(out: &mut String, name: &str, value: &Value) {
+    match value {
+        Value::Object(fields) => {
+            for (field, value) in fields {
+                encode_counters(out, &format!("{name}_{}", metric_name(field)), value);
+            }
+        }
+        Value::Number(number) => {
+            let _ = writeln!(out, "# TYPE {name} counter");
+            let _ = writeln!(out, "{name}_total {number}");
+        }
+        _ => {}
+    }
+}
+
+/// Replaces the characters which are not allowed in a metric name
+fn metric_name(field: &str) -> String {
+    field
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect()
+}
+
+fn escape_label_value(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encode_counters() {
+        //given
+        let value = serde_json::json!({
+            "producer": { "records": 3, "bytes": 120 },
+            "smart-module": { "invocations": 1 },
+            "name": "ignored",
+        });
+        let mut out = String::new();
+
+        //when
+        encode_counters(&mut out, METRIC_PREFIX, &value);
+
+        //then
+        assert!(out
+            .contains("# TYPE fluvio_producer_records counter\nfluvio_producer_records_total 3\n"));
+        assert!(out.contains("fluvio_producer_bytes_total 120\n"));
+        assert!(out.contains("fluvio_smart_module_invocations_total 1\n"));
+        assert!(!out.contains("ignored"));
+    }
+
+    #[test]
+    fn test_encode_openmetrics() {
+        let encoded = ConnectorMetrics::default()
+            .encode_openmetrics()
+            .expect("failed to encode metrics");
+        assert!(encoded.contains("# TYPE fluvio_connector_start_time_seconds gauge\n"));
+        assert!(encoded.ends_with("# EOF\n"));
+    }
+
+    #[test]
+    fn test_connector_metrics() {
+        //given
+        let metrics = ConnectorMetrics::default();
+
+        //when
+        metrics.record_read(10);
+        metrics.record_read(20);
+        metrics.record_written(10, Duration::from_millis(3));
+        metrics.record_written(20, Duration::from_secs(60));
+        metrics.record_dropped();
+        metrics.record_failed(&anyhow::anyhow!("unavailable"));
+        metrics.record_failed(&crate::retry::permanent(anyhow::anyhow!("malformed")));
+        metrics.error("format \"json\"");
+        metrics.set_queued_records(4);
+
+        //then
+        let stats = metrics.connector();
+        assert_eq!(stats.records_read(), 2);
+        assert_eq!(stats.records_written(), 2);
+        assert_eq!(stats.records_dropped(), 1);
+        assert_eq!(stats.errors(DOWNSTREAM_ERROR), 1);
+        assert_eq!(stats.errors(FORMAT_ERROR), 1);
+        assert_eq!(stats.errors("upstream"), 0);
+
+        let encoded = metrics
+            .encode_openmetrics()
+            .expect("failed to encode metrics");
+        assert!(encoded.contains("fluvio_connector_records_read_total 2\n"));
+        assert!(encoded.contains("fluvio_connector_read_bytes_total 30\n"));
+        assert!(encoded.contains("fluvio_connector_queued_records 4\n"));
+        assert!(encoded.contains("fluvio_connector_errors_total{kind=\"downstream\"} 1\n"));
+        assert!(encoded.contains("fluvio_connector_errors_total{kind=\"format \\\"json\\\"\"} 1\n"));
+        assert!(encoded.contains("fluvio_connector_write_latency_seconds_bucket{le=\"0.001\"} 0\n"));
+        assert!(encoded.contains("fluvio_connector_write_latency_seconds_bucket{le=\"0.005\"} 1\n"));
+        assert!(encoded.contains("fluvio_connector_write_latency_seconds_bucket{le=\"10\"} 1\n"));
+        assert!(encoded.contains("fluvio_connector_write_latency_seconds_bucket{le=\"+Inf\"} 2\n"));
+        assert!(encoded.contains("fluvio_connector_write_latency_seconds_count 2\n"));
+
+        let json = serde_json::to_value(&metrics).expect("failed to serialize metrics");
+        assert_eq!(json["connector"]["records_written"], 2);
+        assert_eq!(json["connector"]["errors"]["downstream"], 1);
+        assert_eq!(json["connector"]["write_latency"]["buckets"]["+Inf"], 2);
+    }
+
+    #[test]
+    fn test_endpoint_metrics() {
+        //given
+        let metrics = ConnectorMetrics::default();
+        let github = metrics.endpoint("github");
+        let status = metrics.endpoint("status");
+
+        //when
+        github.record_read(10);
+        github.record_written(10, Duration::from_millis(3));
+        status.record_read(5);
+        status.error(UPSTREAM_ERROR);
+        metrics.endpoint("github").record_dropped();
+
+        //then
+        assert_eq!(metrics.connector().records_read(), 2);
+        assert_eq!(metrics.connector().errors(UPSTREAM_ERROR), 1);
+        let github_stats = metrics.endpoints().get("github").expect("no github stats");
+        assert_eq!(github_stats.records_read(), 1);
+        assert_eq!(github_stats.records_dropped(), 1);
+        assert_eq!(github_stats.errors(UPSTREAM_ERROR), 0);
+
+        let encoded = metrics
+            .encode_openmetrics()
+            .expect("failed to encode metrics");
+        assert!(encoded.contains("fluvio_connector_records_read_total 2\n"));
+        assert!(encoded.contains(
+            "# TYPE fluvio_connector_endpoint_records_read counter\n\
+             # HELP fluvio_connector_endpoint_records_read Records read from the source of the connector\n\
+             fluvio_connector_endpoint_records_read_total{endpoint=\"github\"} 1\n\
+             fluvio_connector_endpoint_records_read_total{endpoint=\"status\"} 1\n"
+        ));
+        assert!(encoded.contains(
+            "fluvio_connector_endpoint_errors_total{endpoint=\"status\",kind=\"upstream\"} 1\n"
+        ));
+        assert!(encoded.contains(
+            "fluvio_connector_endpoint_write_latency_seconds_bucket{endpoint=\"github\",le=\"0.005\"} 1\n"
+        ));
+        assert!(encoded.contains(
+            "fluvio_connector_endpoint_write_latency_seconds_count{endpoint=\"status\"} 0\n"
+        ));
+
+        let json = serde_json::to_value(&metrics).expect("failed to serialize metrics");
+        assert_eq!(json["endpoints"]["github"]["records_written"], 1);
+        assert!(serde_json::to_value(ConnectorMetrics::default()).unwrap()["endpoints"].is_null());
     }
 }
@@ -1,14 +1,30 @@
-use std::{io::Error as IoError, sync::Arc};
+use std::io::Error as IoError;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex, PoisonError};
 
 use async_net::unix::UnixListener;
-use futures_util::{AsyncWriteExt, StreamExt};
+use async_net::{TcpListener, TcpStream};
+use futures_util::{AsyncReadExt, AsyncWriteExt, StreamExt};
 
 use fluvio_future::task::spawn;
-use tracing::{error, info, trace};
+use tracing::{debug, error, info, trace};
 
+use crate::config::{LIVENESS_PATH, READINESS_PATH};
+use crate::health::ConnectorHealth;
 use crate::metrics::ConnectorMetrics;
 
 const SOCKET_PATH: &str = "/tmp/fluvio-connector.sock";
+const METRICS_PATH: &str = "/metrics";
+const OPENMETRICS_CONTENT_TYPE: &str = "application/openmetrics-text; version=1.0.0; charset=utf-8";
+/// Upper bound of the request head read from a scraper
+const MAX_REQUEST_SIZE: usize = 8 * 1024;
+
+/// Status line, content type and body of an HTTP response
+type HttpResponse = (&'static str, &'static str, String);
+
+/// Metrics served by the HTTP endpoint. Connectors which create a new producer on
+/// reconnect replace them, the endpoint itself is only started once.
+static ENDPOINT_METRICS: Mutex<Option<Arc<ConnectorMetrics>>> = Mutex::new(None);
 
 pub fn init_monitoring(metrics: Arc<ConnectorMetrics>) {
     spawn(async move {
@@ -57,3 +73,122 @@ async fn start_monitoring(metrics: Arc<ConnectorMetrics>) -> Result<(), IoError>
 
     Ok(())
 }
+
+/// Serves the metrics in the OpenMetrics text format on `http://<addr>/metrics`
+pub fn init_metrics_endpoint(addr: SocketAddr, metrics: Arc<ConnectorMetrics>) {
+    let previous = ENDPOINT_METRICS
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .replace(metrics);
+    if previous.is_some() {
+        return;
+    }
+    spawn(async move {
+        if let Err(err) = start_http_endpoint(addr, "metrics", metrics_response).await {
+            error!("error running metrics endpoint: {}", err);
+        }
+    });
+}
+
+/// Serves the liveness and readiness of the connector on `http://<addr>/live` and
+/// `http://<addr>/ready`. Both answer 503 when failing, along with the health report.
+pub fn init_health_endpoint(addr: SocketAddr, health: Arc<ConnectorHealth>) {
+    spawn(async move {
+        let handler = move |path: &str| health_response(&health, path);
+        if let Err(err) = start_http_endpoint(addr, "health", handler).await {
+            error!("error running health endpoint: {}", err);
+        }
+    });
+}
+
+fn metrics_response(path: &str) -> Result<Option<HttpResponse>, IoError> {
+    if path != METRICS_PATH {
+        return Ok(None);
+    }
+    let metrics = ENDPOINT_METRICS
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .clone();
+    let body = match metrics {
+        Some(metrics) => metrics.encode_openmetrics()?,
+        None => "# EOF\n".to_string(),
+    };
+    Ok(Some(("200 OK", OPENMETRICS_CONTENT_TYPE, body)))
+}
+
+fn health_response(health: &ConnectorHealth, path: &str) -> Result<Option<HttpResponse>, IoError> {
+    let report = health.report();
+    let healthy = match path {
+        LIVENESS_PATH => report.live,
+        READINESS_PATH => report.ready,
+        _ => return Ok(None),
+    };
+    let status = if healthy {
+        "200 OK"
+    } else {
+        "503 Service Unavailable"
+    };
+    let body = serde_json::to_string(&report)?;
+    Ok(Some((status, "application/json", body)))
+}
+
+/// Answers GET requests with the response of `handler` for the requested path,
+/// or 404 if it has none.
+async fn start_http_endpoint<H>(addr: SocketAddr, name: &str, handler: H) -> Result<(), IoError>
+where
+    H: Fn(&str) -> Result<Option<HttpResponse>, IoError> + Clone + Send + Sync + 'static,
+{
+    let listener = TcpListener::bind(addr).await?;
+    info!(%addr, "{} endpoint started", name);
+
+    let mut incoming = listener.incoming();
+    while let Some(stream) = incoming.next().await {
+        let stream = stream?;
+        let handler = handler.clone();
+        spawn(async move {
+            if let Err(err) = serve_http(stream, handler).await {
+                debug!("error serving http request: {}", err);
+            }
+        });
+    }
+
+    Ok(())
+}
+
+async fn serve_http<H>(mut stream: TcpStream, handler: H) -> Result<(), IoError>
+where
+    H: Fn(&str) -> Result<Option<HttpResponse>, IoError>,
+{
+    let mut request = Vec::new();
+    let mut buf = [0u8; 1024];
+    while !request.windows(4).any(|window| window == b"\r\n\r\n") {
+        let read = stream.read(&mut buf).await?;
+        if read == 0 || request.len() + read > MAX_REQUEST_SIZE {
+            return Ok(());
+        }
+        request.extend_from_slice(&buf[..read]);
+    }
+
+    let request = String::from_utf8_lossy(&request);
+    let mut request_line = request.lines().next().unwrap_or_default().split(' ');
+    let method = request_line.next().unwrap_or_default();
+    let path = request_line.next().unwrap_or_default();
+
+    let (status, content_type, body) = match method {
+        "GET" => {
+            handler(path)?.unwrap_or(("404 Not Found", "text/plain", "not found\n".to_string()))
+        }
+        _ => (
+            "405 Method Not Allowed",
+            "text/plain",
+            "method not allowed\n".to_string(),
+        ),
+    };
+
+    let response = format!(
+        "HTTP/1.1 {status}\r\nContent-Type: {content_type}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+        body.len()
+    );
+    stream.write_all(response.as_bytes()).await?;
+    stream.flush().await
+}
@@ -0,0 +1,254 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use async_trait::async_trait;
+use fluvio::Offset;
+use serde::de::{self, Visitor};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use tracing::{debug, info};
+
+use crate::fs::write_atomic;
+
+/// Where a sink connector starts consuming from
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ConsumerOffset {
+    /// The first record available in the partition
+    Beginning,
+    /// Only records produced after the connector started
+    #[default]
+    End,
+    /// An absolute offset in the partition
+    Absolute(i64),
+    /// The record following the last committed offset, or the beginning if nothing was committed
+    Checkpoint,
+}
+
+impl ConsumerOffset {
+    /// Translates into a fluvio offset, given the last committed offset for the partition
+    pub fn resolve(&self, committed: Option<i64>) -> anyhow::Result<Offset> {
+        let offset = match self {
+            Self::Beginning => Offset::beginning(),
+            Self::End => Offset::end(),
+            Self::Absolute(offset) => Offset::absolute(*offset)?,
+            Self::Checkpoint => match committed {
+                Some(offset) => Offset::absolute(offset + 1)?,
+                None => Offset::beginning(),
+            },
+        };
+        Ok(offset)
+    }
+}
+
+impl FromStr for ConsumerOffset {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "beginning" => Ok(Self::Beginning),
+            "end" => Ok(Self::End),
+            "checkpoint" => Ok(Self::Checkpoint),
+            other => other
+                .parse::<i64>()
+                .map(Self::Absolute)
+                .map_err(|_| format!("invalid consumer offset `{other}`")),
+        }
+    }
+}
+
+impl fmt::Display for ConsumerOffset {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Beginning => f.write_str("beginning"),
+            Self::End => f.write_str("end"),
+            Self::Absolute(offset) => write!(f, "{offset}"),
+            Self::Checkpoint => f.write_str("checkpoint"),
+        }
+    }
+}
+
+impl Serialize for ConsumerOffset {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match self {
+            Self::Absolute(offset) => serializer.serialize_i64(*offset),
+            other => serializer.serialize_str(&other.to_string()),
+        }
+    }
+}
+
+struct ConsumerOffsetVisitor;
+impl<'de> Deserialize<'de> for ConsumerOffset {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_any(ConsumerOffsetVisitor)
+    }
+}
+
+impl<'de> Visitor<'de> for ConsumerOffsetVisitor {
+    type Value = ConsumerOffset;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("`beginning`, `end`, `checkpoint` or an absolute offset")
+    }
+
+    fn visit_i64<E>(self, v: i64) -> Result<Self::Value, E>
+    where
+        E: de::Error,
+    {
+        Ok(ConsumerOffset::Absolute(v))
+    }
+
+    fn visit_u64<E>(self, v: u64) -> Result<Self::Value, E>
+    where
+        E: de::Error,
+    {
+        i64::try_from(v)
+            .map(ConsumerOffset::Absolute)
+            .map_err(|_| E::custom(format!("offset {v} is out of range")))
+    }
+
+    fn visit_str<E>(self, v: &str) -> Result<Self::Value, E>
+    where
+        E: de::Error,
+    {
+        v.parse().map_err(E::custom)
+    }
+}
+
+/// Durable storage for the offsets a sink connector has processed
+#[async_trait]
+pub trait OffsetStore: Send {
+    /// Returns the last committed offset for the given topic partition, if any
+    async fn load(&mut self, topic: &str, partition: u32) -> anyhow::Result<Option<i64>>;
+
+    /// Records that every record up to and including `offset` has been processed
+    async fn commit(&mut self, topic: &str, partition: u32, offset: i64) -> anyhow::Result<()>;
+}
+
+/// Offset store used when no checkpoint location is configured. Nothing is persisted.
+#[derive(Debug, Default)]
+pub struct NoopOffsetStore;
+
+#[async_trait]
+impl OffsetStore for NoopOffsetStore {
+    async fn load(&mut self, _topic: &str, _partition: u32) -> anyhow::Result<Option<i64>> {
+        Ok(None)
+    }
+
+    async fn commit(&mut self, _topic: &str, _partition: u32, _offset: i64) -> anyhow::Result<()> {
+        Ok(())
+    }
+}
+
+/// Keeps committed offsets in a local JSON file, grouped by topic and partition. The
+/// file is replaced atomically on every commit.
+#[derive(Debug)]
+pub struct FileOffsetStore {
+    path: PathBuf,
+    offsets: BTreeMap<String, BTreeMap<u32, i64>>,
+}
+
+impl FileOffsetStore {
+    pub fn open<P: Into<PathBuf>>(path: P) -> anyhow::Result<Self> {
+        let path = path.into();
+        let offsets = match std::fs::read(&path) {
+            Ok(contents) => serde_json::from_slice(&contents)?,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+                info!(
+                    "offset file {} not found, starting without checkpoints",
+                    path.display()
+                );
+                BTreeMap::new()
+            }
+            Err(err) => return Err(err.into()),
+        };
+        debug!(?offsets, "loaded offsets from {}", path.display());
+        Ok(Self { path, offsets })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    async fn persist(&self) -> anyhow::Result<()> {
+        write_atomic(&self.path, serde_json::to_vec(&self.offsets)?).await?;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl OffsetStore for FileOffsetStore {
+    async fn load(&mut self, topic: &str, partition: u32) -> anyhow::Result<Option<i64>> {
+        Ok(self
+            .offsets
+            .get(topic)
+            .and_then(|partitions| partitions.get(&partition))
+            .copied())
+    }
+
+    async fn commit(&mut self, topic: &str, partition: u32, offset: i64) -> anyhow::Result<()> {
+        self.offsets
+            .entry(topic.to_string())
+            .or_default()
+            .insert(partition, offset);
+        self.persist().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_consumer_offset_parse() {
+        assert_eq!("beginning".parse(), Ok(ConsumerOffset::Beginning));
+        assert_eq!("end".parse(), Ok(ConsumerOffset::End));
+        assert_eq!("checkpoint".parse(), Ok(ConsumerOffset::Checkpoint));
+        assert_eq!("42".parse(), Ok(ConsumerOffset::Absolute(42)));
+        assert!("latest".parse::<ConsumerOffset>().is_err());
+
+        assert_eq!(ConsumerOffset::Absolute(42).to_string(), "42");
+        assert_eq!(ConsumerOffset::Checkpoint.to_string(), "checkpoint");
+    }
+
+    #[test]
+    fn test_consumer_offset_deserialize() {
+        let offset: ConsumerOffset = serde_yaml::from_str("10").expect("absolute offset");
+        assert_eq!(offset, ConsumerOffset::Absolute(10));
+        let offset: ConsumerOffset = serde_yaml::from_str("checkpoint").expect("named offset");
+        assert_eq!(offset, ConsumerOffset::Checkpoint);
+        assert!(serde_yaml::from_str::<ConsumerOffset>("somewhere").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_file_offset_store() -> anyhow::Result<()> {
+        //given
+        let path = std::env::temp_dir().join(format!(
+            "fluvio-connector-offsets-{}.json",
+            std::process::id()
+        ));
+        let _ = std::fs::remove_file(&path);
+
+        //when
+        let mut store = FileOffsetStore::open(&path)?;
+        assert_eq!(store.load("topic", 0).await?, None);
+        store.commit("topic", 0, 10).await?;
+        store.commit("topic", 1, 20).await?;
+        store.commit("topic", 0, 11).await?;
+
+        //then
+        let mut reopened = FileOffsetStore::open(&path)?;
+        assert_eq!(reopened.load("topic", 0).await?, Some(11));
+        assert_eq!(reopened.load("topic", 1).await?, Some(20));
+        assert_eq!(reopened.load("other", 0).await?, None);
+
+        std::fs::remove_file(&path)?;
+        Ok(())
+    }
+}
@@ -3,18 +3,49 @@ use clap::{AppSettings, Parser};
 use fluvio_sc_schema::topic::TopicSpec;
 use humantime::parse_duration;
 use schemars::{schema_for, JsonSchema};
+use std::net::SocketAddr;
+use std::path::PathBuf;
 use std::str::FromStr;
+use std::sync::Arc;
 use std::{collections::BTreeMap, time::Duration};
 
 use fluvio::{
-    Compression, FluvioConfig, PartitionConsumer, SmartModuleContextData, SmartModuleExtraParams,
+    Compression, FluvioConfig, SmartModuleContextData, SmartModuleExtraParams,
     SmartModuleInvocation, SmartModuleInvocationWasm, SmartModuleKind,
 };
+use serde::de::{self, DeserializeOwned, Deserializer};
 use serde::Deserialize;
 
-#[derive(Parser, Debug, JsonSchema, Clone, Default)]
+use crate::config::SecretString;
+#[cfg(feature = "sink")]
+use crate::consumer::{topic_partitions, ConnectorConsumer};
+use crate::dead_letter::DeadLetterProducer;
+use crate::health::ConnectorHealth;
+use crate::offset::ConsumerOffset;
+#[cfg(feature = "sink")]
+use crate::offset::{FileOffsetStore, NoopOffsetStore, OffsetStore};
+use crate::retry::{CircuitBreaker, Retrier, RetryPolicy};
+use crate::shutdown::ShutdownSignal;
+#[cfg(not(target_os = "windows"))]
+use crate::{metrics::ConnectorMetrics, monitoring};
+
+#[cfg(feature = "sink")]
+pub type ConsumerStream = std::pin::Pin<
+    Box<
+        dyn tokio_stream::Stream<
+            Item = Result<fluvio::consumer::Record, fluvio_protocol::link::ErrorCode>,
+        >,
+    >,
+>;
+
+#[derive(Parser, Debug, JsonSchema, Clone, Default, Deserialize)]
 #[clap(settings = &[AppSettings::DeriveDisplayOrder])]
 pub struct CommonConnectorOpt {
+    /// Connector config (YAML) to read the options from. Options also given on the
+    /// command line take precedence over the ones of the config.
+    #[clap(long)]
+    pub config: Option<PathBuf>,
+
     /// The topic where this connector sends or receives records
     #[clap(long)]
     #[schemars(skip)]
@@ -26,43 +57,235 @@ pub struct CommonConnectorOpt {
     #[clap(long)]
     pub rust_log: Option<String>,
 
+    /// Time given to the connector to flush pending records and commit offsets
+    /// after receiving SIGTERM or SIGINT, before it exits anyway.
+    /// Ex: '150ms', '20s'
+    #[clap(long, default_value = "20s", parse(try_from_str = parse_duration))]
+    #[serde(deserialize_with = "crate::de::duration")]
+    pub shutdown_grace_period: Duration,
+
+    /// Address to serve the connector metrics on, in the OpenMetrics text format,
+    /// at the `/metrics` path.
+    /// Ex: '0.0.0.0:9090'
+    #[clap(long)]
+    pub metrics_addr: Option<SocketAddr>,
+
+    /// Topic where records that failed to be processed are sent to, along with the
+    /// error reason. If it is not defined, failed records are only logged.
+    #[clap(long)]
+    pub dead_letter_topic: Option<String>,
+
     #[cfg(feature = "sink")]
     #[clap(flatten)]
     #[schemars(flatten)]
+    #[serde(flatten)]
     pub consumer_common: CommonConsumerOpt,
 
     #[cfg(feature = "source")]
     #[clap(flatten)]
     #[schemars(flatten)]
+    #[serde(flatten)]
     pub producer_common: CommonProducerOpt,
 
     #[clap(flatten)]
     #[schemars(flatten)]
+    #[serde(flatten)]
     pub transform_common: CommonTransformOpt,
+
+    #[clap(flatten)]
+    #[schemars(flatten)]
+    #[serde(flatten)]
+    pub retry_common: CommonRetryOpt,
+
+    #[clap(flatten)]
+    #[schemars(flatten)]
+    #[serde(flatten)]
+    pub health_common: CommonHealthOpt,
+
+    /// Secrets of the connector config, by name. Templated options read them before
+    /// the environment variables.
+    #[clap(skip)]
+    #[schemars(skip)]
+    #[serde(default)]
+    pub secrets: BTreeMap<String, SecretString>,
 }
 
-#[derive(Parser, Debug, JsonSchema, Clone, Default)]
+#[derive(Parser, Debug, JsonSchema, Clone, Default, Deserialize)]
 pub struct CommonConsumerOpt {
-    #[clap(long, default_value = "0")]
-    pub consumer_partition: u32,
+    /// Partitions of the topic to consume from. Can be repeated or comma separated.
+    #[clap(long, default_value = "0", use_value_delimiter = true)]
+    pub consumer_partition: Vec<u32>,
+
+    /// Consume from every partition of the topic, ignoring `--consumer-partition`
+    #[clap(long)]
+    pub consumer_all_partitions: bool,
+
+    /// Where to start consuming from: beginning, end, an absolute offset or checkpoint.
+    /// `checkpoint` resumes right after the last offset committed by the connector.
+    /// Defaults to end, or to checkpoint for the sinks keeping their offsets in the database
+    /// they write to by default.
+    #[clap(long)]
+    #[schemars(with = "Option<String>")]
+    #[serde(default)]
+    pub consumer_offset: Option<ConsumerOffset>,
+
+    /// File where processed offsets are committed to and read back from on restart.
+    #[clap(long)]
+    pub consumer_offset_path: Option<PathBuf>,
 }
 
-#[derive(Parser, Debug, JsonSchema, Clone, Default)]
+impl CommonConsumerOpt {
+    /// `--consumer-offset`, end if it is not given
+    pub fn offset(&self) -> ConsumerOffset {
+        self.consumer_offset.unwrap_or_default()
+    }
+
+    /// Listed partitions, without duplicates. Defaults to partition 0.
+    pub fn partitions(&self) -> Vec<u32> {
+        let mut partitions = self.consumer_partition.clone();
+        partitions.sort_unstable();
+        partitions.dedup();
+        if partitions.is_empty() {
+            partitions.push(0);
+        }
+        partitions
+    }
+}
+
+#[derive(Parser, Debug, JsonSchema, Clone, Deserialize)]
+pub struct CommonRetryOpt {
+    /// Maximum number of attempts to deliver a record downstream, including the first one
+    #[clap(long, default_value = "5")]
+    pub retry_max_attempts: u32,
+
+    /// Time to wait before the first retry, doubled on every subsequent one
+    /// Ex: '150ms', '20s'
+    #[clap(long, default_value = "100ms", parse(try_from_str = parse_duration))]
+    #[serde(deserialize_with = "crate::de::duration")]
+    pub retry_initial_backoff: Duration,
+
+    /// Maximum time to wait between two attempts
+    /// Ex: '150ms', '20s'
+    #[clap(long, default_value = "30s", parse(try_from_str = parse_duration))]
+    #[serde(deserialize_with = "crate::de::duration")]
+    pub retry_max_backoff: Duration,
+
+    /// Fraction of each wait that is randomized, between 0 and 1
+    #[clap(long, default_value = "0.2", parse(try_from_str = parse_jitter))]
+    #[serde(deserialize_with = "deserialize_jitter")]
+    pub retry_jitter: f64,
+
+    /// Number of consecutive records failing every attempt before the connector
+    /// pauses calling the downstream system. Disabled if not defined.
+    #[clap(long)]
+    pub retry_circuit_breaker_threshold: Option<u32>,
+
+    /// How long the connector pauses once the circuit breaker opens
+    /// Ex: '150ms', '20s'
+    #[clap(long, default_value = "30s", parse(try_from_str = parse_duration))]
+    #[serde(deserialize_with = "crate::de::duration")]
+    pub retry_circuit_breaker_reset: Duration,
+}
+
+impl Default for CommonRetryOpt {
+    fn default() -> Self {
+        let policy = RetryPolicy::default();
+        Self {
+            retry_max_attempts: policy.max_attempts,
+            retry_initial_backoff: policy.initial_backoff,
+            retry_max_backoff: policy.max_backoff,
+            retry_jitter: policy.jitter,
+            retry_circuit_breaker_threshold: None,
+            retry_circuit_breaker_reset: Duration::from_secs(30),
+        }
+    }
+}
+
+impl CommonRetryOpt {
+    pub fn retry_policy(&self) -> RetryPolicy {
+        RetryPolicy {
+            max_attempts: self.retry_max_attempts.max(1),
+            initial_backoff: self.retry_initial_backoff,
+            max_backoff: self.retry_max_backoff,
+            jitter: self.retry_jitter,
+        }
+    }
+
+    pub fn create_retrier(&self) -> Retrier {
+        let circuit_breaker = self
+            .retry_circuit_breaker_threshold
+            .map(|threshold| CircuitBreaker::new(threshold, self.retry_circuit_breaker_reset));
+        Retrier::new(self.retry_policy(), circuit_breaker)
+    }
+}
+
+#[derive(Parser, Debug, JsonSchema, Clone, Deserialize)]
+pub struct CommonHealthOpt {
+    /// Address to serve the liveness and readiness probes on, at the `/live` and
+    /// `/ready` paths.
+    /// Ex: '0.0.0.0:8080'
+    #[clap(long)]
+    pub health_addr: Option<SocketAddr>,
+
+    /// Time the upstream or downstream system can stay disconnected before the
+    /// connector is no longer reported as live
+    /// Ex: '30s', '5m'
+    #[clap(long, default_value = "5m", parse(try_from_str = parse_duration))]
+    #[serde(deserialize_with = "crate::de::duration")]
+    pub health_unhealthy_after: Duration,
+
+    /// Time without any record going through before the connector is no longer
+    /// reported as live. Disabled if not defined.
+    /// Ex: '30s', '1h'
+    #[clap(long, parse(try_from_str = parse_duration))]
+    #[serde(default, deserialize_with = "crate::de::option_duration")]
+    pub health_max_idle: Option<Duration>,
+}
+
+impl Default for CommonHealthOpt {
+    fn default() -> Self {
+        Self {
+            health_addr: None,
+            health_unhealthy_after: Duration::from_secs(5 * 60),
+            health_max_idle: None,
+        }
+    }
+}
+
+impl CommonHealthOpt {
+    /// Creates the health tracker of the connector, served over HTTP if `--health-addr` is set
+    pub fn create_health(&self) -> Arc<ConnectorHealth> {
+        let health = Arc::new(ConnectorHealth::new(
+            self.health_unhealthy_after,
+            self.health_max_idle,
+        ));
+        #[cfg(not(target_os = "windows"))]
+        if let Some(addr) = self.health_addr {
+            monitoring::init_health_endpoint(addr, health.clone());
+        }
+        health
+    }
+}
+
+#[derive(Parser, Debug, JsonSchema, Clone, Default, Deserialize)]
 pub struct CommonProducerOpt {
     /// Time to wait before sending
     /// Ex: '150ms', '20s'
     #[clap(long, parse(try_from_str = parse_duration))]
+    #[serde(default, deserialize_with = "crate::de::option_duration")]
     pub producer_linger: Option<Duration>,
 
     /// Compression algorithm to use when sending records.
     /// Supported values: none, gzip, snappy and lz4.
     #[clap(long)]
     #[schemars(skip)]
+    #[serde(default, deserialize_with = "crate::de::option_from_str")]
     pub producer_compression: Option<Compression>,
 
     /// Max amount of bytes accumulated before sending
     #[clap(long)]
     #[schemars(skip)]
+    #[serde(default, deserialize_with = "crate::de::option_from_str")]
     pub producer_batch_size: Option<ByteSize>,
 }
 
@@ -72,10 +295,25 @@ impl CommonConnectorOpt {
         &self,
         connector_name: &str,
     ) -> anyhow::Result<fluvio::TopicProducer> {
+        let fluvio = self.connect_fluvio(connector_name).await?;
+        self.create_topic_producer(&fluvio).await
+    }
+
+    /// Connection to the Fluvio cluster, shared by the producers of a connector writing
+    /// to several topics
+    pub async fn connect_fluvio(&self, connector_name: &str) -> anyhow::Result<fluvio::Fluvio> {
         let mut cluster_config = FluvioConfig::load()?;
         cluster_config.client_id = Some(format!("fluvio_connector_{connector_name}"));
 
-        let fluvio = fluvio::Fluvio::connect_with_config(&cluster_config).await?;
+        fluvio::Fluvio::connect_with_config(&cluster_config).await
+    }
+
+    /// Producer of `fluvio_topic` through the `fluvio` connection, with the producer
+    /// options and transforms of the connector
+    pub async fn create_topic_producer(
+        &self,
+        fluvio: &fluvio::Fluvio,
+    ) -> anyhow::Result<fluvio::TopicProducer> {
         self.ensure_topic_exists().await?;
         let config_builder = fluvio::TopicProducerConfigBuilder::default();
 
@@ -114,37 +352,100 @@ impl CommonConnectorOpt {
 }
 #[cfg(feature = "sink")]
 impl CommonConnectorOpt {
-    pub async fn create_consumer(&self) -> anyhow::Result<fluvio::PartitionConsumer> {
+    pub async fn create_consumer(&self) -> anyhow::Result<ConnectorConsumer> {
         self.ensure_topic_exists().await?;
-        fluvio::consumer(&self.fluvio_topic, self.consumer_common.consumer_partition).await
+        let partitions = if self.consumer_common.consumer_all_partitions {
+            topic_partitions(&self.fluvio_topic).await?
+        } else {
+            self.consumer_common.partitions()
+        };
+        ConnectorConsumer::connect(&self.fluvio_topic, &partitions).await
+    }
+
+    /// Returns the store offsets should be committed to, based on `--consumer-offset-path`
+    pub fn create_offset_store(&self) -> anyhow::Result<Box<dyn OffsetStore>> {
+        match self.consumer_common.consumer_offset_path {
+            Some(ref path) => Ok(Box::new(FileOffsetStore::open(path)?)),
+            None if self.consumer_common.offset() == ConsumerOffset::Checkpoint => Err(
+                anyhow::anyhow!("--consumer-offset=checkpoint requires --consumer-offset-path"),
+            ),
+            None => Ok(Box::new(NoopOffsetStore)),
+        }
     }
 
+    /// Merges the streams of every consumed partition into a single stream.
+    ///
+    /// Each partition starts from its own committed offset, if any. Records of the
+    /// same partition are yielded in order, while partitions are interleaved.
     pub async fn create_consumer_stream(
         &self,
-        consumer: PartitionConsumer,
+        consumer: ConnectorConsumer,
         connector_name: &str,
-    ) -> anyhow::Result<
-        impl tokio_stream::Stream<
-            Item = Result<fluvio::consumer::Record, fluvio_protocol::link::ErrorCode>,
-        >,
-    > {
+        offsets: &mut dyn OffsetStore,
+    ) -> anyhow::Result<ConsumerStream> {
         let mut cluster_config = FluvioConfig::load()?;
         cluster_config.client_id = Some(format!("fluvio_connector_{connector_name}"));
-        let smartmodule = self
-            .transform_common
-            .transform
-            .iter()
-            .map(|t| t.into())
-            .collect();
-        let mut builder = fluvio::ConsumerConfig::builder();
-        builder.smartmodule(smartmodule);
-        let config = builder.build()?;
-        let offset = fluvio::Offset::end();
-        Ok(consumer.stream_with_config(offset, config).await?)
+        let mut streams = Vec::new();
+        for (partition, partition_consumer) in consumer.into_consumers() {
+            let smartmodule = self
+                .transform_common
+                .transform
+                .iter()
+                .map(|t| t.into())
+                .collect();
+            let mut builder = fluvio::ConsumerConfig::builder();
+            builder.smartmodule(smartmodule);
+            let config = builder.build()?;
+            let committed = offsets.load(&self.fluvio_topic, partition).await?;
+            let offset = self.consumer_common.offset().resolve(committed)?;
+            let stream = partition_consumer
+                .stream_with_config(offset, config)
+                .await?;
+            streams.push(Box::pin(stream) as ConsumerStream);
+        }
+        Ok(Box::pin(futures_util::stream::select_all(streams)))
     }
 }
 
 impl CommonConnectorOpt {
+    /// Producer to the dead-letter topic of the connector, created if missing. Records
+    /// are produced as they are, without the transforms of the connector.
+    pub async fn create_dead_letter_producer(
+        &self,
+        connector_name: &str,
+        dead_letter_topic: Option<&str>,
+    ) -> anyhow::Result<Option<DeadLetterProducer>> {
+        if dead_letter_topic.is_none() {
+            return Ok(None);
+        }
+        let mut cluster_config = FluvioConfig::load()?;
+        cluster_config.client_id = Some(format!("fluvio_connector_{connector_name}_dead_letter"));
+
+        let fluvio = fluvio::Fluvio::connect_with_config(&cluster_config).await?;
+        self.create_dead_letter_topic_producer(&fluvio, dead_letter_topic)
+            .await
+    }
+
+    /// Producer of `dead_letter_topic` through the `fluvio` connection, if any
+    pub async fn create_dead_letter_topic_producer(
+        &self,
+        fluvio: &fluvio::Fluvio,
+        dead_letter_topic: Option<&str>,
+    ) -> anyhow::Result<Option<DeadLetterProducer>> {
+        let dead_letter_topic = match dead_letter_topic {
+            Some(topic) => topic,
+            None => return Ok(None),
+        };
+        create_topic_if_missing(dead_letter_topic).await?;
+        let producer = fluvio.topic_producer(dead_letter_topic).await?;
+
+        Ok(Some(DeadLetterProducer::new(
+            self.fluvio_topic.clone(),
+            dead_letter_topic.to_string(),
+            producer,
+        )))
+    }
+
     pub fn enable_logging(&self) {
         if std::env::var("RUST_LOG").is_err() {
             std::env::set_var("RUST_LOG", "info")
@@ -154,27 +455,47 @@ impl CommonConnectorOpt {
         }
         fluvio_future::subscriber::init_logger();
     }
-    pub async fn ensure_topic_exists(&self) -> anyhow::Result<()> {
-        let admin = fluvio::FluvioAdmin::connect().await?;
-        let topics = admin.list::<TopicSpec, String>(vec![]).await?;
-        let topic_exists = topics.iter().any(|t| t.name == self.fluvio_topic);
-        if !topic_exists {
-            let _ = admin
-                .create(
-                    self.fluvio_topic.clone(),
-                    false,
-                    TopicSpec::new_computed(1, 1, Some(false)),
-                )
-                .await;
+
+    /// Installs the SIGTERM/SIGINT handler. Connectors stop taking new records once
+    /// the returned signal is notified.
+    pub fn install_shutdown_handler(&self) -> std::io::Result<ShutdownSignal> {
+        crate::shutdown::install(self.shutdown_grace_period)
+    }
+
+    /// Exposes the metrics over the monitoring socket, and over HTTP if `--metrics-addr` is set
+    #[cfg(not(target_os = "windows"))]
+    pub fn init_monitoring(&self, metrics: Arc<ConnectorMetrics>) {
+        if let Some(addr) = self.metrics_addr {
+            monitoring::init_metrics_endpoint(addr, metrics.clone());
         }
-        Ok(())
+        monitoring::init_monitoring(metrics);
+    }
+
+    pub async fn ensure_topic_exists(&self) -> anyhow::Result<()> {
+        create_topic_if_missing(&self.fluvio_topic).await
     }
 }
 
-#[derive(Parser, Debug, JsonSchema, Clone, Default)]
+async fn create_topic_if_missing(topic: &str) -> anyhow::Result<()> {
+    let admin = fluvio::FluvioAdmin::connect().await?;
+    let topics = admin.list::<TopicSpec, String>(vec![]).await?;
+    let topic_exists = topics.iter().any(|t| t.name == topic);
+    if !topic_exists {
+        let _ = admin
+            .create(
+                topic.to_string(),
+                false,
+                TopicSpec::new_computed(1, 1, Some(false)),
+            )
+            .await;
+    }
+    Ok(())
+}
+
+#[derive(Parser, Debug, JsonSchema, Clone, Default, Deserialize)]
 pub struct CommonTransformOpt {
     #[clap(long)]
-    transform: Vec<TransformOpt>,
+    pub(crate) transform: Vec<TransformOpt>,
 }
 
 #[cfg(feature = "source")]
@@ -234,8 +555,31 @@ impl From<&TransformOpt> for SmartModuleInvocation {
     }
 }
 
+/// Parses the options of a connector from the command line, merged with the ones of
+/// the connector config if `--config` is given.
+pub fn parse_connector_opts<T: Parser + DeserializeOwned + JsonSchema>() -> T {
+    match crate::loader::load_opts(std::env::args().collect()) {
+        Ok(opts) => opts,
+        Err(err) => err.exit(),
+    }
+}
+
+fn parse_jitter(s: &str) -> Result<f64, String> {
+    let jitter: f64 = s.parse().map_err(|e| format!("{e}"))?;
+    if (0.0..=1.0).contains(&jitter) {
+        Ok(jitter)
+    } else {
+        Err(format!("{s} is not between 0 and 1"))
+    }
+}
+
+fn deserialize_jitter<'de, D: Deserializer<'de>>(deserializer: D) -> Result<f64, D::Error> {
+    crate::de::from_str::<D, String>(deserializer)
+        .and_then(|jitter| parse_jitter(&jitter).map_err(de::Error::custom))
+}
+
 pub trait GetOpts {
-    type Opt: Parser + JsonSchema;
+    type Opt: Parser + JsonSchema + DeserializeOwned;
     fn get_opt() -> Option<Self::Opt> {
         if let Some("metadata") = std::env::args().nth(1).as_deref() {
             let schema = schema_for!(Self::Opt);
@@ -249,10 +593,25 @@ pub trait GetOpts {
             println!("{metadata_json}");
             None
         } else {
-            Some(Self::Opt::from_args())
+            Some(parse_connector_opts())
         }
     }
     fn name() -> &'static str;
     fn version() -> &'static str;
     fn description() -> &'static str;
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_retry_jitter_range() {
+        assert_eq!(parse_jitter("0.5"), Ok(0.5));
+        assert_eq!(parse_jitter("1"), Ok(1.0));
+        assert!(parse_jitter("NaN").is_err());
+        assert!(parse_jitter("-0.1").is_err());
+        assert!(parse_jitter("1.5").is_err());
+        assert!(CommonRetryOpt::try_parse_from(["retry", "--retry-jitter", "2"]).is_err());
+    }
+}
@@ -0,0 +1,333 @@
+use std::fmt;
+use std::future::Future;
+use std::time::{Duration, Instant};
+
+use fluvio_future::timer::sleep;
+use tracing::{info, warn};
+
+/// Wraps an error which must not be retried, e.g. a malformed record
+#[derive(Debug)]
+pub struct PermanentError(pub anyhow::Error);
+
+impl fmt::Display for PermanentError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&self.0, f)
+    }
+}
+
+impl std::error::Error for PermanentError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        self.0.source()
+    }
+}
+
+/// Marks the error as permanent, so it is returned right away instead of being retried
+pub fn permanent<E: Into<anyhow::Error>>(err: E) -> anyhow::Error {
+    anyhow::Error::new(PermanentError(err.into()))
+}
+
+/// Every error is considered transient unless it was marked with [`permanent`]
+pub fn is_retryable(err: &anyhow::Error) -> bool {
+    !err.chain().any(|cause| cause.is::<PermanentError>())
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RetryPolicy {
+    /// Total number of attempts, including the first one
+    pub max_attempts: u32,
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+    /// Fraction of each delay that is randomized, between 0 and 1
+    pub jitter: f64,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 5,
+            initial_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_secs(30),
+            jitter: 0.2,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Delay to wait after the given failed attempt (starting at 1).
+    ///
+    /// The delay doubles on every attempt up to `max_backoff`, then it is reduced by
+    /// a random amount of up to `jitter` of its value.
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        let exponent = attempt.saturating_sub(1).min(31);
+        let backoff = self
+            .initial_backoff
+            .saturating_mul(1 << exponent)
+            .min(self.max_backoff);
+        let jitter = self.jitter.clamp(0.0, 1.0);
+        backoff.mul_f64(1.0 - jitter * fastrand::f64())
+    }
+
+    /// Runs the operation until it succeeds, fails with a permanent error or
+    /// the attempts are exhausted. The last error is returned in the latter cases.
+    pub async fn retry<T, F, Fut>(&self, mut operation: F) -> anyhow::Result<T>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = anyhow::Result<T>>,
+    {
+        let mut attempt = 1;
+        loop {
+            match operation().await {
+                Ok(value) => return Ok(value),
+                Err(err) if attempt < self.max_attempts && is_retryable(&err) => {
+                    let backoff = self.backoff(attempt);
+                    warn!(
+                        attempt,
+                        max_attempts = self.max_attempts,
+                        ?backoff,
+                        "retrying after error: {:?}",
+                        err
+                    );
+                    sleep(backoff).await;
+                    attempt += 1;
+                }
+                Err(err) => return Err(err),
+            }
+        }
+    }
+}
+
+/// Stops calling a failing downstream system for a while.
+///
+/// After `failure_threshold` consecutive operations exhausted their retries, the circuit
+/// opens and the next operation is held back until `reset_timeout` has elapsed. A success
+/// closes the circuit again, a failure keeps it open for another `reset_timeout`.
+#[derive(Debug)]
+pub struct CircuitBreaker {
+    failure_threshold: u32,
+    reset_timeout: Duration,
+    consecutive_failures: u32,
+    opened_at: Option<Instant>,
+}
+
+impl CircuitBreaker {
+    pub fn new(failure_threshold: u32, reset_timeout: Duration) -> Self {
+        Self {
+            failure_threshold: failure_threshold.max(1),
+            reset_timeout,
+            consecutive_failures: 0,
+            opened_at: None,
+        }
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.opened_at.is_some()
+    }
+
+    /// Waits for the rest of `reset_timeout` if the circuit is open
+    pub async fn wait_if_open(&self) {
+        if let Some(opened_at) = self.opened_at {
+            let remaining = self.reset_timeout.saturating_sub(opened_at.elapsed());
+            if !remaining.is_zero() {
+                info!(?remaining, "circuit breaker open, waiting");
+                sleep(remaining).await;
+            }
+        }
+    }
+
+    pub fn on_success(&mut self) {
+        if self.is_open() {
+            info!("circuit breaker closed");
+        }
+        self.consecutive_failures = 0;
+        self.opened_at = None;
+    }
+
+    pub fn on_failure(&mut self) {
+        self.consecutive_failures = self.consecutive_failures.saturating_add(1);
+        if self.consecutive_failures >= self.failure_threshold {
+            if !self.is_open() {
+                warn!(
+                    consecutive_failures = self.consecutive_failures,
+                    "circuit breaker opened"
+                );
+            }
+            self.opened_at = Some(Instant::now());
+        }
+    }
+}
+
+/// Applies a [`RetryPolicy`] to every operation, guarded by an optional [`CircuitBreaker`]
+#[derive(Debug)]
+pub struct Retrier {
+    policy: RetryPolicy,
+    circuit_breaker: Option<CircuitBreaker>,
+}
+
+impl Retrier {
+    pub fn new(policy: RetryPolicy, circuit_breaker: Option<CircuitBreaker>) -> Self {
+        Self {
+            policy,
+            circuit_breaker,
+        }
+    }
+
+    pub fn policy(&self) -> &RetryPolicy {
+        &self.policy
+    }
+
+    pub async fn run<T, F, Fut>(&mut self, operation: F) -> anyhow::Result<T>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = anyhow::Result<T>>,
+    {
+        if let Some(ref circuit_breaker) = self.circuit_breaker {
+            circuit_breaker.wait_if_open().await;
+        }
+        let result = self.policy.retry(operation).await;
+        if let Some(ref mut circuit_breaker) = self.circuit_breaker {
+            match result {
+                Ok(_) => circuit_breaker.on_success(),
+                // a permanent error is caused by the record, not by the downstream system
+                Err(ref err) if !is_retryable(err) => {}
+                Err(_) => circuit_breaker.on_failure(),
+            }
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_policy(max_attempts: u32) -> RetryPolicy {
+        RetryPolicy {
+            max_attempts,
+            initial_backoff: Duration::from_millis(1),
+            max_backoff: Duration::from_millis(4),
+            jitter: 0.0,
+        }
+    }
+
+    #[test]
+    fn test_backoff() {
+        let policy = test_policy(10);
+        assert_eq!(policy.backoff(1), Duration::from_millis(1));
+        assert_eq!(policy.backoff(2), Duration::from_millis(2));
+        assert_eq!(policy.backoff(3), Duration::from_millis(4));
+        assert_eq!(policy.backoff(4), Duration::from_millis(4));
+        assert_eq!(policy.backoff(u32::MAX), Duration::from_millis(4));
+
+        let policy = RetryPolicy {
+            jitter: 0.5,
+            ..test_policy(10)
+        };
+        for _ in 0..100 {
+            let backoff = policy.backoff(3);
+            assert!(backoff >= Duration::from_millis(2) && backoff <= Duration::from_millis(4));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_retry_until_success() {
+        //given
+        let policy = test_policy(5);
+        let mut calls = 0;
+
+        //when
+        let result = policy
+            .retry(|| {
+                calls += 1;
+                let calls = calls;
+                async move {
+                    if calls < 3 {
+                        anyhow::bail!("unavailable")
+                    }
+                    Ok(calls)
+                }
+            })
+            .await;
+
+        //then
+        assert_eq!(result.expect("should succeed"), 3);
+    }
+
+    #[tokio::test]
+    async fn test_retry_exhausted() {
+        //given
+        let policy = test_policy(3);
+        let mut calls = 0;
+
+        //when
+        let result: anyhow::Result<()> = policy
+            .retry(|| {
+                calls += 1;
+                async { anyhow::bail!("unavailable") }
+            })
+            .await;
+
+        //then
+        assert!(result.is_err());
+        assert_eq!(calls, 3);
+    }
+
+    #[tokio::test]
+    async fn test_retry_permanent_error() {
+        //given
+        let policy = test_policy(3);
+        let mut calls = 0;
+
+        //when
+        let result: anyhow::Result<()> = policy
+            .retry(|| {
+                calls += 1;
+                async { Err(permanent(anyhow::anyhow!("malformed record"))) }
+            })
+            .await;
+
+        //then
+        let err = result.expect_err("should fail");
+        assert!(!is_retryable(&err));
+        assert_eq!(err.to_string(), "malformed record");
+        assert_eq!(calls, 1);
+    }
+
+    #[tokio::test]
+    async fn test_circuit_breaker() {
+        //given
+        let mut retrier = Retrier::new(
+            test_policy(1),
+            Some(CircuitBreaker::new(2, Duration::from_millis(10))),
+        );
+        let is_open = |retrier: &Retrier| {
+            retrier
+                .circuit_breaker
+                .as_ref()
+                .map(CircuitBreaker::is_open)
+                .unwrap_or_default()
+        };
+
+        //when
+        let _ = retrier
+            .run(|| async { Err::<(), _>(permanent(anyhow::anyhow!("malformed"))) })
+            .await;
+        let _ = retrier
+            .run(|| async { Err::<(), _>(anyhow::anyhow!("unavailable")) })
+            .await;
+        assert!(!is_open(&retrier));
+        let _ = retrier
+            .run(|| async { Err::<(), _>(anyhow::anyhow!("unavailable")) })
+            .await;
+        assert!(is_open(&retrier));
+
+        let started = Instant::now();
+        retrier
+            .run(|| async { Ok(()) })
+            .await
+            .expect("should succeed");
+
+        //then
+        assert!(started.elapsed() >= Duration::from_millis(5));
+        assert!(!is_open(&retrier));
+    }
+}
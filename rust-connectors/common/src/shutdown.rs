@@ -0,0 +1,142 @@
+use std::future::Future;
+use std::time::Duration;
+
+use futures_util::future::{select, Either};
+use futures_util::{Stream, StreamExt};
+use tokio::sync::watch;
+use tracing::{info, warn};
+
+/// Notified once the connector has been asked to stop, e.g. on SIGTERM during a pod rollout.
+///
+/// The default signal is never notified.
+#[derive(Debug, Clone)]
+pub struct ShutdownSignal {
+    receiver: watch::Receiver<bool>,
+}
+
+impl Default for ShutdownSignal {
+    fn default() -> Self {
+        let (_, receiver) = watch::channel(false);
+        Self { receiver }
+    }
+}
+
+impl ShutdownSignal {
+    fn new() -> (watch::Sender<bool>, Self) {
+        let (sender, receiver) = watch::channel(false);
+        (sender, Self { receiver })
+    }
+
+    pub fn is_requested(&self) -> bool {
+        *self.receiver.borrow()
+    }
+
+    /// Resolves once a shutdown has been requested
+    pub async fn requested(&mut self) {
+        while !self.is_requested() {
+            if self.receiver.changed().await.is_err() {
+                // nothing can request a shutdown anymore
+                std::future::pending::<()>().await;
+            }
+        }
+    }
+
+    /// Runs the future unless a shutdown is requested first, in which case `None` is returned
+    pub async fn until<F: Future>(&mut self, future: F) -> Option<F::Output> {
+        let requested = Box::pin(self.requested());
+        match select(Box::pin(future), requested).await {
+            Either::Left((output, _)) => Some(output),
+            Either::Right(_) => None,
+        }
+    }
+
+    /// Ends the stream once a shutdown is requested. The item being processed when
+    /// the signal arrives is not interrupted, only the following ones are not yielded.
+    pub fn guard<S: Stream>(&self, stream: S) -> impl Stream<Item = S::Item> {
+        let mut signal = self.clone();
+        stream.take_until(Box::pin(async move { signal.requested().await }))
+    }
+}
+
+/// Listens for SIGTERM and SIGINT in a dedicated thread, so it works with any async runtime.
+///
+/// The first signal notifies the returned [`ShutdownSignal`]. The process exits anyway
+/// once `grace_period` has elapsed, or right away on a second signal.
+pub fn install(grace_period: Duration) -> std::io::Result<ShutdownSignal> {
+    let (sender, signal) = ShutdownSignal::new();
+
+    #[cfg(unix)]
+    {
+        use signal_hook::consts::{SIGINT, SIGTERM};
+        use signal_hook::iterator::Signals;
+
+        let mut signals = Signals::new([SIGTERM, SIGINT])?;
+        std::thread::Builder::new()
+            .name("shutdown".to_string())
+            .spawn(move || {
+                let mut signals = signals.forever();
+                if let Some(signal) = signals.next() {
+                    info!(signal, ?grace_period, "shutdown requested");
+                    let _ = sender.send(true);
+                    std::thread::spawn(move || {
+                        std::thread::sleep(grace_period);
+                        warn!("shutdown grace period elapsed, exiting");
+                        std::process::exit(1);
+                    });
+                }
+                if let Some(signal) = signals.next() {
+                    warn!(signal, "shutdown requested again, exiting");
+                    std::process::exit(1);
+                }
+            })?;
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = (sender, grace_period);
+    }
+
+    Ok(signal)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_shutdown_signal() {
+        //given
+        let (sender, mut signal) = ShutdownSignal::new();
+        assert!(!signal.is_requested());
+        assert_eq!(signal.until(async { 1 }).await, Some(1));
+
+        //when
+        sender.send(true).expect("signal receiver dropped");
+
+        //then
+        assert!(signal.is_requested());
+        signal.requested().await;
+        assert_eq!(signal.until(std::future::pending::<()>()).await, None);
+    }
+
+    #[tokio::test]
+    async fn test_shutdown_guard() {
+        //given
+        let (sender, signal) = ShutdownSignal::new();
+        let mut stream = Box::pin(signal.guard(futures_util::stream::iter(0..10)));
+
+        //when
+        let first = stream.next().await;
+        sender.send(true).expect("signal receiver dropped");
+
+        //then
+        assert_eq!(first, Some(0));
+        assert_eq!(stream.next().await, None);
+    }
+
+    #[tokio::test]
+    async fn test_default_signal_never_requested() {
+        let mut signal = ShutdownSignal::default();
+        assert!(!signal.is_requested());
+        assert_eq!(signal.until(async { 1 }).await, Some(1));
+    }
+}
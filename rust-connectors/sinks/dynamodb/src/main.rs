@@ -1,4 +1,5 @@
 use std::sync::Arc;
+use std::time::Instant;
 
 use aws_sdk_dynamodb::{
     model::{
@@ -8,11 +9,13 @@ use aws_sdk_dynamodb::{
     Client, Endpoint,
 };
 use clap::Parser;
+use fluvio_connectors_common::opt::{parse_connector_opts, CommonConnectorOpt};
+use fluvio_connectors_common::retry::permanent;
 use fluvio_connectors_common::{common_initialize, git_hash_version};
 use fluvio_connectors_common::{fluvio::Record, metrics::ConnectorMetrics};
-use fluvio_connectors_common::{monitoring::init_monitoring, opt::CommonConnectorOpt};
 use fluvio_future::tracing::{error, info};
 use schemars::{schema_for, JsonSchema};
+use serde::Deserialize;
 use serde_json::value::Value;
 use tokio_stream::StreamExt;
 
@@ -30,7 +33,7 @@ async fn main() -> anyhow::Result<()> {
         println!("{}", serde_json::to_string_pretty(&schema).unwrap());
         return Ok(());
     }
-    let opts: DynamoDbOpt = DynamoDbOpt::from_args();
+    let opts: DynamoDbOpt = parse_connector_opts();
     opts.common.enable_logging();
     info!(
         connector_version = env!("CARGO_PKG_VERSION"),
@@ -42,7 +45,7 @@ async fn main() -> anyhow::Result<()> {
     Ok(())
 }
 
-#[derive(Parser, Debug, JsonSchema, Clone)]
+#[derive(Parser, Debug, JsonSchema, Clone, Deserialize)]
 pub struct DynamoDbOpt {
     #[clap(long)]
     pub aws_endpoint: Option<String>,
@@ -58,11 +61,13 @@ pub struct DynamoDbOpt {
 
     #[clap(flatten)]
     #[schemars(flatten)]
+    #[serde(flatten)]
     pub common: CommonConnectorOpt,
 }
 
 impl DynamoDbOpt {
     pub async fn execute(&self) -> anyhow::Result<()> {
+        let health = self.common.health_common.create_health();
         let config = aws_config::load_from_env().await;
         let mut builder = aws_sdk_dynamodb::config::Builder::from(&config);
         if let Some(endpoint) = &self.aws_endpoint {
@@ -74,22 +79,52 @@ impl DynamoDbOpt {
 
         let client = Client::from_conf(dynamodb_local_config);
         self.create_table(&client).await?;
+        health.set_downstream_connected(true);
 
+        let shutdown = self.common.install_shutdown_handler()?;
         let consumer = self.common.create_consumer().await?;
+        health.set_upstream_connected(true);
         let metrics = Arc::new(ConnectorMetrics::new(consumer.metrics()));
 
-        init_monitoring(metrics);
+        self.common.init_monitoring(metrics.clone());
 
-        let mut stream = self
+        let dead_letter = self
             .common
-            .create_consumer_stream(consumer, "dynamodb")
+            .create_dead_letter_producer(
+                "dynamodb",
+                self.common.dead_letter_topic.as_deref(),
+            )
             .await?;
+        let mut offsets = self.common.create_offset_store()?;
+        let mut retrier = self.common.retry_common.create_retrier();
+        let mut stream = shutdown.guard(
+            self.common
+                .create_consumer_stream(consumer, "dynamodb", offsets.as_mut())
+                .await?,
+        );
         info!("Starting stream");
         while let Some(Ok(record)) = stream.next().await {
-            if let Err(e) = self.send_to_dynamodb(&record, &client).await {
-                error!("{:?}", e);
+            metrics.record_read(record.value().len());
+            let started = Instant::now();
+            if let Err(e) = retrier
+                .run(|| self.send_to_dynamodb(&record, &client))
+                .await
+            {
+                metrics.record_failed(&e);
+                health.record_failed(&e);
+                match dead_letter {
+                    Some(ref dead_letter) => dead_letter.send(&record, &e).await?,
+                    None => error!("{:?}", e),
+                }
+            } else {
+                metrics.record_written(record.value().len(), started.elapsed());
+                health.record_succeeded();
             }
+            offsets
+                .commit(&self.common.fluvio_topic, record.partition, record.offset)
+                .await?;
         }
+        info!("Stream ended");
         Ok(())
     }
 
@@ -129,7 +164,7 @@ impl DynamoDbOpt {
         record: &Record,
         client: &aws_sdk_dynamodb::Client,
     ) -> anyhow::Result<()> {
-        let json: Value = serde_json::from_slice(record.value())?;
+        let json: Value = serde_json::from_slice(record.value()).map_err(permanent)?;
         let mut request = client.put_item().table_name(&self.table_name);
         let column_names = self.column_names.split(',');
 
@@ -1,16 +1,17 @@
 use clap::Parser;
 use fluvio_connectors_common::metrics::ConnectorMetrics;
-use fluvio_connectors_common::monitoring::init_monitoring;
-use fluvio_connectors_common::opt::CommonConnectorOpt;
+use fluvio_connectors_common::opt::{parse_connector_opts, CommonConnectorOpt};
 use fluvio_connectors_common::{common_initialize, git_hash_version};
 use fluvio_future::tracing::{error, info};
 use rdkafka::config::ClientConfig;
-use rdkafka::producer::{FutureProducer, FutureRecord};
+use rdkafka::producer::{FutureProducer, FutureRecord, Producer};
 use schemars::schema_for;
 use schemars::JsonSchema;
+use serde::Deserialize;
 use std::io::Write;
 use std::str::FromStr;
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 use tempfile::NamedTempFile;
 use tokio_stream::StreamExt;
 
@@ -27,8 +28,10 @@ async fn main() -> anyhow::Result<()> {
         println!("{}", serde_json::to_string_pretty(&schema).unwrap());
         return Ok(());
     }
-    let raw_opts = KafkaOpt::from_args();
+    let raw_opts: KafkaOpt = parse_connector_opts();
     raw_opts.common.enable_logging();
+    let shutdown = raw_opts.common.install_shutdown_handler()?;
+    let health = raw_opts.common.health_common.create_health();
     info!(
         connector_version = env!("CARGO_PKG_VERSION"),
         git_hash = git_hash_version(),
@@ -45,6 +48,8 @@ async fn main() -> anyhow::Result<()> {
     let kafka_producer = &kafka_sink_deps.kafka_producer;
     let kafka_partition = &kafka_sink_deps.kafka_partition;
     let kafka_topic = &kafka_sink_deps.kafka_topic;
+    // The producer connects lazily, failed sends disconnect it
+    health.set_downstream_connected(true);
 
     info!("Starting stream");
 
@@ -52,37 +57,87 @@ async fn main() -> anyhow::Result<()> {
         .common_connector_opt
         .create_consumer()
         .await?;
+    health.set_upstream_connected(true);
     let metrics = Arc::new(ConnectorMetrics::new(consumer.metrics()));
 
-    init_monitoring(metrics);
+    kafka_sink_deps
+        .common_connector_opt
+        .init_monitoring(metrics.clone());
 
-    let mut stream = kafka_sink_deps
+    let dead_letter = kafka_sink_deps
         .common_connector_opt
-        .create_consumer_stream(consumer, "kafka")
+        .create_dead_letter_producer(
+            "kafka",
+            kafka_sink_deps
+                .common_connector_opt
+                .dead_letter_topic
+                .as_deref(),
+        )
         .await?;
+    let mut offsets = kafka_sink_deps.common_connector_opt.create_offset_store()?;
+    let mut retrier = kafka_sink_deps
+        .common_connector_opt
+        .retry_common
+        .create_retrier();
+    let mut stream = shutdown.guard(
+        kafka_sink_deps
+            .common_connector_opt
+            .create_consumer_stream(consumer, "kafka", offsets.as_mut())
+            .await?,
+    );
 
     while let Some(Ok(record)) = stream.next().await {
-        let mut kafka_record = FutureRecord::to(kafka_topic.as_str())
-            .payload(record.value())
-            .key(record.key().unwrap_or(&[]));
-        if let Some(kafka_partition) = &(kafka_partition) {
-            kafka_record = kafka_record.partition(*kafka_partition);
-        }
-
-        let enqueue_res = kafka_producer.send_result(kafka_record);
+        metrics.record_read(record.value().len());
+        let started = Instant::now();
+        // the offset is only committed once kafka confirmed the delivery, or the record
+        // was dead-lettered
+        let delivery_res = retrier
+            .run(|| {
+                let mut kafka_record = FutureRecord::to(kafka_topic.as_str())
+                    .payload(record.value())
+                    .key(record.key().unwrap_or(&[]));
+                if let Some(kafka_partition) = &(kafka_partition) {
+                    kafka_record = kafka_record.partition(*kafka_partition);
+                }
+                async move {
+                    kafka_producer
+                        .send(kafka_record, Duration::ZERO)
+                        .await
+                        .map(|_| ())
+                        .map_err(|(error, _)| anyhow::Error::from(error))
+                }
+            })
+            .await;
 
-        if let Err((error, _)) = enqueue_res {
+        if let Err(error) = delivery_res {
+            metrics.record_failed(&error);
+            health.record_failed(&error);
             error!(
                 "KafkaError {:?}, offset: {}, partition: {}",
                 error, &record.offset, &record.partition
             );
+            if let Some(ref dead_letter) = dead_letter {
+                dead_letter.send(&record, &error).await?;
+            }
+        } else {
+            metrics.record_written(record.value().len(), started.elapsed());
+            health.record_succeeded();
         }
+        offsets
+            .commit(
+                &kafka_sink_deps.common_connector_opt.fluvio_topic,
+                record.partition,
+                record.offset,
+            )
+            .await?;
     }
 
+    info!("Stream ended, flushing kafka producer");
+    kafka_producer.flush(kafka_sink_deps.common_connector_opt.shutdown_grace_period);
     Ok(())
 }
 
-#[derive(Parser, Debug, JsonSchema, Clone)]
+#[derive(Parser, Debug, JsonSchema, Clone, Deserialize)]
 pub struct KafkaOpt {
     /// A Comma separated list of the kafka brokers to connect to
     #[clap(long, env = "KAFKA_URL", hide_env_values = true)]
@@ -98,6 +153,7 @@ pub struct KafkaOpt {
 
     /// A key value pair in the form key:value
     #[clap(long)]
+    #[serde(deserialize_with = "fluvio_connectors_common::de::key_values")]
     pub kafka_option: Vec<String>,
 
     /// Boolean to create a kafka topic. Defaults to `false`
@@ -106,10 +162,12 @@ pub struct KafkaOpt {
 
     #[clap(flatten)]
     #[schemars(flatten)]
+    #[serde(flatten)]
     pub common: CommonConnectorOpt,
 
     #[clap(flatten)]
     #[schemars(flatten)]
+    #[serde(flatten)]
     pub security: SecurityOpt,
 }
 
@@ -138,12 +196,11 @@ impl KafkaSinkDependencies {
         let client_config = {
             let mut kafka_options: Vec<(String, String)> = Vec::new();
             for opt in &kafka_option {
-                let mut splits = opt.split(':');
-                let (key, value) = (
-                    splits.next().unwrap().to_string(),
-                    splits.next().unwrap().to_string(),
-                );
-                kafka_options.push((key, value));
+                // values such as broker addresses contain colons themselves
+                let (key, value) = opt.split_once(':').ok_or_else(|| {
+                    anyhow::anyhow!("invalid kafka option {opt:?}, expected key:value")
+                })?;
+                kafka_options.push((key.to_string(), value.to_string()));
             }
             info!("kafka_options: {:?}", kafka_options);
 
@@ -231,7 +288,7 @@ impl KafkaSinkDependencies {
     }
 }
 
-#[derive(Parser, Debug, JsonSchema, Clone)]
+#[derive(Parser, Debug, JsonSchema, Clone, Deserialize)]
 pub struct SecurityOpt {
     /// The SSL key file to use. This should be used when running the connector outside the docker
     /// environment.
@@ -265,6 +322,10 @@ pub struct SecurityOpt {
 
     /// The kafka security protocol. Currently only supports `SSL`.
     #[clap(long)]
+    #[serde(
+        default,
+        deserialize_with = "fluvio_connectors_common::de::option_from_str"
+    )]
     pub security_protocol: Option<SecurityProtocolOpt>,
 }
 #[derive(Parser, Debug, JsonSchema, Clone)]
@@ -1,7 +1,14 @@
 use std::{cmp::Ordering, sync::Arc};
 
-use fluvio::{Fluvio, Offset, PartitionConsumer};
-use fluvio_connectors_common::{metrics::ConnectorMetrics, monitoring::init_monitoring};
+use fluvio::{Offset, PartitionConsumer};
+use fluvio_connectors_common::consumer::ConnectorConsumer;
+use fluvio_connectors_common::dead_letter::DeadLetterProducer;
+use fluvio_connectors_common::health::ConnectorHealth;
+use fluvio_connectors_common::metrics::{ConnectorMetrics, FORMAT_ERROR};
+use fluvio_connectors_common::offset::{ConsumerOffset, OffsetStore};
+use fluvio_connectors_common::opt::CommonConnectorOpt;
+use fluvio_connectors_common::retry::Retrier;
+use fluvio_connectors_common::shutdown::ShutdownSignal;
 use fluvio_model_postgres::{
     Column, DeleteBody, InsertBody, LogicalReplicationMessage, ReplicationEvent, TruncateBody,
     UpdateBody,
@@ -9,33 +16,61 @@ use fluvio_model_postgres::{
 use postgres_types::Type;
 use tokio_stream::StreamExt;
 
+use crate::offset::PgOffsetStore;
 use crate::PgConnectorOpt;
 use fluvio_model_postgres::RelationBody;
 use std::collections::BTreeMap;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 use tokio_postgres::{Client, NoTls};
 
 /// A Fluvio connector for Postgres CDC.
 pub struct PgConnector {
     /// The Postgres client for streaming replication changes.
-    pg_client: Client,
-    /// The Fluvio producer for recording change events.
-    consumer: PartitionConsumer,
+    pg_client: Arc<Client>,
+    /// The Fluvio consumers of the partitions of the change events, until they are streamed.
+    consumer: Option<ConnectorConsumer>,
+    common: CommonConnectorOpt,
+    /// Where processed offsets are committed to.
+    offsets: Box<dyn OffsetStore>,
+    /// Whether `offsets` is the `fluvio.offsets` table, committed along with each record.
+    offsets_in_db: bool,
     /// The current Log Sequence Number (offset) to read in the replication stream.
     //lsn: Option<PgLsn>,
     /// Caches the schema for each new table we see, grouped by relation_id
     relations: BTreeMap<u32, RelationBody>,
+    /// Where records that failed to be applied are sent to, if configured.
+    dead_letter: Option<DeadLetterProducer>,
+    /// Retries applying a record while Postgres is unavailable.
+    retrier: Retrier,
+    /// Stops consuming new records once notified.
+    shutdown: ShutdownSignal,
+    metrics: Arc<ConnectorMetrics>,
+    health: Arc<ConnectorHealth>,
 }
 
 impl PgConnector {
-    pub async fn new(config: PgConnectorOpt) -> anyhow::Result<Self> {
-        let fluvio = Fluvio::connect().await?;
-        let consumer = fluvio
-            .partition_consumer(&config.common.fluvio_topic, 0)
-            .await?;
+    pub async fn new(mut config: PgConnectorOpt) -> anyhow::Result<Self> {
+        // resumes from the committed offset unless told otherwise, as it always did
+        config
+            .common
+            .consumer_common
+            .consumer_offset
+            .get_or_insert(ConsumerOffset::Checkpoint);
+        let health = config.common.health_common.create_health();
+        let consumer = config.common.create_consumer().await?;
+        health.set_upstream_connected(true);
 
         let metrics = Arc::new(ConnectorMetrics::new(consumer.metrics()));
-        init_monitoring(metrics);
+        config.common.init_monitoring(metrics.clone());
+
+        let dead_letter = config
+            .common
+            .create_dead_letter_producer(
+                "postgres",
+                config.common.dead_letter_topic.as_deref(),
+            )
+            .await?;
+        let retrier = config.common.retry_common.create_retrier();
 
         let (pg_client, conn) = config
             .url
@@ -44,36 +79,46 @@ impl PgConnector {
             .connect(NoTls)
             .await?;
         tokio::spawn(conn);
+        health.set_downstream_connected(true);
+        let pg_client = Arc::new(pg_client);
+
+        let offsets_in_db = config.common.consumer_common.consumer_offset_path.is_none();
+        let offsets: Box<dyn OffsetStore> = if offsets_in_db {
+            Box::new(PgOffsetStore::new(pg_client.clone(), &config.common.fluvio_topic).await?)
+        } else {
+            config.common.create_offset_store()?
+        };
         Ok(Self {
-            consumer,
+            consumer: Some(consumer),
+            common: config.common,
+            offsets,
+            offsets_in_db,
             pg_client,
             relations: BTreeMap::new(),
+            dead_letter,
+            retrier,
+            shutdown: ShutdownSignal::default(),
+            metrics,
+            health,
         })
     }
-    pub async fn get_offset(&self) -> anyhow::Result<i64> {
-        let schema_create = "CREATE SCHEMA IF NOT EXISTS fluvio";
-        let _ = self.pg_client.execute(schema_create, &[]).await?;
-        let table_create = "CREATE TABLE IF NOT EXISTS fluvio.offset (id INT8 PRIMARY KEY, current_offset INT8 NOT NULL)";
-        let _ = self.pg_client.execute(table_create, &[]).await?;
-        let sql = "select * from fluvio.offset where id = 1";
-        let rows = self.pg_client.query(sql, &[]).await?;
-        if let Some(offset) = rows.first() {
-            let current_offset: i64 = offset.get("current_offset");
-            Ok(current_offset)
-        } else {
-            let current_offset = 0_i64;
-            let sql = "INSERT INTO fluvio.offset (id, current_offset) VALUES(1, $1);";
-            let _ = self.pg_client.execute(sql, &[&current_offset]).await?;
-            Ok(current_offset)
-        }
-    }
 
-    pub async fn get_relations(&mut self, offset: i64) -> anyhow::Result<()> {
-        let stream = self.consumer.stream(Offset::from_beginning(0)).await?;
+    pub fn with_shutdown(mut self, shutdown: ShutdownSignal) -> Self {
+        self.shutdown = shutdown;
+        self
+    }
+    /// Caches the tables of the relation events of a partition preceding `until`, or of
+    /// every event already in the partition if it is `None`.
+    pub async fn get_relations(
+        &mut self,
+        consumer: &PartitionConsumer,
+        until: Option<i64>,
+    ) -> anyhow::Result<()> {
+        let stream = consumer.stream(Offset::beginning()).await?;
         let stream = stream.timeout(Duration::from_millis(100));
         tokio::pin!(stream);
         while let Some(Ok(Ok(record))) = stream.next().await {
-            if record.offset >= offset {
+            if matches!(until, Some(until) if record.offset >= until) {
                 break;
             }
             let next = record.value();
@@ -86,23 +131,39 @@ impl PgConnector {
         Ok(())
     }
     pub async fn process_stream(&mut self) -> anyhow::Result<()> {
-        let offset = self.get_offset().await?;
-        if offset >= 0 {
-            self.get_relations(offset).await?;
-        }
-        // Offset needs to be one more than the last.
-        let offset = offset + 1;
-        let mut stream = self
+        let consumer = self
             .consumer
-            .stream(Offset::from_beginning(offset.try_into().unwrap()))
+            .take()
+            .ok_or_else(|| anyhow::anyhow!("the change events are already being processed"))?;
+        let consumer_offset = self.common.consumer_common.offset();
+        for (partition, partition_consumer) in consumer.consumers() {
+            let committed = self
+                .offsets
+                .load(&self.common.fluvio_topic, *partition)
+                .await?;
+            self.get_relations(partition_consumer, replay_until(consumer_offset, committed))
+                .await?;
+        }
+        let stream = self
+            .common
+            .create_consumer_stream(consumer, "postgres", self.offsets.as_mut())
             .await?;
-        while let Some(Ok(next)) = stream.next().await {
-            let offset = next.offset;
-            let next = next.value();
+        let mut stream = self.shutdown.guard(stream);
+        while let Some(Ok(record)) = stream.next().await {
+            let offset = record.offset;
+            let next = record.value();
+            self.metrics.record_read(next.len());
             let event: ReplicationEvent = match serde_json::de::from_slice(next) {
                 Ok(next) => next,
                 Err(e) => {
                     tracing::error!("Error deseralizing ReplicationEvent {:?}", e);
+                    self.metrics.error(FORMAT_ERROR);
+                    if let Some(ref dead_letter) = self.dead_letter {
+                        dead_letter.send(&record, &anyhow::Error::from(e)).await?;
+                    }
+                    self.offsets
+                        .commit(&self.common.fluvio_topic, record.partition, offset)
+                        .await?;
                     continue;
                 }
             };
@@ -153,12 +214,49 @@ impl PgConnector {
                     tracing::error!("Uncaught replication message: {:?}", other);
                 }
             }
-            if !sql_statements.is_empty() {
-                let sql = format!("UPDATE fluvio.offset SET current_offset={offset} where id = 1");
-                sql_statements.push(sql);
+            if sql_statements.is_empty() {
+                self.offsets
+                    .commit(&self.common.fluvio_topic, record.partition, offset)
+                    .await?;
+            } else {
+                if self.offsets_in_db {
+                    sql_statements.push(PgOffsetStore::commit_statement(
+                        &self.common.fluvio_topic,
+                        record.partition,
+                        offset,
+                    ));
+                }
                 let batch = sql_statements.join(";");
                 tracing::info!("executing sql: {:?}", batch);
-                self.pg_client.batch_execute(&batch).await?;
+                let pg_client = &self.pg_client;
+                let batch = &batch;
+                let started = Instant::now();
+                let result = self
+                    .retrier
+                    .run(move || async move { Ok(pg_client.batch_execute(batch).await?) })
+                    .await;
+                if let Err(e) = result {
+                    self.metrics.record_failed(&e);
+                    self.health.record_failed(&e);
+                    let dead_letter = match self.dead_letter {
+                        Some(ref dead_letter) => dead_letter,
+                        None => return Err(e),
+                    };
+                    dead_letter.send(&record, &e).await?;
+                    // Still advance the offset so the failed event isn't replayed
+                    self.offsets
+                        .commit(&self.common.fluvio_topic, record.partition, offset)
+                        .await?;
+                } else {
+                    self.metrics
+                        .record_written(record.value().len(), started.elapsed());
+                    self.health.record_succeeded();
+                    if !self.offsets_in_db {
+                        self.offsets
+                            .commit(&self.common.fluvio_topic, record.partition, offset)
+                            .await?;
+                    }
+                }
             }
         }
         Ok(())
@@ -379,3 +477,14 @@ impl PgConnector {
         )
     }
 }
+
+/// Offset the cached relations are replayed up to, excluded. `None` replays every
+/// event already in the topic.
+fn replay_until(consumer_offset: ConsumerOffset, committed: Option<i64>) -> Option<i64> {
+    match consumer_offset {
+        ConsumerOffset::Beginning => Some(0),
+        ConsumerOffset::End => None,
+        ConsumerOffset::Absolute(offset) => Some(offset),
+        ConsumerOffset::Checkpoint => Some(committed.map_or(0, |offset| offset + 1)),
+    }
+}
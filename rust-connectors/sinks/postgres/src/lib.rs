@@ -1,4 +1,5 @@
 mod connect;
+mod offset;
 mod opt;
 
 pub use connect::PgConnector;
@@ -1,7 +1,7 @@
+use fluvio_connectors_common::opt::parse_connector_opts;
 use fluvio_connectors_common::{common_initialize, git_hash_version};
 use postgres_sink::{PgConnector, PgConnectorOpt};
 
-use clap::Parser;
 use schemars::schema_for;
 use tracing::info;
 
@@ -32,8 +32,9 @@ async fn main() -> anyhow::Result<()> {
         return Ok(());
     }
 
-    let config: PgConnectorOpt = PgConnectorOpt::from_args();
-    let mut connector = PgConnector::new(config).await?;
+    let config: PgConnectorOpt = parse_connector_opts();
+    let shutdown = config.common.install_shutdown_handler()?;
+    let mut connector = PgConnector::new(config).await?.with_shutdown(shutdown);
 
     info!(
         connector_version = env!("CARGO_PKG_VERSION"),
@@ -41,5 +42,6 @@ async fn main() -> anyhow::Result<()> {
         "Starting Postgres sink connector",
     );
     connector.process_stream().await?;
+    info!("Postgres sink connector stopped");
     Ok(())
 }
@@ -0,0 +1,69 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use fluvio_connectors_common::offset::OffsetStore;
+use tokio_postgres::Client;
+
+const CREATE_SCHEMA: &str = "CREATE SCHEMA IF NOT EXISTS fluvio";
+const CREATE_OFFSETS_TABLE: &str = "CREATE TABLE IF NOT EXISTS fluvio.offsets (\
+    topic TEXT NOT NULL, \
+    partition_id INT8 NOT NULL, \
+    current_offset INT8 NOT NULL, \
+    PRIMARY KEY (topic, partition_id))";
+/// Offset kept by previous versions of the connector, for partition 0 of its topic
+const MIGRATE_LEGACY_OFFSET: &str =
+    "INSERT INTO fluvio.offsets (topic, partition_id, current_offset) \
+    SELECT $1::TEXT, 0, current_offset FROM fluvio.offset WHERE id = 1 \
+    ON CONFLICT (topic, partition_id) DO NOTHING";
+const SELECT_OFFSET: &str =
+    "SELECT current_offset FROM fluvio.offsets WHERE topic = $1 AND partition_id = $2";
+
+/// Keeps committed offsets in the `fluvio.offsets` table of the target database.
+///
+/// The connection is shared with the connector, so offsets can be committed in the
+/// same batch as the statements of the record they describe.
+pub struct PgOffsetStore {
+    client: Arc<Client>,
+}
+
+impl PgOffsetStore {
+    pub async fn new(client: Arc<Client>, topic: &str) -> anyhow::Result<Self> {
+        client.batch_execute(CREATE_SCHEMA).await?;
+        client.batch_execute(CREATE_OFFSETS_TABLE).await?;
+        let legacy = client
+            .query_one("SELECT to_regclass('fluvio.offset') IS NOT NULL", &[])
+            .await?;
+        if legacy.get::<_, bool>(0) {
+            client.execute(MIGRATE_LEGACY_OFFSET, &[&topic]).await?;
+        }
+        Ok(Self { client })
+    }
+
+    /// Statement committing `offset`, to be executed along with the record statements
+    pub fn commit_statement(topic: &str, partition: u32, offset: i64) -> String {
+        format!(
+            "INSERT INTO fluvio.offsets (topic, partition_id, current_offset) \
+            VALUES ('{}', {partition}, {offset}) \
+            ON CONFLICT (topic, partition_id) DO UPDATE SET current_offset = excluded.current_offset",
+            topic.replace('\'', "''")
+        )
+    }
+}
+
+#[async_trait]
+impl OffsetStore for PgOffsetStore {
+    async fn load(&mut self, topic: &str, partition: u32) -> anyhow::Result<Option<i64>> {
+        let row = self
+            .client
+            .query_opt(SELECT_OFFSET, &[&topic, &i64::from(partition)])
+            .await?;
+        Ok(row.map(|row| row.get("current_offset")))
+    }
+
+    async fn commit(&mut self, topic: &str, partition: u32, offset: i64) -> anyhow::Result<()> {
+        self.client
+            .batch_execute(&Self::commit_statement(topic, partition, offset))
+            .await?;
+        Ok(())
+    }
+}
@@ -299,7 +299,7 @@ async fn cleanup(
     let _ = pg_source_client.execute(table_truncate, &[]).await?;
     let _ = pg_sink_client.execute(table_truncate, &[]).await?;
     let _ = pg_sink_client
-        .execute("DROP TABLE fluvio.offset", &[])
+        .execute("DROP TABLE fluvio.offsets", &[])
         .await?;
 
     Ok(())
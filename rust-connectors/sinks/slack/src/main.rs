@@ -1,14 +1,16 @@
 use clap::Parser;
 use fluvio_connectors_common::fluvio::Record;
 use fluvio_connectors_common::metrics::ConnectorMetrics;
-use fluvio_connectors_common::monitoring::init_monitoring;
-use fluvio_connectors_common::opt::CommonConnectorOpt;
+use fluvio_connectors_common::opt::{parse_connector_opts, CommonConnectorOpt};
+use fluvio_connectors_common::retry::permanent;
 use fluvio_connectors_common::{common_initialize, git_hash_version};
-use fluvio_future::tracing::{debug, info};
+use fluvio_future::tracing::{debug, error, info};
 use schemars::schema_for;
 use schemars::JsonSchema;
+use serde::Deserialize;
 use std::collections::HashMap;
 use std::sync::Arc;
+use std::time::Instant;
 use tokio_stream::StreamExt;
 
 #[tokio::main]
@@ -25,7 +27,7 @@ async fn main() -> anyhow::Result<()> {
         println!("{}", serde_json::to_string_pretty(&schema).unwrap());
         return Ok(());
     }
-    let opts: SlackOpt = SlackOpt::from_args();
+    let opts: SlackOpt = parse_connector_opts();
     opts.common.enable_logging();
     info!(
         connector_version = env!("CARGO_PKG_VERSION"),
@@ -36,31 +38,63 @@ async fn main() -> anyhow::Result<()> {
     Ok(())
 }
 
-#[derive(Parser, Debug, JsonSchema, Clone)]
+#[derive(Parser, Debug, JsonSchema, Clone, Deserialize)]
 pub struct SlackOpt {
     #[clap(long, env = "WEBHOOK_URL", hide_env_values = true)]
     pub webhook_url: String,
 
     #[clap(flatten)]
     #[schemars(flatten)]
+    #[serde(flatten)]
     pub common: CommonConnectorOpt,
 }
 
 impl SlackOpt {
     pub async fn execute(&self) -> anyhow::Result<()> {
+        let shutdown = self.common.install_shutdown_handler()?;
+        let health = self.common.health_common.create_health();
         let consumer = self.common.create_consumer().await?;
+        health.set_upstream_connected(true);
+        // Slack webhooks are stateless, only failed requests disconnect it
+        health.set_downstream_connected(true);
         let metrics = Arc::new(ConnectorMetrics::new(consumer.metrics()));
 
-        init_monitoring(metrics);
+        self.common.init_monitoring(metrics.clone());
 
-        let mut stream = self
+        let dead_letter = self
             .common
-            .create_consumer_stream(consumer, "slack")
+            .create_dead_letter_producer(
+                "slack",
+                self.common.dead_letter_topic.as_deref(),
+            )
             .await?;
+        let mut offsets = self.common.create_offset_store()?;
+        let mut retrier = self.common.retry_common.create_retrier();
+        let mut stream = shutdown.guard(
+            self.common
+                .create_consumer_stream(consumer, "slack", offsets.as_mut())
+                .await?,
+        );
         info!("Starting stream");
         while let Some(Ok(record)) = stream.next().await {
-            let _ = self.send_to_slack(&record).await;
+            metrics.record_read(record.value().len());
+            let started = Instant::now();
+            if let Err(err) = retrier.run(|| self.send_to_slack(&record)).await {
+                metrics.record_failed(&err);
+                health.record_failed(&err);
+                match dead_letter {
+                    Some(ref dead_letter) => dead_letter.send(&record, &err).await?,
+                    None => error!("{:?}", err),
+                }
+            } else {
+                metrics.record_written(record.value().len(), started.elapsed());
+                health.record_succeeded();
+            }
+            offsets
+                .commit(&self.common.fluvio_topic, record.partition, record.offset)
+                .await?;
         }
+        info!("Stream ended");
         Ok(())
     }
     pub async fn send_to_slack(&self, record: &Record) -> anyhow::Result<()> {
@@ -70,7 +104,16 @@ impl SlackOpt {
         map.insert("text", text);
 
         let client = reqwest::Client::new();
-        let _res = client.post(&self.webhook_url).json(&map).send().await?;
+        let response = client.post(&self.webhook_url).json(&map).send().await?;
+        let status = response.status();
+        response.error_for_status().map_err(|err| {
+            // Slack rejects the same payload again unless it is rate limiting us
+            if status.is_client_error() && status != reqwest::StatusCode::TOO_MANY_REQUESTS {
+                permanent(err)
+            } else {
+                err.into()
+            }
+        })?;
         Ok(())
     }
 }
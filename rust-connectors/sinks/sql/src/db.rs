@@ -1,4 +1,5 @@
 use anyhow::anyhow;
+use fluvio_connectors_common::retry::permanent;
 use fluvio_future::tracing::{debug, error};
 use fluvio_model_sql::{Operation, Type, Value};
 use itertools::Itertools;
@@ -84,7 +85,7 @@ where
             Ok(q) => q,
             Err(err) => {
                 error!("Unable to bind {:?}. Reason: {:?}", value, err);
-                return Err(err);
+                return Err(permanent(err));
             }
         }
     }
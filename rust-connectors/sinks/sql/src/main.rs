@@ -1,16 +1,20 @@
 use std::sync::Arc;
+use std::time::Instant;
 
 use fluvio_connectors_common::metrics::ConnectorMetrics;
-use fluvio_connectors_common::monitoring::init_monitoring;
+use fluvio_connectors_common::offset::{ConsumerOffset, OffsetStore};
+use fluvio_connectors_common::opt::parse_connector_opts;
+use fluvio_connectors_common::retry::permanent;
 use fluvio_future::tracing::{debug, info};
 
 use fluvio_model_sql::Operation;
+use futures::lock::Mutex;
 use futures::StreamExt;
 
-use clap::Parser;
 use fluvio_connectors_common::git_hash_version;
 use schemars::schema_for;
 use sql_sink::db::Db;
+use sql_sink::offset::SqlOffsetStore;
 use sql_sink::opt::SqlConnectorOpt;
 
 #[async_std::main]
@@ -26,25 +30,49 @@ async fn main() -> anyhow::Result<()> {
         println!("{}", serde_json::to_string_pretty(&schema).unwrap());
         return Ok(());
     }
-    let raw_opts = SqlConnectorOpt::from_args();
+    let raw_opts: SqlConnectorOpt = parse_connector_opts();
     raw_opts.common.enable_logging();
+    let shutdown = raw_opts.common.install_shutdown_handler()?;
+    let health = raw_opts.common.health_common.create_health();
     info!(
         connector_version = env!("CARGO_PKG_VERSION"),
         git_hash = git_hash_version(),
         "starting JSON SQL sink connector",
     );
-    let mut db = Db::connect(raw_opts.database_url.as_str()).await?;
+    let db = Db::connect(raw_opts.database_url.as_str()).await?;
     info!("connected to database {}", db.kind());
+    health.set_downstream_connected(true);
+    // the retried operation needs exclusive access to the connection on every attempt
+    let db = Mutex::new(db);
 
     let consumer = raw_opts.common.create_consumer().await?;
+    health.set_upstream_connected(true);
     let metrics = Arc::new(ConnectorMetrics::new(consumer.metrics()));
 
-    init_monitoring(metrics);
+    raw_opts.common.init_monitoring(metrics.clone());
 
-    let mut stream = raw_opts
+    let dead_letter = raw_opts
         .common
-        .create_consumer_stream(consumer, "sql")
+        .create_dead_letter_producer(
+            "sql",
+            raw_opts.common.dead_letter_topic.as_deref(),
+        )
         .await?;
+    // the `fluvio_offsets` table is only created when checkpoints are kept in the database
+    let consumer_common = &raw_opts.common.consumer_common;
+    let mut offsets: Box<dyn OffsetStore> = match consumer_common.consumer_offset_path {
+        None if consumer_common.offset() == ConsumerOffset::Checkpoint => {
+            Box::new(SqlOffsetStore::connect(raw_opts.database_url.as_str()).await?)
+        }
+        _ => raw_opts.common.create_offset_store()?,
+    };
+    let mut retrier = raw_opts.common.retry_common.create_retrier();
+    let mut stream = shutdown.guard(
+        raw_opts
+            .common
+            .create_consumer_stream(consumer, "sql", offsets.as_mut())
+            .await?,
+    );
     info!("connected to fluvio stream");
 
     info!(
@@ -52,10 +80,42 @@ async fn main() -> anyhow::Result<()> {
         raw_opts.common.fluvio_topic
     );
     while let Some(Ok(consumer_record)) = stream.next().await {
-        let operation: Operation = serde_json::from_slice(consumer_record.as_ref())?;
-        debug!("{:?}", operation);
-        db.execute(operation).await?;
+        let db = &db;
+        let record = consumer_record.as_ref();
+        metrics.record_read(record.len());
+        let started = Instant::now();
+        let result = retrier
+            .run(move || async move { process_record(&mut *db.lock().await, record).await })
+            .await;
+        match result {
+            Ok(()) => {
+                metrics.record_written(record.len(), started.elapsed());
+                health.record_succeeded();
+            }
+            Err(err) => {
+                metrics.record_failed(&err);
+                health.record_failed(&err);
+                match dead_letter {
+                    Some(ref dead_letter) => dead_letter.send(&consumer_record, &err).await?,
+                    None => return Err(err),
+                }
+            }
+        }
+        offsets
+            .commit(
+                &raw_opts.common.fluvio_topic,
+                consumer_record.partition,
+                consumer_record.offset,
+            )
+            .await?;
     }
+    info!("stream processing ended");
 
     Ok(())
 }
+
+async fn process_record(db: &mut Db, record: &[u8]) -> anyhow::Result<()> {
+    let operation: Operation = serde_json::from_slice(record).map_err(permanent)?;
+    debug!("{:?}", operation);
+    db.execute(operation).await
+}
@@ -0,0 +1,117 @@
+use async_trait::async_trait;
+use fluvio_connectors_common::offset::OffsetStore;
+use sqlx::Executor;
+
+use crate::db::Db;
+
+const CREATE_OFFSETS_TABLE: &str = r#"CREATE TABLE IF NOT EXISTS fluvio_offsets (
+    topic TEXT NOT NULL,
+    partition_id BIGINT NOT NULL,
+    current_offset BIGINT NOT NULL,
+    PRIMARY KEY (topic, partition_id)
+)"#;
+
+const PG_SELECT_OFFSET: &str =
+    "SELECT current_offset FROM fluvio_offsets WHERE topic = $1 AND partition_id = $2";
+const PG_UPSERT_OFFSET: &str = "INSERT INTO fluvio_offsets (topic, partition_id, current_offset) \
+    VALUES ($1, $2, $3) \
+    ON CONFLICT (topic, partition_id) DO UPDATE SET current_offset = excluded.current_offset";
+
+const SQLITE_SELECT_OFFSET: &str =
+    "SELECT current_offset FROM fluvio_offsets WHERE topic = ? AND partition_id = ?";
+const SQLITE_UPSERT_OFFSET: &str =
+    "INSERT INTO fluvio_offsets (topic, partition_id, current_offset) \
+    VALUES (?, ?, ?) \
+    ON CONFLICT (topic, partition_id) DO UPDATE SET current_offset = excluded.current_offset";
+
+/// Keeps committed offsets in the `fluvio_offsets` table of the target database.
+///
+/// A dedicated connection is used, so offsets live next to the data they describe
+/// without interfering with the connection the records are written with.
+pub struct SqlOffsetStore {
+    db: Db,
+}
+
+impl SqlOffsetStore {
+    pub async fn connect(url: &str) -> anyhow::Result<Self> {
+        let mut db = Db::connect(url).await?;
+        match db {
+            Db::Postgres(ref mut conn) => {
+                conn.execute(CREATE_OFFSETS_TABLE).await?;
+            }
+            Db::Sqlite(ref mut conn) => {
+                conn.execute(CREATE_OFFSETS_TABLE).await?;
+            }
+        };
+        Ok(Self { db })
+    }
+}
+
+#[async_trait]
+impl OffsetStore for SqlOffsetStore {
+    async fn load(&mut self, topic: &str, partition: u32) -> anyhow::Result<Option<i64>> {
+        let offset = match self.db {
+            Db::Postgres(ref mut conn) => {
+                sqlx::query_scalar(PG_SELECT_OFFSET)
+                    .bind(topic)
+                    .bind(i64::from(partition))
+                    .fetch_optional(conn.as_mut())
+                    .await?
+            }
+            Db::Sqlite(ref mut conn) => {
+                sqlx::query_scalar(SQLITE_SELECT_OFFSET)
+                    .bind(topic)
+                    .bind(i64::from(partition))
+                    .fetch_optional(conn.as_mut())
+                    .await?
+            }
+        };
+        Ok(offset)
+    }
+
+    async fn commit(&mut self, topic: &str, partition: u32, offset: i64) -> anyhow::Result<()> {
+        match self.db {
+            Db::Postgres(ref mut conn) => {
+                sqlx::query(PG_UPSERT_OFFSET)
+                    .bind(topic)
+                    .bind(i64::from(partition))
+                    .bind(offset)
+                    .execute(conn.as_mut())
+                    .await?;
+            }
+            Db::Sqlite(ref mut conn) => {
+                sqlx::query(SQLITE_UPSERT_OFFSET)
+                    .bind(topic)
+                    .bind(i64::from(partition))
+                    .bind(offset)
+                    .execute(conn.as_mut())
+                    .await?;
+            }
+        };
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[async_std::test]
+    async fn test_offsets_sqlite() -> anyhow::Result<()> {
+        //given
+        let mut store = SqlOffsetStore::connect("sqlite::memory:").await?;
+
+        //when
+        let initial = store.load("topic", 0).await?;
+        store.commit("topic", 0, 10).await?;
+        store.commit("topic", 1, 20).await?;
+        store.commit("topic", 0, 11).await?;
+
+        //then
+        assert_eq!(initial, None);
+        assert_eq!(store.load("topic", 0).await?, Some(11));
+        assert_eq!(store.load("topic", 1).await?, Some(20));
+        assert_eq!(store.load("other", 0).await?, None);
+        Ok(())
+    }
+}
@@ -0,0 +1,321 @@
+//! Authentication of the requests: basic, static bearer token and OAuth2 access tokens
+
+use std::fmt;
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+
+use clap::Parser;
+use fluvio_connectors_common::de;
+use humantime::parse_duration;
+use reqwest::header::CONTENT_TYPE;
+use reqwest::RequestBuilder;
+use schemars::JsonSchema;
+use serde::Deserialize;
+use serde_json::Value;
+use tokio::sync::Mutex;
+
+use crate::error::{Error, Result};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, JsonSchema)]
+pub enum AuthKind {
+    None,
+    /// `Authorization: Basic` with `auth_username` and `auth_password`
+    Basic,
+    /// `Authorization: Bearer` with the static `auth_token`
+    Bearer,
+    /// `Authorization: Bearer` with an access token from `auth_token_url`, fetched
+    /// again before it expires
+    OAuth2,
+}
+
+impl FromStr for AuthKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "none" => Ok(Self::None),
+            "basic" => Ok(Self::Basic),
+            "bearer" => Ok(Self::Bearer),
+            "oauth2" => Ok(Self::OAuth2),
+            _ => Err(format!(
+                "unknown auth {s}, expected none, basic, bearer or oauth2"
+            )),
+        }
+    }
+}
+
+impl fmt::Display for AuthKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let kind = match self {
+            Self::None => "none",
+            Self::Basic => "basic",
+            Self::Bearer => "bearer",
+            Self::OAuth2 => "oauth2",
+        };
+        f.write_str(kind)
+    }
+}
+
+#[derive(Parser, Debug, JsonSchema, Clone, Deserialize)]
+pub struct HttpAuthOpt {
+    /// Authentication of the requests: none | basic | bearer | oauth2
+    #[clap(long, default_value = "none")]
+    #[serde(deserialize_with = "de::from_str")]
+    pub auth: AuthKind,
+
+    /// User name of `basic` auth
+    #[clap(long, env = "FLUVIO_HTTP_AUTH_USERNAME", hide_env_values = true)]
+    pub auth_username: Option<String>,
+
+    /// Password of `basic` auth
+    #[clap(long, env = "FLUVIO_HTTP_AUTH_PASSWORD", hide_env_values = true)]
+    pub auth_password: Option<String>,
+
+    /// Token of `bearer` auth
+    #[clap(long, env = "FLUVIO_HTTP_AUTH_TOKEN", hide_env_values = true)]
+    pub auth_token: Option<String>,
+
+    /// Token endpoint of `oauth2` auth
+    #[clap(long)]
+    pub auth_token_url: Option<String>,
+
+    /// Client ID of the `oauth2` client credentials grant
+    #[clap(long, env = "FLUVIO_HTTP_AUTH_CLIENT_ID", hide_env_values = true)]
+    pub auth_client_id: Option<String>,
+
+    /// Client secret of the `oauth2` client credentials grant
+    #[clap(long, env = "FLUVIO_HTTP_AUTH_CLIENT_SECRET", hide_env_values = true)]
+    pub auth_client_secret: Option<String>,
+
+    /// Scope requested with the `oauth2` client credentials grant
+    #[clap(long)]
+    pub auth_scope: Option<String>,
+
+    /// Body posted to `auth_token_url` instead of the client credentials grant, for
+    /// custom token endpoints. Sent as JSON if it is valid JSON, as a form otherwise
+    #[clap(long, env = "FLUVIO_HTTP_AUTH_TOKEN_BODY", hide_env_values = true)]
+    pub auth_token_body: Option<String>,
+
+    /// JSON pointer to the access token in the token endpoint response
+    #[clap(long, default_value = "/access_token")]
+    pub auth_token_pointer: String,
+
+    /// JSON pointer to the lifetime of the access token in seconds, in the token
+    /// endpoint response. Tokens without lifetime are used until rejected
+    #[clap(long, default_value = "/expires_in")]
+    pub auth_expires_pointer: String,
+
+    /// How long before it expires the access token is fetched again
+    /// Ex: '30s', '5m'
+    #[clap(long, parse(try_from_str = parse_duration), default_value = "60s")]
+    #[serde(deserialize_with = "de::duration")]
+    pub auth_refresh_before: Duration,
+}
+
+#[derive(Debug)]
+struct AccessToken {
+    value: String,
+    refresh_at: Option<Instant>,
+}
+
+/// Adds credentials to the requests, keeping the current OAuth2 access token
+#[derive(Debug)]
+pub struct Authenticator {
+    opts: HttpAuthOpt,
+    client: reqwest::Client,
+    token: Mutex<Option<AccessToken>>,
+}
+
+impl Authenticator {
+    pub fn new(opts: HttpAuthOpt, client: reqwest::Client) -> Result<Self> {
+        let missing = match opts.auth {
+            AuthKind::Basic if opts.auth_username.is_none() => Some("auth_username"),
+            AuthKind::Bearer if opts.auth_token.is_none() => Some("auth_token"),
+            AuthKind::OAuth2 if opts.auth_token_url.is_none() => Some("auth_token_url"),
+            AuthKind::OAuth2 if opts.auth_token_body.is_none() && opts.auth_client_id.is_none() => {
+                Some("auth_client_id")
+            }
+            _ => None,
+        };
+        if let Some(missing) = missing {
+            return Err(Error::Auth(format!(
+                "{} auth requires {missing}",
+                opts.auth
+            )));
+        }
+        Ok(Self {
+            opts,
+            client,
+            token: Mutex::new(None),
+        })
+    }
+
+    pub async fn authenticate(&self, req: RequestBuilder) -> Result<RequestBuilder> {
+        let req = match self.opts.auth {
+            AuthKind::None => req,
+            AuthKind::Basic => req.basic_auth(
+                self.opts.auth_username.as_deref().unwrap_or_default(),
+                self.opts.auth_password.as_deref(),
+            ),
+            AuthKind::Bearer => {
+                req.bearer_auth(self.opts.auth_token.as_deref().unwrap_or_default())
+            }
+            AuthKind::OAuth2 => req.bearer_auth(self.access_token().await?),
+        };
+        Ok(req)
+    }
+
+    /// Drops the current access token after it got rejected. Returns whether a new
+    /// one will be fetched for the next request.
+    pub async fn invalidate(&self) -> bool {
+        self.token.lock().await.take().is_some()
+    }
+
+    async fn access_token(&self) -> Result<String> {
+        let mut token = self.token.lock().await;
+        if let Some(ref token) = *token {
+            match token.refresh_at {
+                Some(refresh_at) if Instant::now() >= refresh_at => {}
+                _ => return Ok(token.value.clone()),
+            }
+        }
+        let fetched = self.fetch_token().await?;
+        let value = fetched.value.clone();
+        *token = Some(fetched);
+        Ok(value)
+    }
+
+    async fn fetch_token(&self) -> Result<AccessToken> {
+        let token_url = self.opts.auth_token_url.as_deref().unwrap_or_default();
+        tracing::debug!(%token_url, "Fetching access token");
+
+        let req = self.client.post(token_url);
+        let req = match self.opts.auth_token_body {
+            Some(ref body) if serde_json::from_str::<Value>(body).is_ok() => req
+                .header(CONTENT_TYPE, "application/json")
+                .body(body.clone()),
+            Some(ref body) => req
+                .header(CONTENT_TYPE, "application/x-www-form-urlencoded")
+                .body(body.clone()),
+            None => {
+                let mut form = vec![("grant_type", "client_credentials")];
+                form.extend(
+                    self.opts
+                        .auth_client_id
+                        .as_deref()
+                        .map(|id| ("client_id", id)),
+                );
+                form.extend(
+                    self.opts
+                        .auth_client_secret
+                        .as_deref()
+                        .map(|secret| ("client_secret", secret)),
+                );
+                form.extend(
+                    self.opts
+                        .auth_scope
+                        .as_deref()
+                        .map(|scope| ("scope", scope)),
+                );
+                req.form(&form)
+            }
+        };
+
+        let response = req
+            .send()
+            .await
+            .and_then(reqwest::Response::error_for_status)
+            .map_err(Error::TokenRequest)?;
+        let body = response.bytes().await.map_err(Error::TokenRequest)?;
+        let response: Value = serde_json::from_slice(&body)
+            .map_err(|e| Error::Auth(format!("invalid token endpoint response: {e}")))?;
+        let token = parse_token(
+            &response,
+            &self.opts.auth_token_pointer,
+            &self.opts.auth_expires_pointer,
+            self.opts.auth_refresh_before,
+        )?;
+        tracing::info!(expires_at = ?token.refresh_at, "Fetched access token");
+        Ok(token)
+    }
+}
+
+fn parse_token(
+    response: &Value,
+    token_pointer: &str,
+    expires_pointer: &str,
+    refresh_before: Duration,
+) -> Result<AccessToken> {
+    let value = response
+        .pointer(token_pointer)
+        .and_then(Value::as_str)
+        .ok_or_else(|| {
+            Error::Auth(format!(
+                "no access token at {token_pointer} in the response"
+            ))
+        })?;
+    let lifetime = response
+        .pointer(expires_pointer)
+        .and_then(|expires| match expires {
+            Value::Number(seconds) => seconds.as_u64(),
+            Value::String(seconds) => seconds.parse().ok(),
+            _ => None,
+        });
+    Ok(AccessToken {
+        value: value.to_string(),
+        refresh_at: lifetime.map(|seconds| {
+            Instant::now() + Duration::from_secs(seconds).saturating_sub(refresh_before)
+        }),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_token() {
+        //given
+        let response = serde_json::json!({
+            "access_token": "abc",
+            "token_type": "Bearer",
+            "expires_in": 3600
+        });
+        let custom_response = serde_json::json!({"data": {"token": "def", "ttl": "30"}});
+
+        //when
+        let token = parse_token(
+            &response,
+            "/access_token",
+            "/expires_in",
+            Duration::from_secs(60),
+        )
+        .unwrap();
+        let custom = parse_token(
+            &custom_response,
+            "/data/token",
+            "/data/ttl",
+            Duration::from_secs(60),
+        )
+        .unwrap();
+        let without_lifetime = parse_token(
+            &custom_response,
+            "/data/token",
+            "/expires_in",
+            Duration::ZERO,
+        )
+        .unwrap();
+
+        //then
+        assert_eq!(token.value, "abc");
+        let refresh_in = token.refresh_at.unwrap() - Instant::now();
+        assert!(refresh_in > Duration::from_secs(3530) && refresh_in <= Duration::from_secs(3540));
+        assert_eq!(custom.value, "def");
+        assert!(custom.refresh_at.unwrap() <= Instant::now());
+        assert_eq!(without_lifetime.refresh_at, None);
+        assert!(matches!(
+            parse_token(&response, "/token", "/expires_in", Duration::ZERO),
+            Err(Error::Auth(_))
+        ));
+    }
+}
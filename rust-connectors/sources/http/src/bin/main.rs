@@ -1,20 +1,20 @@
-// Techdebt: Granular errors
-#![allow(clippy::redundant_closure)]
-
 use std::sync::Arc;
 
-use fluvio_connectors_common::fluvio::RecordKey;
+use fluvio_connectors_common::dead_letter::DeadLetterProducer;
+use fluvio_connectors_common::fluvio::TopicProducer;
 use fluvio_connectors_common::git_hash_version;
 use fluvio_connectors_common::metrics::ConnectorMetrics;
-use fluvio_connectors_common::monitoring::init_monitoring;
-use tokio_stream::StreamExt;
 
 type Result<T, E = Box<dyn std::error::Error + Send + Sync + 'static>> = core::result::Result<T, E>;
 
 use ::http_source::HttpOpt;
 
-use ::http_source::error::Error;
+use ::http_source::endpoints::EndpointConfig;
+use ::http_source::source::HttpSource;
+use ::http_source::webhook::WebhookSource;
+use fluvio_connectors_common::health::ConnectorHealth;
 use fluvio_connectors_common::opt::GetOpts;
+use fluvio_connectors_common::shutdown::ShutdownSignal;
 
 #[tokio::main]
 async fn main() -> Result<()> {
@@ -30,6 +30,8 @@ async fn main() -> Result<()> {
         std::env::set_var("RUST_LOG", "http=info");
     }
 
+    opts.validate()?;
+
     // Error on deprecated metadata options
     if opts.output_format.is_some() {
         panic!("ERROR: output_format has been deprecated and renamed as output_parts");
@@ -47,53 +49,139 @@ async fn main() -> Result<()> {
         topic = %opts.common.fluvio_topic,
         output_parts = %opts.output_parts,
         output_type = %opts.output_type,
-        endpoint = %opts.endpoint
+        endpoint = ?opts.endpoint,
+        stream = opts.stream,
+        webhook_addr = ?opts.webhook.webhook_addr,
+        endpoints = opts.endpoints.len()
     );
 
-    let timer = tokio::time::interval(opts.interval);
-    let mut timer_stream = tokio_stream::wrappers::IntervalStream::new(timer);
+    let shutdown = opts.common.install_shutdown_handler()?;
+    let health = opts.common.health_common.create_health();
+    if !opts.endpoints.is_empty() {
+        return run_endpoints(opts, health, shutdown).await;
+    }
+
     let producer = opts
         .common
         .create_producer("http")
         .await
         .expect("Failed to create producer");
     tracing::info!("Connected to Fluvio");
+    health.set_downstream_connected(true);
 
     let metrics = Arc::new(ConnectorMetrics::new(producer.metrics()));
-    init_monitoring(metrics);
+    opts.common.init_monitoring(metrics.clone());
 
-    let client = reqwest::Client::new();
-    let method: reqwest::Method = opts.method.parse()?;
-
-    while timer_stream.next().await.is_some() {
-        let mut req = client.request(method.clone(), &opts.endpoint);
-        req = req.header("user-agent", opts.user_agent.clone());
-
-        let headers = opts.headers.iter().flat_map(|h| h.split_once(':'));
-        for (key, value) in headers {
-            req = req.header(key, value);
-        }
-
-        if let Some(ref body) = opts.body {
-            req = req.body(body.clone());
-        }
-        let response = req.send().await.map_err(|e| Error::Request(e))?;
-
-        let mut formatter = ::http_source::formatter::HttpResponseRecord::try_from(&response)
-            .map_err(|e| Error::Record(e))?;
-
-        formatter
-            .configure_output(&opts.output_type, &opts.output_parts)
-            .expect("Unable to configure output type/parts");
+    if opts.webhook.webhook_addr.is_some() {
+        WebhookSource::new(opts, producer, metrics, health, shutdown)?
+            .run()
+            .await?;
+        return Ok(());
+    }
 
-        let response_body = response.text().await.map_err(|e| Error::ResponseBody(e))?;
+    let dead_letter = opts
+        .common
+        .create_dead_letter_producer("http", opts.common.dead_letter_topic.as_deref())
+        .await?;
+    create_source(opts, producer, dead_letter, metrics, health, shutdown)?
+        .run()
+        .await?;
+    Ok(())
+}
 
-        let record_out = formatter.record(Some(&response_body));
+fn create_source(
+    opts: HttpOpt,
+    producer: TopicProducer,
+    dead_letter: Option<DeadLetterProducer>,
+    metrics: Arc<ConnectorMetrics>,
+    health: Arc<ConnectorHealth>,
+    shutdown: ShutdownSignal,
+) -> Result<HttpSource> {
+    let mut source = HttpSource::new(opts, producer, metrics, health, shutdown)?;
+    if let Some(dead_letter) = dead_letter {
+        source = source.with_dead_letter(dead_letter);
+    }
+    Ok(source)
+}
 
-        tracing::debug!(%record_out, "Producing");
+/// Polls every endpoint of `endpoints` concurrently, each with its own producer, metrics
+/// and health, through one Fluvio connection. An endpoint failing doesn't stop the others.
+async fn run_endpoints(
+    opts: HttpOpt,
+    health: Arc<ConnectorHealth>,
+    shutdown: ShutdownSignal,
+) -> Result<()> {
+    EndpointConfig::check_names(&opts.endpoints)?;
 
-        producer.send(RecordKey::NULL, record_out).await?;
+    let fluvio = opts
+        .common
+        .connect_fluvio("http")
+        .await
+        .expect("Failed to connect to Fluvio");
+    let mut metrics = None;
+    let mut sources = Vec::with_capacity(opts.endpoints.len());
+    for endpoint in &opts.endpoints {
+        let endpoint_opts = endpoint.opts(&opts);
+        tracing::info!(
+            endpoint = endpoint.name(),
+            topic = %endpoint_opts.common.fluvio_topic,
+            interval = ?endpoint_opts.interval,
+            method = %endpoint_opts.method,
+        );
+        let producer = endpoint_opts
+            .common
+            .create_topic_producer(&fluvio)
+            .await
+            .expect("Failed to create producer");
+        // the producers share the client, so its metrics are the ones of every endpoint
+        let metrics = metrics.get_or_insert_with(|| {
+            let metrics = Arc::new(ConnectorMetrics::new(producer.metrics()));
+            opts.common.init_monitoring(metrics.clone());
+            metrics
+        });
+        let endpoint_metrics = Arc::new(metrics.endpoint(endpoint.name()));
+        let endpoint_health = health.endpoint(endpoint.name());
+        endpoint_health.set_downstream_connected(true);
+        let dead_letter = endpoint_opts
+            .common
+            .create_dead_letter_topic_producer(
+                &fluvio,
+                endpoint_opts.common.dead_letter_topic.as_deref(),
+            )
+            .await?;
+        let source = create_source(
+            endpoint_opts,
+            producer,
+            dead_letter,
+            endpoint_metrics,
+            endpoint_health,
+            shutdown.clone(),
+        )?;
+        sources.push((endpoint.name().to_string(), source));
+    }
+    tracing::info!("Connected to Fluvio");
+    health.set_downstream_connected(true);
+
+    let tasks: Vec<_> = sources
+        .into_iter()
+        .map(|(name, source)| (name, tokio::spawn(async move { source.run().await })))
+        .collect();
+    let mut failed = 0;
+    for (name, task) in tasks {
+        match task.await {
+            Ok(Ok(())) => {}
+            Ok(Err(err)) => {
+                tracing::error!(endpoint = %name, ?err, "Endpoint stopped");
+                failed += 1;
+            }
+            Err(err) => {
+                tracing::error!(endpoint = %name, %err, "Endpoint task failed");
+                failed += 1;
+            }
+        }
+    }
+    if failed > 0 {
+        return Err(format!("{failed} of {} endpoints failed", opts.endpoints.len()).into());
     }
-
     Ok(())
 }
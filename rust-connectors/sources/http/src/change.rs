@@ -0,0 +1,277 @@
+//! Change detection: responses are only produced when the polled resource changed
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::{Mutex, MutexGuard, PoisonError};
+
+use fluvio_connectors_common::fs::write_atomic;
+use reqwest::header::{
+    HeaderMap, HeaderName, ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED,
+};
+use reqwest::{RequestBuilder, StatusCode, Url};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+/// How a response is compared with the last produced one
+#[derive(Debug, Clone, Copy, PartialEq, Eq, JsonSchema)]
+pub enum ChangeDetection {
+    /// Every response is produced
+    None,
+    /// `If-None-Match` and `If-Modified-Since` requests, unchanged when the server
+    /// answers 304 or the same `ETag` or `Last-Modified`
+    Conditional,
+    /// Unchanged when the SHA-256 of the body is the same
+    Hash,
+}
+
+impl FromStr for ChangeDetection {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "none" => Ok(Self::None),
+            "conditional" => Ok(Self::Conditional),
+            "hash" => Ok(Self::Hash),
+            _ => Err(format!(
+                "unknown change detection {s}, expected none, conditional or hash"
+            )),
+        }
+    }
+}
+
+impl fmt::Display for ChangeDetection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let detection = match self {
+            Self::None => "none",
+            Self::Conditional => "conditional",
+            Self::Hash => "hash",
+        };
+        f.write_str(detection)
+    }
+}
+
+/// What identifies the version of a resource that was last produced
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Validators {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub etag: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_modified: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub body_hash: Option<String>,
+    /// Page following the response, walked on when the server answers 304
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub next_url: Option<String>,
+}
+
+impl Validators {
+    fn same_version(&self, other: &Self) -> bool {
+        self.etag == other.etag
+            && self.last_modified == other.last_modified
+            && self.body_hash == other.body_hash
+    }
+}
+
+/// Keeps the validators of the last produced response of each page of the endpoint, in
+/// a local JSON file if a path is given. Pages are keyed by the endpoint before its
+/// templates are rendered, so templated URLs don't add a key on every poll.
+#[derive(Debug)]
+pub struct ChangeDetector {
+    detection: ChangeDetection,
+    path: Option<PathBuf>,
+    validators: Mutex<BTreeMap<String, Validators>>,
+    /// Held while the file is written, so writes happen in the order of the commits
+    file: tokio::sync::Mutex<()>,
+}
+
+impl ChangeDetector {
+    pub fn open(detection: ChangeDetection, path: Option<PathBuf>) -> std::io::Result<Self> {
+        let validators = match path {
+            Some(ref path) if detection != ChangeDetection::None => match std::fs::read(path) {
+                Ok(contents) => serde_json::from_slice(&contents)?,
+                Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+                    tracing::info!(
+                        "change detection file {} not found, starting without validators",
+                        path.display()
+                    );
+                    BTreeMap::new()
+                }
+                Err(err) => return Err(err),
+            },
+            _ => BTreeMap::new(),
+        };
+        Ok(Self {
+            detection,
+            path,
+            validators: Mutex::new(validators),
+            file: tokio::sync::Mutex::new(()),
+        })
+    }
+
+    fn validators(&self) -> MutexGuard<'_, BTreeMap<String, Validators>> {
+        self.validators
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Makes the request conditional on the last produced version of the page `key`
+    pub fn conditional_request(&self, key: &str, mut req: RequestBuilder) -> RequestBuilder {
+        if self.detection != ChangeDetection::Conditional {
+            return req;
+        }
+        if let Some(last) = self.validators().get(key) {
+            if let Some(ref etag) = last.etag {
+                req = req.header(IF_NONE_MATCH, etag);
+            }
+            if let Some(ref last_modified) = last.last_modified {
+                req = req.header(IF_MODIFIED_SINCE, last_modified);
+            }
+        }
+        req
+    }
+
+    /// Validators of the response for the page `key`, or `None` if the resource didn't
+    /// change since the last committed response
+    pub fn check(
+        &self,
+        key: &str,
+        status: StatusCode,
+        headers: &HeaderMap,
+        body: &[u8],
+    ) -> Option<Validators> {
+        let validators = match self.detection {
+            ChangeDetection::None => return Some(Validators::default()),
+            ChangeDetection::Conditional if status == StatusCode::NOT_MODIFIED => return None,
+            ChangeDetection::Conditional => Validators {
+                etag: header_value(headers, ETAG),
+                last_modified: header_value(headers, LAST_MODIFIED),
+                ..Default::default()
+            },
+            ChangeDetection::Hash => Validators {
+                body_hash: Some(hex::encode(Sha256::digest(body))),
+                ..Default::default()
+            },
+        };
+
+        // servers ignoring conditional requests still answer with the same validators
+        let unchanged = validators != Validators::default()
+            && self
+                .validators()
+                .get(key)
+                .into_iter()
+                .any(|last| last.same_version(&validators));
+        if unchanged {
+            None
+        } else {
+            Some(validators)
+        }
+    }
+
+    /// Page following the last committed response for the page `key`, for a 304
+    /// response without the body or headers the next page is read from
+    pub fn next_url(&self, key: &str) -> Option<Url> {
+        let next_url = self.validators().get(key)?.next_url.clone()?;
+        Url::parse(&next_url).ok()
+    }
+
+    /// Records the validators of the response for the page `key` once it has been
+    /// produced, along with the page following it
+    pub async fn commit(
+        &self,
+        key: &str,
+        mut validators: Validators,
+        next_url: Option<&Url>,
+    ) -> std::io::Result<()> {
+        if self.detection == ChangeDetection::None {
+            return Ok(());
+        }
+        validators.next_url = next_url.map(Url::to_string);
+        let path = match self.path {
+            Some(ref path) => path,
+            None => {
+                self.validators().insert(key.to_string(), validators);
+                return Ok(());
+            }
+        };
+        let _file = self.file.lock().await;
+        let contents = {
+            let mut all_validators = self.validators();
+            all_validators.insert(key.to_string(), validators);
+            serde_json::to_vec(&*all_validators)?
+        };
+        write_atomic(path, contents).await
+    }
+}
+
+fn header_value(headers: &HeaderMap, name: HeaderName) -> Option<String> {
+    headers
+        .get(name)
+        .and_then(|value| value.to_str().ok())
+        .map(str::to_owned)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_conditional_change_detection() {
+        //given
+        let changes = ChangeDetector::open(ChangeDetection::Conditional, None).unwrap();
+        let key = "https://api.github.com/repos/infinyon/fluvio";
+        let mut headers = HeaderMap::new();
+        headers.insert(ETAG, "\"v1\"".parse().unwrap());
+
+        //when
+        let first = changes.check(key, StatusCode::OK, &headers, b"{}");
+        changes
+            .commit(key, first.clone().unwrap(), None)
+            .await
+            .unwrap();
+        let same_etag = changes.check(key, StatusCode::OK, &headers, b"{}");
+        let not_modified = changes.check(key, StatusCode::NOT_MODIFIED, &HeaderMap::new(), b"");
+        let without_validators = changes.check(key, StatusCode::OK, &HeaderMap::new(), b"{}");
+
+        //then
+        assert_eq!(first.unwrap().etag.as_deref(), Some("\"v1\""));
+        assert_eq!(same_etag, None);
+        assert_eq!(not_modified, None);
+        assert_eq!(without_validators, Some(Validators::default()));
+    }
+
+    #[tokio::test]
+    async fn test_hash_change_detection_persisted() {
+        //given
+        let path = std::env::temp_dir().join(format!(
+            "fluvio-http-source-changes-{}.json",
+            std::process::id()
+        ));
+        let _ = std::fs::remove_file(&path);
+        let key = "https://api.github.com/repos/{{ env:REPO }}";
+        let changes = ChangeDetector::open(ChangeDetection::Hash, Some(path.clone())).unwrap();
+
+        //when
+        let first = changes.check(key, StatusCode::OK, &HeaderMap::new(), b"42");
+        let next = Url::parse("https://api.github.com/repos/infinyon/fluvio?page=2").unwrap();
+        changes
+            .commit(key, first.clone().unwrap(), Some(&next))
+            .await
+            .unwrap();
+        let restarted = ChangeDetector::open(ChangeDetection::Hash, Some(path.clone())).unwrap();
+
+        //then
+        assert!(first.is_some());
+        assert_eq!(
+            restarted.check(key, StatusCode::OK, &HeaderMap::new(), b"42"),
+            None
+        );
+        assert!(restarted
+            .check(key, StatusCode::OK, &HeaderMap::new(), b"43")
+            .is_some());
+        assert_eq!(restarted.next_url(key), Some(next));
+        let _ = std::fs::remove_file(&path);
+    }
+}
@@ -0,0 +1,378 @@
+//! TLS and proxy configuration of the client the requests are sent with
+
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::SystemTime;
+
+use clap::Parser;
+use reqwest::{Certificate, ClientBuilder, Identity, Proxy};
+use rustls::client::{ServerCertVerified, ServerCertVerifier, WebPkiVerifier};
+use rustls::{PrivateKey, RootCertStore, ServerName};
+use schemars::JsonSchema;
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+
+use crate::error::{Error, Result};
+
+#[derive(Parser, Debug, JsonSchema, Clone, Deserialize)]
+pub struct HttpClientOpt {
+    /// PEM file of the CA certificates the server certificates are verified with,
+    /// along with the system ones
+    #[clap(long)]
+    pub tls_ca_cert: Option<PathBuf>,
+
+    /// PEM CA certificates, instead of the `tls_ca_cert` file
+    #[clap(
+        long,
+        env = "FLUVIO_HTTP_TLS_CA_CERT",
+        hide_env_values = true,
+        conflicts_with = "tls-ca-cert"
+    )]
+    pub tls_ca_cert_pem: Option<String>,
+
+    /// Only trust the certificates of `tls_ca_cert`, not the system ones, so the
+    /// server has to present a certificate issued by them
+    #[clap(long, parse(try_from_str), default_value = "false")]
+    pub tls_ca_only: bool,
+
+    /// SHA-256 fingerprints of the server certificates accepted, in hex with or without
+    /// colons. Connections to a server presenting another certificate are refused
+    #[clap(long)]
+    #[serde(default)]
+    pub tls_pinned_sha256: Vec<String>,
+
+    /// PEM file of the client certificate, for servers requiring mutual TLS
+    #[clap(long)]
+    pub tls_client_cert: Option<PathBuf>,
+
+    /// PEM client certificate, instead of the `tls_client_cert` file
+    #[clap(
+        long,
+        env = "FLUVIO_HTTP_TLS_CLIENT_CERT",
+        hide_env_values = true,
+        conflicts_with = "tls-client-cert"
+    )]
+    pub tls_client_cert_pem: Option<String>,
+
+    /// PEM file of the PKCS #8 private key of the client certificate
+    #[clap(long)]
+    pub tls_client_key: Option<PathBuf>,
+
+    /// PEM PKCS #8 private key of the client certificate, instead of the
+    /// `tls_client_key` file
+    #[clap(
+        long,
+        env = "FLUVIO_HTTP_TLS_CLIENT_KEY",
+        hide_env_values = true,
+        conflicts_with = "tls-client-key"
+    )]
+    pub tls_client_key_pem: Option<String>,
+
+    /// Accept any server certificate, even self-signed, expired or for another host.
+    /// Only meant for development
+    #[clap(long, parse(try_from_str), default_value = "false")]
+    pub tls_insecure_skip_verify: bool,
+
+    /// Proxy the requests are sent through: `http://`, `https://`, `socks5://` or
+    /// `socks5h://` URL, with credentials if needed. Defaults to the `HTTP_PROXY`,
+    /// `HTTPS_PROXY` and `ALL_PROXY` environment variables
+    #[clap(long, env = "FLUVIO_HTTP_PROXY", hide_env_values = true)]
+    pub proxy: Option<String>,
+}
+
+impl HttpClientOpt {
+    /// Client the requests of the connector are sent with
+    pub fn build(&self) -> Result<reqwest::Client> {
+        if self.tls_ca_only && self.tls_ca_cert.is_none() && self.tls_ca_cert_pem.is_none() {
+            return Err(Error::Tls("tls_ca_only requires tls_ca_cert".to_string()));
+        }
+        if self.tls_insecure_skip_verify {
+            tracing::warn!("tls_insecure_skip_verify is set, server certificates are not verified");
+        }
+
+        let pins = self.pins()?;
+        let mut builder = if pins.is_empty() {
+            self.native_tls(reqwest::Client::builder())?
+        } else {
+            // pinned certificates are checked during the handshake, before anything is sent
+            reqwest::Client::builder().use_preconfigured_tls(self.pinned_tls(pins)?)
+        };
+
+        if let Some(ref proxy) = self.proxy {
+            let proxy = Proxy::all(proxy).map_err(|e| Error::Proxy(e.to_string()))?;
+            builder = builder.proxy(proxy);
+        }
+
+        builder
+            .build()
+            .map_err(|e| Error::Tls(format!("failed to create client: {e}")))
+    }
+
+    /// Configures `builder` to verify the server certificates with the system TLS library
+    fn native_tls(&self, mut builder: ClientBuilder) -> Result<ClientBuilder> {
+        if let Some(ca_cert) = read_pem(&self.tls_ca_cert, &self.tls_ca_cert_pem)? {
+            let certs = Certificate::from_pem_bundle(&ca_cert)
+                .map_err(|e| Error::Tls(format!("invalid CA certificate: {e}")))?;
+            if certs.is_empty() {
+                return Err(Error::Tls("no certificate in tls_ca_cert".to_string()));
+            }
+            for cert in certs {
+                builder = builder.add_root_certificate(cert);
+            }
+            builder = builder.tls_built_in_root_certs(!self.tls_ca_only);
+        }
+
+        if let Some((cert, key)) = self.client_identity()? {
+            let identity = Identity::from_pkcs8_pem(&cert, &key)
+                .map_err(|e| Error::Tls(format!("invalid client certificate: {e}")))?;
+            builder = builder.identity(identity);
+        }
+
+        Ok(builder.danger_accept_invalid_certs(self.tls_insecure_skip_verify))
+    }
+
+    /// TLS configuration only accepting servers presenting one of the `pins` certificates
+    fn pinned_tls(&self, pins: Vec<String>) -> Result<rustls::ClientConfig> {
+        let mut roots = RootCertStore::empty();
+        if !self.tls_ca_only {
+            let system_certs = rustls_native_certs::load_native_certs()
+                .map_err(|e| Error::Tls(format!("failed to load system certificates: {e}")))?;
+            roots.add_parsable_certificates(
+                &system_certs
+                    .into_iter()
+                    .map(|cert| cert.0)
+                    .collect::<Vec<_>>(),
+            );
+        }
+        if let Some(ca_cert) = read_pem(&self.tls_ca_cert, &self.tls_ca_cert_pem)? {
+            let certs = rustls_pemfile::certs(&mut ca_cert.as_slice())
+                .map_err(|e| Error::Tls(format!("invalid CA certificate: {e}")))?;
+            if certs.is_empty() {
+                return Err(Error::Tls("no certificate in tls_ca_cert".to_string()));
+            }
+            for cert in certs {
+                roots
+                    .add(&rustls::Certificate(cert))
+                    .map_err(|e| Error::Tls(format!("invalid CA certificate: {e}")))?;
+            }
+        }
+
+        let verifier = PinnedCertVerifier {
+            pins,
+            verifier: (!self.tls_insecure_skip_verify).then(|| WebPkiVerifier::new(roots, None)),
+        };
+        let config = rustls::ClientConfig::builder()
+            .with_safe_defaults()
+            .with_custom_certificate_verifier(Arc::new(verifier));
+
+        match self.client_identity()? {
+            Some((cert, key)) => {
+                let certs = rustls_pemfile::certs(&mut cert.as_slice())
+                    .map_err(|e| Error::Tls(format!("invalid client certificate: {e}")))?;
+                let key = rustls_pemfile::pkcs8_private_keys(&mut key.as_slice())
+                    .map_err(|e| Error::Tls(format!("invalid client key: {e}")))?
+                    .into_iter()
+                    .next()
+                    .ok_or_else(|| Error::Tls("no PKCS #8 key in tls_client_key".to_string()))?;
+                config
+                    .with_client_auth_cert(
+                        certs.into_iter().map(rustls::Certificate).collect(),
+                        PrivateKey(key),
+                    )
+                    .map_err(|e| Error::Tls(format!("invalid client certificate: {e}")))
+            }
+            None => Ok(config.with_no_client_auth()),
+        }
+    }
+
+    /// PEM certificate and key of `tls_client_cert` and `tls_client_key`, if set
+    fn client_identity(&self) -> Result<Option<(Vec<u8>, Vec<u8>)>> {
+        let client_cert = read_pem(&self.tls_client_cert, &self.tls_client_cert_pem)?;
+        let client_key = read_pem(&self.tls_client_key, &self.tls_client_key_pem)?;
+        match (client_cert, client_key) {
+            (Some(cert), Some(key)) => Ok(Some((cert, key))),
+            (None, None) => Ok(None),
+            _ => Err(Error::Tls(
+                "tls_client_cert and tls_client_key go together".to_string(),
+            )),
+        }
+    }
+
+    /// Fingerprints of `tls_pinned_sha256`, in lowercase hex without colons
+    fn pins(&self) -> Result<Vec<String>> {
+        self.tls_pinned_sha256
+            .iter()
+            .map(|pin| {
+                let fingerprint = pin.replace(':', "").to_ascii_lowercase();
+                if fingerprint.len() == 64 && fingerprint.bytes().all(|b| b.is_ascii_hexdigit()) {
+                    Ok(fingerprint)
+                } else {
+                    Err(Error::Tls(format!("invalid SHA-256 fingerprint {pin}")))
+                }
+            })
+            .collect()
+    }
+}
+
+/// Accepts the server certificates whose SHA-256 fingerprint is pinned, once verified by
+/// `verifier` unless certificates are not verified
+struct PinnedCertVerifier {
+    pins: Vec<String>,
+    verifier: Option<WebPkiVerifier>,
+}
+
+impl ServerCertVerifier for PinnedCertVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &rustls::Certificate,
+        intermediates: &[rustls::Certificate],
+        server_name: &ServerName,
+        scts: &mut dyn Iterator<Item = &[u8]>,
+        ocsp_response: &[u8],
+        now: SystemTime,
+    ) -> std::result::Result<ServerCertVerified, rustls::Error> {
+        if let Some(ref verifier) = self.verifier {
+            verifier.verify_server_cert(
+                end_entity,
+                intermediates,
+                server_name,
+                scts,
+                ocsp_response,
+                now,
+            )?;
+        }
+        let fingerprint = hex::encode(Sha256::digest(&end_entity.0));
+        if self.pins.contains(&fingerprint) {
+            Ok(ServerCertVerified::assertion())
+        } else {
+            Err(rustls::Error::General(format!(
+                "server certificate with SHA-256 {fingerprint} is not pinned"
+            )))
+        }
+    }
+}
+
+/// Contents of the PEM `file`, or the `pem` itself
+fn read_pem(file: &Option<PathBuf>, pem: &Option<String>) -> Result<Option<Vec<u8>>> {
+    match (file, pem) {
+        (Some(file), _) => std::fs::read(file)
+            .map(Some)
+            .map_err(|e| Error::Tls(format!("failed to read {}: {e}", file.display()))),
+        (None, Some(pem)) => Ok(Some(pem.as_bytes().to_vec())),
+        (None, None) => Ok(None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TLS_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/test-data/tls");
+
+    fn opts(args: &[&str]) -> HttpClientOpt {
+        HttpClientOpt::parse_from(std::iter::once("http-source").chain(args.iter().copied()))
+    }
+
+    #[test]
+    fn test_mutual_tls_client() {
+        //given
+        let ca_cert = format!("--tls-ca-cert={TLS_DIR}/ca.pem");
+        let client_cert = format!("--tls-client-cert={TLS_DIR}/client.pem");
+        let client_key = std::fs::read_to_string(format!("{TLS_DIR}/client-key.pem")).unwrap();
+        let client_key = format!("--tls-client-key-pem={client_key}");
+
+        //then
+        assert!(
+            opts(&[&ca_cert, "--tls-ca-only=true", &client_cert, &client_key])
+                .build()
+                .is_ok()
+        );
+        assert!(opts(&[
+            "--tls-insecure-skip-verify=true",
+            "--proxy=http://proxy:3128"
+        ])
+        .build()
+        .is_ok());
+    }
+
+    #[test]
+    fn test_invalid_client() {
+        //given
+        let client_cert = format!("--tls-client-cert={TLS_DIR}/client.pem");
+        let not_a_cert = format!("--tls-ca-cert={TLS_DIR}/client-key.pem");
+
+        //then
+        assert!(matches!(opts(&[&client_cert]).build(), Err(Error::Tls(_))));
+        assert!(matches!(
+            opts(&["--tls-ca-only=true"]).build(),
+            Err(Error::Tls(_))
+        ));
+        assert!(matches!(opts(&[&not_a_cert]).build(), Err(Error::Tls(_))));
+        assert!(matches!(
+            opts(&["--tls-ca-cert=/missing/ca.pem"]).build(),
+            Err(Error::Tls(_))
+        ));
+        assert!(matches!(
+            opts(&["--proxy=ftp://proxy"]).build(),
+            Err(Error::Proxy(_))
+        ));
+    }
+
+    #[test]
+    fn test_pinned_certificates() {
+        //given
+        let pem = std::fs::read(format!("{TLS_DIR}/client.pem")).unwrap();
+        let certificate = rustls::Certificate(
+            rustls_pemfile::certs(&mut pem.as_slice())
+                .unwrap()
+                .remove(0),
+        );
+        let fingerprint = hex::encode(Sha256::digest(&certificate.0));
+        let pin = format!("--tls-pinned-sha256={fingerprint}");
+        let with_colons = fingerprint
+            .as_bytes()
+            .chunks(2)
+            .map(|byte| std::str::from_utf8(byte).unwrap().to_uppercase())
+            .collect::<Vec<_>>()
+            .join(":");
+        let verify = |pins: Vec<String>, verifier: Option<WebPkiVerifier>| {
+            PinnedCertVerifier { pins, verifier }.verify_server_cert(
+                &certificate,
+                &[],
+                &ServerName::try_from("localhost").unwrap(),
+                &mut std::iter::empty(),
+                &[],
+                SystemTime::now(),
+            )
+        };
+
+        //then
+        assert!(opts(&[&pin]).build().is_ok());
+        assert!(opts(&[
+            &pin,
+            &format!("--tls-ca-cert={TLS_DIR}/ca.pem"),
+            "--tls-ca-only=true",
+            &format!("--tls-client-cert={TLS_DIR}/client.pem"),
+            &format!("--tls-client-key={TLS_DIR}/client-key.pem"),
+        ])
+        .build()
+        .is_ok());
+        assert_eq!(
+            opts(&[&format!("--tls-pinned-sha256={with_colons}")])
+                .pins()
+                .unwrap(),
+            std::slice::from_ref(&fingerprint)
+        );
+        assert!(matches!(
+            opts(&["--tls-pinned-sha256=abc"]).build(),
+            Err(Error::Tls(_))
+        ));
+        assert!(verify(vec![fingerprint.clone()], None).is_ok());
+        assert!(verify(vec!["0".repeat(64)], None).is_err());
+        assert!(verify(
+            vec![fingerprint],
+            Some(WebPkiVerifier::new(RootCertStore::empty(), None))
+        )
+        .is_err());
+    }
+}
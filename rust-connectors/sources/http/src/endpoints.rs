@@ -0,0 +1,204 @@
+//! Several endpoints polled concurrently by one connector, each with its own request,
+//! interval and topic
+
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::str::FromStr;
+use std::time::Duration;
+
+use fluvio_connectors_common::de;
+use schemars::JsonSchema;
+use serde::Deserialize;
+
+use crate::error::{Error, Result};
+use crate::HttpOpt;
+
+/// One of the `endpoints`, given as an object or its JSON text. Unset fields default to
+/// the options of the connector.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct EndpointConfig {
+    /// Name of the endpoint in logs and metrics, defaults to the endpoint itself
+    #[serde(default)]
+    pub name: Option<String>,
+    /// Endpoint polled, may contain `{{ variable }}` placeholders
+    pub endpoint: String,
+    #[serde(default)]
+    pub method: Option<String>,
+    /// Headers sent along with the ones of the connector
+    #[serde(default, deserialize_with = "de::key_values")]
+    pub headers: Vec<String>,
+    #[serde(default)]
+    pub body: Option<String>,
+    /// Ex: '150ms', '20s'
+    #[serde(default, with = "humantime_serde")]
+    #[schemars(with = "Option<String>")]
+    pub interval: Option<Duration>,
+    /// Topic the responses are produced to
+    #[serde(default)]
+    pub topic: Option<String>,
+}
+
+impl FromStr for EndpointConfig {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        serde_json::from_str(s).map_err(|e| format!("invalid endpoint: {e}"))
+    }
+}
+
+impl EndpointConfig {
+    pub fn name(&self) -> &str {
+        self.name.as_deref().unwrap_or(&self.endpoint)
+    }
+
+    /// Options of the source polling this endpoint: the ones of the connector,
+    /// overridden by the ones of the endpoint
+    pub fn opts(&self, opts: &HttpOpt) -> HttpOpt {
+        let mut opts = opts.clone();
+        opts.endpoints.clear();
+        opts.endpoint = Some(self.endpoint.clone());
+        if let Some(ref method) = self.method {
+            opts.method = method.clone();
+        }
+        opts.headers.extend(self.headers.iter().cloned());
+        if let Some(ref body) = self.body {
+            opts.body = Some(body.clone());
+        }
+        if let Some(interval) = self.interval {
+            opts.interval = interval;
+        }
+        if let Some(ref topic) = self.topic {
+            opts.common.fluvio_topic = topic.clone();
+        }
+        // the validators and template state of each endpoint are kept in their own file
+        if let Some(ref path) = opts.change_detection_path {
+            opts.change_detection_path = Some(endpoint_path(path, self.name()));
+        }
+        if let Some(ref path) = opts.template_state_path {
+            opts.template_state_path = Some(endpoint_path(path, self.name()));
+        }
+        opts
+    }
+
+    /// Checks the endpoints can be told apart by their name, and by the files they keep
+    /// their state in
+    pub fn check_names(endpoints: &[EndpointConfig]) -> Result<()> {
+        let mut names = BTreeMap::new();
+        for endpoint in endpoints {
+            if let Some(other) = names.insert(file_name(endpoint.name()), endpoint.name()) {
+                return Err(Error::Endpoints(if other == endpoint.name() {
+                    format!("duplicate endpoint name {other}")
+                } else {
+                    format!(
+                        "endpoint names {other} and {} only differ by punctuation",
+                        endpoint.name()
+                    )
+                }));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Endpoint `name` as it appears in file names
+fn file_name(name: &str) -> String {
+    name.chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect()
+}
+
+/// `path` with the endpoint name appended to the file stem, e.g. `changes-github.json`
+fn endpoint_path(path: &Path, name: &str) -> std::path::PathBuf {
+    let name = file_name(name);
+    let stem = path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy())
+        .unwrap_or_default();
+    match path.extension() {
+        Some(extension) => {
+            path.with_file_name(format!("{stem}-{name}.{}", extension.to_string_lossy()))
+        }
+        None => path.with_file_name(format!("{stem}-{name}")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::Parser;
+
+    use super::*;
+
+    #[test]
+    fn test_endpoint_opts() {
+        //given
+        let opts = HttpOpt::parse_from([
+            "http-source",
+            "--header=User-Agent:fluvio",
+            "--interval=10s",
+            "--change-detection-path=/var/lib/fluvio/changes.json",
+            "--template-state-path=/var/lib/fluvio/state",
+            "--fluvio-topic=events",
+            r#"--endpoints={"name": "github", "endpoint": "https://api.github.com/events", "headers": ["Accept:application/json"], "interval": "1m", "topic": "github"}"#,
+            r#"--endpoints={"endpoint": "https://status.example.com", "method": "POST", "body": "{}"}"#,
+        ]);
+
+        //when
+        let github = opts.endpoints[0].opts(&opts);
+        let status = opts.endpoints[1].opts(&opts);
+
+        //then
+        assert_eq!(
+            github.endpoint.as_deref(),
+            Some("https://api.github.com/events")
+        );
+        assert_eq!(
+            github.headers,
+            ["User-Agent:fluvio", "Accept:application/json"]
+        );
+        assert_eq!(github.interval, Duration::from_secs(60));
+        assert_eq!(github.common.fluvio_topic, "github");
+        assert_eq!(github.method, "GET");
+        assert_eq!(
+            github.change_detection_path,
+            Some("/var/lib/fluvio/changes-github.json".into())
+        );
+        assert_eq!(
+            github.template_state_path,
+            Some("/var/lib/fluvio/state-github".into())
+        );
+        assert!(github.endpoints.is_empty());
+        assert_eq!(opts.endpoints[1].name(), "https://status.example.com");
+        assert_eq!(status.method, "POST");
+        assert_eq!(status.body.as_deref(), Some("{}"));
+        assert_eq!(status.interval, Duration::from_secs(10));
+        assert_eq!(status.common.fluvio_topic, "events");
+        assert!(EndpointConfig::check_names(&opts.endpoints).is_ok());
+    }
+
+    #[test]
+    fn test_invalid_endpoints() {
+        //given
+        let endpoint = r#"{"name": "a", "endpoint": "https://a.example.com"}"#;
+        let endpoints: Vec<EndpointConfig> = vec![endpoint.parse().unwrap(); 2];
+        let urls: Vec<EndpointConfig> = ["https://example.com/a.b", "https://example.com/a/b"]
+            .iter()
+            .map(|url| format!(r#"{{"endpoint": "{url}"}}"#).parse().unwrap())
+            .collect();
+
+        //then
+        assert!(matches!(
+            EndpointConfig::check_names(&endpoints),
+            Err(Error::Endpoints(_))
+        ));
+        assert!(matches!(
+            EndpointConfig::check_names(&urls),
+            Err(Error::Endpoints(_))
+        ));
+        assert!(EndpointConfig::check_names(&endpoints[..1]).is_ok());
+        assert!(r#"{"endpoint": "https://a.example.com", "timeout": "1s"}"#
+            .parse::<EndpointConfig>()
+            .is_err());
+        assert!(r#"{"name": "a"}"#.parse::<EndpointConfig>().is_err());
+    }
+}
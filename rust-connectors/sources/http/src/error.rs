@@ -8,4 +8,51 @@ pub enum Error {
     Request(#[source] reqwest::Error),
     #[error("HTTP Response body error")]
     ResponseBody(#[source] reqwest::Error),
+    #[error("Invalid HTTP method {0}")]
+    Method(String),
+    #[error("Invalid HTTP endpoint {0}")]
+    Endpoint(String),
+    #[error("Invalid request template: {0}")]
+    Template(String),
+    #[error("Invalid schedule: {0}")]
+    Schedule(String),
+    #[error("Invalid endpoints: {0}")]
+    Endpoints(String),
+    #[error("Webhook error: {0}")]
+    Webhook(String),
+    #[error("Invalid pagination: {0}")]
+    Pagination(String),
+    #[error("Change detection file error")]
+    ChangeDetection(#[source] std::io::Error),
+    #[error("Template state file error")]
+    TemplateState(#[source] std::io::Error),
+    #[error("Invalid TLS configuration: {0}")]
+    Tls(String),
+    #[error("Invalid proxy: {0}")]
+    Proxy(String),
+    #[error("Invalid auth: {0}")]
+    Auth(String),
+    #[error("Access token request error")]
+    TokenRequest(#[source] reqwest::Error),
+    #[error("Invalid client error handling: {0}")]
+    ClientError(String),
+    #[error("HTTP status {0}")]
+    Status(reqwest::StatusCode),
+    #[error("Dead-letter record error: {0}")]
+    DeadLetter(String),
+    #[error("Fluvio error: {0:#}")]
+    Fluvio(anyhow::Error),
+}
+
+impl Error {
+    /// Errors which sending the request again doesn't recover from
+    pub fn is_fatal(&self) -> bool {
+        matches!(
+            self,
+            Self::Fluvio(_)
+                | Self::ChangeDetection(_)
+                | Self::TemplateState(_)
+                | Self::DeadLetter(_)
+        )
+    }
 }
@@ -4,33 +4,146 @@
 // ---------------------------
 // HttpResponseRecord Implements
 // - TryFrom<reqwest::Response>
+// - TryFrom<hyper::http::request::Parts>
+mod from_hyper;
 mod from_reqwest;
 
+use std::fmt;
+use std::str::FromStr;
+use std::time::{Duration, SystemTime};
+
+use base64::Engine;
+use reqwest::header::{HeaderMap, CONTENT_TYPE};
+use schemars::JsonSchema;
+
 // Output Implementations
 // ---------------------------
 // HttpJsonRecord Implements
 // - TryFrom<HttpResponseRecord>
 // - ToString
 mod to_json;
+use to_json::HttpJsonBody;
 // Techdebt: Move text to mod to_text;
 
 #[derive(thiserror::Error, Debug)]
 pub enum HttpRecordError {
     #[error("Options output_parts`{0}` and/or type`{1}` Setting Error")]
     OutputOptions(String, String),
+    #[error("Response body can't be split at `{0}`: {1}")]
+    Split(String, String),
+}
+
+/// Record key and value of a split array element
+pub type SplitRecord = (Option<String>, String);
+
+/// How bodies are put in the records
+#[derive(Debug, Clone, Copy, PartialEq, Eq, JsonSchema)]
+pub enum BodyEncoding {
+    /// `text` for textual content types, e.g. `text/*` or JSON, `raw` otherwise
+    Auto,
+    /// Decoded as text, invalid characters being replaced
+    Text,
+    /// The bytes as they are in text records, base64 encoded in JSON records
+    Raw,
+    /// Base64 encoded
+    Base64,
+}
+
+impl FromStr for BodyEncoding {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "auto" => Ok(Self::Auto),
+            "text" => Ok(Self::Text),
+            "raw" => Ok(Self::Raw),
+            "base64" => Ok(Self::Base64),
+            _ => Err(format!(
+                "unknown body encoding {s}, expected auto, text, raw or base64"
+            )),
+        }
+    }
+}
+
+impl fmt::Display for BodyEncoding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let encoding = match self {
+            Self::Auto => "auto",
+            Self::Text => "text",
+            Self::Raw => "raw",
+            Self::Base64 => "base64",
+        };
+        f.write_str(encoding)
+    }
+}
+
+impl BodyEncoding {
+    /// Encoding of a body with the `Content-Type` of `headers`. Bodies without content
+    /// type are taken as text.
+    pub fn resolve(&self, headers: &HeaderMap) -> Self {
+        if *self != Self::Auto {
+            return *self;
+        }
+        let content_type = headers
+            .get(CONTENT_TYPE)
+            .and_then(|content_type| content_type.to_str().ok());
+        match content_type {
+            Some(content_type) if !is_text_content_type(content_type) => Self::Raw,
+            _ => Self::Text,
+        }
+    }
+}
+
+fn is_text_content_type(content_type: &str) -> bool {
+    let mime = content_type
+        .split(';')
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .to_ascii_lowercase();
+    let (kind, subtype) = mime.split_once('/').unwrap_or((&mime, ""));
+    kind == "text"
+        || subtype.ends_with("json")
+        || subtype.ends_with("xml")
+        || subtype.ends_with("yaml")
+        || matches!(
+            subtype,
+            "javascript" | "x-www-form-urlencoded" | "graphql" | "csv"
+        )
 }
 
 #[derive(Debug, Default, PartialEq, Eq)]
 pub struct HttpResponseRecord {
+    /// Request line of received webhooks, in place of the status line
+    pub request: Option<HttpRequestLine>,
     pub version: Option<String>,
     pub status_code: Option<u16>,
     pub status_string: Option<String>,
     pub headers: Option<Vec<HttpHeader>>,
     pub body: Option<String>,
+    /// Request polled responses were fetched with
+    pub fetch: Option<HttpFetch>,
     pub output_type: Option<HttpOutputType>,
     pub output_parts: Option<HttpOutputParts>,
 }
 
+#[derive(Debug, PartialEq, Eq)]
+pub struct HttpRequestLine {
+    pub method: String,
+    pub path: String,
+    pub version: String,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct HttpFetch {
+    pub method: String,
+    pub url: String,
+    /// Time the request was sent
+    pub fetched_at: SystemTime,
+    /// Time taken to get the whole response, including retries
+    pub elapsed: Duration,
+}
+
 #[derive(Debug, PartialEq, Eq)]
 pub struct HttpHeader {
     pub name: String,
@@ -91,26 +204,100 @@ impl HttpResponseRecord {
             _ => panic!("ERROR: record called without output_parts set via output() ?"),
         }
     }
+    /// Records of each element of the JSON array at `pointer` in `body`, keyed by the
+    /// value at `key_pointer` in the element if any
+    pub fn split_records(
+        &mut self,
+        body: &str,
+        pointer: &str,
+        key_pointer: Option<&str>,
+    ) -> Result<Vec<SplitRecord>, HttpRecordError> {
+        let split_error =
+            |reason: &str| HttpRecordError::Split(pointer.to_owned(), reason.to_owned());
+
+        let mut body: serde_json::Value =
+            serde_json::from_str(body).map_err(|e| split_error(&e.to_string()))?;
+        let items = match body.pointer_mut(pointer) {
+            Some(serde_json::Value::Array(items)) => std::mem::take(items),
+            Some(_) => return Err(split_error("not an array")),
+            None => return Err(split_error("not found")),
+        };
+
+        let records = items
+            .into_iter()
+            .map(|item| {
+                let key = key_pointer
+                    .and_then(|key_pointer| item.pointer(key_pointer))
+                    .and_then(|key| match key {
+                        serde_json::Value::Null => None,
+                        serde_json::Value::String(key) => Some(key.to_owned()),
+                        key => Some(key.to_string()),
+                    });
+                (key, self.record_item(item))
+            })
+            .collect();
+        Ok(records)
+    }
+    /// Record of a body as bytes, encoded with `encoding` as resolved for its content
+    /// type. Bodies are base64 encoded in JSON records unless decoded as text.
+    pub fn record_bytes(&mut self, body: &[u8], encoding: BodyEncoding) -> Vec<u8> {
+        let base64 = || base64::engine::general_purpose::STANDARD.encode(body);
+        match (encoding, &self.output_type) {
+            (BodyEncoding::Auto | BodyEncoding::Text, _) => {
+                self.record(Some(&String::from_utf8_lossy(body)))
+            }
+            (_, Some(HttpOutputType::HttpRecordJSON)) => {
+                let json_record = self.json_record(Some(HttpJsonBody::Text(base64())));
+                to_json::HttpJsonRecord {
+                    body_encoding: Some("base64"),
+                    ..json_record
+                }
+                .to_string()
+            }
+            (BodyEncoding::Base64, _) => self.record_text(Some(&base64())),
+            (BodyEncoding::Raw, _) => {
+                // status and header lines followed by the empty line, if any
+                let mut record = self.record_text(Some("")).into_bytes();
+                record.extend_from_slice(body);
+                return record;
+            }
+        }
+        .into_bytes()
+    }
+    /// Record<String> of a JSON value out of the body, kept as JSON in JSON Records
+    pub fn record_item(&mut self, item: serde_json::Value) -> String {
+        match self.output_type {
+            Some(HttpOutputType::HttpRecordJSON) => {
+                self.json_record(Some(HttpJsonBody::Json(item))).to_string()
+            }
+            Some(HttpOutputType::HttpRecordText) => self.record_text(Some(&item.to_string())),
+            _ => panic!("ERROR: record_item called without output_parts set via output() ?"),
+        }
+    }
     /// JSON Record<String> Fan Out
     pub fn record_json(&mut self, body: Option<&str>) -> String {
         self.body = body.map(|b| b.to_owned());
 
+        let json_body = match self.output_parts {
+            Some(HttpOutputParts::HttpRecordBody) => Some(body.unwrap_or("").to_owned()),
+            _ => self.body.to_owned(),
+        };
+        self.json_record(json_body.map(HttpJsonBody::Text))
+            .to_string()
+    }
+    /// JSON Record of the status and headers set by output_parts, along with `body`
+    fn json_record(&mut self, body: Option<HttpJsonBody>) -> to_json::HttpJsonRecord {
         let json_rec = match self.output_parts {
             Some(HttpOutputParts::HttpRecordFull) => {
                 to_json::HttpJsonRecord::try_from(self).unwrap()
             }
             Some(HttpOutputParts::HttpRecordBody) => {
-                let mut _rec = HttpResponseRecord {
-                    body: Some(body.unwrap_or("").to_owned()),
-                    ..Default::default()
-                };
-
-                to_json::HttpJsonRecord::try_from(&mut _rec).unwrap()
+                to_json::HttpJsonRecord::try_from(&mut HttpResponseRecord::default()).unwrap()
             }
             _ => panic!("BUG record_json() Unknown JSON Record type or not set?"),
         };
 
-        json_rec.to_string()
+        to_json::HttpJsonRecord { body, ..json_rec }
     }
     /// Text Record<String> Fan Out
     pub fn record_text(&self, body: Option<&str>) -> String {
@@ -120,8 +307,18 @@ impl HttpResponseRecord {
             None => panic!("record_text called with no HttpOutputParts?"),
         };
 
+        // Request Line METHOD /path HTTP/X of webhooks
+        if let (Some(HttpOutputParts::HttpRecordFull), Some(request)) =
+            (&self.output_parts, &self.request)
+        {
+            record_out_parts.push(format!(
+                "{} {} {}",
+                request.method, request.path, request.version
+            ));
+        }
+
         // Status Line HTTP/X XXX CANONICAL
-        if self.output_parts == Some(HttpOutputParts::HttpRecordFull) {
+        if self.output_parts == Some(HttpOutputParts::HttpRecordFull) && self.request.is_none() {
             let status_line: Vec<String> = vec![
                 self.version.to_owned().unwrap_or_default(),
                 self.status_code.unwrap_or(0).to_string(),
@@ -228,11 +425,13 @@ mod tests {
         );
 
         let response_record = HttpResponseRecord {
+            request: None,
             version,
             status_code: status,
             status_string,
             headers: Some(data_headers),
             body: None,
+            fetch: None,
             output_parts: Some(HttpOutputParts::HttpRecordFull),
             output_type: Some(HttpOutputType::HttpRecordText),
         };
@@ -241,4 +440,138 @@ mod tests {
 
         assert_eq!(got_record, expected_record);
     }
+
+    #[test]
+    fn test_webhook_full_records() {
+        //given
+        let (_, headers, expected_headers) = data_test_header("basic", "basic", 1);
+        let mut webhook_record = HttpResponseRecord {
+            request: Some(HttpRequestLine {
+                method: "POST".to_string(),
+                path: "/github".to_string(),
+                version: "HTTP/1.1".to_string(),
+            }),
+            headers: Some(headers),
+            ..Default::default()
+        };
+
+        //when
+        webhook_record.configure_output("text", "full").unwrap();
+        let text = webhook_record.record(Some("{}"));
+        webhook_record.configure_output("json", "full").unwrap();
+        let json = webhook_record.record(Some("{}"));
+
+        //then
+        assert_eq!(
+            text,
+            format!("POST /github HTTP/1.1\n{expected_headers}\n\n{{}}")
+        );
+        assert_eq!(
+            json,
+            r#"{"request":{"method":"POST","path":"/github","version":"HTTP/1.1"},"header":{"x-basic-key-0":"x-basic-val-0"},"body":"{}"}"#
+        );
+    }
+
+    #[test]
+    fn test_binary_records() {
+        //given
+        let body = [0x89, b'P', b'N', b'G', 0xff];
+        let mut headers = reqwest::header::HeaderMap::new();
+        headers.insert(CONTENT_TYPE, HeaderValue::from_static("image/png"));
+        let mut record = HttpResponseRecord {
+            version: Some("HTTP/1.1".to_string()),
+            status_code: Some(200),
+            status_string: Some("OK".to_string()),
+            ..Default::default()
+        };
+
+        //when
+        let encoding = BodyEncoding::Auto.resolve(&headers);
+        record.configure_output("text", "body").unwrap();
+        let raw = record.record_bytes(&body, encoding);
+        let base64 = record.record_bytes(&body, BodyEncoding::Base64);
+        record.configure_output("text", "full").unwrap();
+        let full_raw = record.record_bytes(&body, encoding);
+        record.configure_output("json", "body").unwrap();
+        let json = record.record_bytes(&body, encoding);
+
+        //then
+        assert_eq!(encoding, BodyEncoding::Raw);
+        assert_eq!(
+            BodyEncoding::Auto.resolve(&reqwest::header::HeaderMap::new()),
+            BodyEncoding::Text
+        );
+        assert_eq!(raw, body);
+        assert_eq!(base64, b"iVBOR/8=");
+        assert_eq!(full_raw, [b"HTTP/1.1 200 OK\n\n".as_slice(), &body].concat());
+        assert_eq!(json, br#"{"body":"iVBOR/8=","body_encoding":"base64"}"#);
+    }
+
+    #[test]
+    fn test_poll_request_metadata() {
+        //given
+        let mut record = HttpResponseRecord {
+            version: Some("HTTP/1.1".to_string()),
+            status_code: Some(200),
+            status_string: Some("OK".to_string()),
+            fetch: Some(HttpFetch {
+                method: "GET".to_string(),
+                url: "https://api.example.com/items?page=2".to_string(),
+                fetched_at: SystemTime::UNIX_EPOCH + Duration::from_millis(1_666_000_000_123),
+                elapsed: Duration::from_micros(42_500),
+            }),
+            ..Default::default()
+        };
+
+        //when
+        record.configure_output("json", "full").unwrap();
+        let full = record.record(Some("{}"));
+        record.configure_output("json", "body").unwrap();
+        let body = record.record(Some("{}"));
+
+        //then
+        assert_eq!(
+            full,
+            r#"{"request":{"method":"GET","url":"https://api.example.com/items?page=2","fetched_at":"2022-10-17T09:46:40.123Z","elapsed_ms":42},"status":{"version":"HTTP/1.1","code":200,"string":"OK"},"body":"{}"}"#
+        );
+        assert_eq!(body, r#"{"body":"{}"}"#);
+    }
+
+    #[test]
+    fn test_split_records() {
+        //given
+        let body = r#"{"items":[{"id":1,"name":"a"},{"id":"b"},{"name":"c"}],"next":null}"#;
+        let mut json_record = HttpResponseRecord::default();
+        json_record.configure_output("json", "body").unwrap();
+        let mut text_record = HttpResponseRecord::default();
+        text_record.configure_output("text", "body").unwrap();
+
+        //when
+        let json_records = json_record
+            .split_records(body, "/items", Some("/id"))
+            .unwrap();
+        let text_records = text_record.split_records(body, "/items", None).unwrap();
+
+        //then
+        assert_eq!(
+            json_records,
+            vec![
+                (
+                    Some("1".to_string()),
+                    r#"{"body":{"id":1,"name":"a"}}"#.to_string()
+                ),
+                (Some("b".to_string()), r#"{"body":{"id":"b"}}"#.to_string()),
+                (None, r#"{"body":{"name":"c"}}"#.to_string()),
+            ]
+        );
+        assert_eq!(text_records[2], (None, r#"{"name":"c"}"#.to_string()));
+        assert!(matches!(
+            text_record.split_records(body, "/next", None),
+            Err(HttpRecordError::Split(..))
+        ));
+        assert!(matches!(
+            text_record.split_records("not json", "", None),
+            Err(HttpRecordError::Split(..))
+        ));
+    }
 }
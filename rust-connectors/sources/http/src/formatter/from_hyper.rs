@@ -0,0 +1,22 @@
+//! hyper input helper, for received webhooks
+
+use crate::formatter::from_reqwest::headers;
+use crate::formatter::HttpRecordError;
+use crate::formatter::HttpRequestLine;
+use crate::formatter::HttpResponseRecord;
+
+impl TryFrom<&hyper::http::request::Parts> for HttpResponseRecord {
+    type Error = HttpRecordError;
+
+    fn try_from(parts: &hyper::http::request::Parts) -> Result<Self, Self::Error> {
+        Ok(Self {
+            request: Some(HttpRequestLine {
+                method: parts.method.to_string(),
+                path: parts.uri.to_string(),
+                version: format!("{:?}", parts.version),
+            }),
+            headers: Some(headers(&parts.headers)),
+            ..Default::default()
+        })
+    }
+}
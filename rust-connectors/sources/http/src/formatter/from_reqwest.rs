@@ -11,11 +11,13 @@ impl TryFrom<&reqwest::Response> for HttpResponseRecord {
         let (status_code, status_string) = status(&response.status());
 
         Ok(Self {
+            request: None,
             version: Some(version(&response.version())),
             status_code,
             status_string,
             headers: Some(headers(response.headers())),
             body: None,
+            fetch: None,
             output_type: None,
             output_parts: None,
         })
@@ -37,7 +39,7 @@ fn status(status: &reqwest::StatusCode) -> (Option<u16>, Option<String>) {
 }
 
 // Reqwest Response TryFrom helper impl.
-fn headers(hdr_map: &reqwest::header::HeaderMap) -> Vec<HttpHeader> {
+pub(super) fn headers(hdr_map: &reqwest::header::HeaderMap) -> Vec<HttpHeader> {
     let mut hdr_vec = Vec::with_capacity(hdr_map.len());
 
     for (hdr_key, hdr_val) in hdr_map.iter() {
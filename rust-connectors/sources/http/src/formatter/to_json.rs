@@ -6,6 +6,7 @@ use crate::formatter::HttpResponseRecord;
 
 use serde::Serialize;
 use std::collections::HashMap;
+use std::time::Duration;
 
 /// JSON Record (Response Status) Serialisation
 #[derive(Debug, Serialize, PartialEq, Eq)]
@@ -18,15 +19,44 @@ pub struct HttpJsonStatus {
     pub string: Option<String>,
 }
 
+/// JSON Record (Webhook or Polling Request) Serialisation
+#[derive(Debug, Serialize, PartialEq, Eq)]
+pub struct HttpJsonRequest {
+    pub method: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub path: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fetched_at: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub elapsed_ms: Option<u64>,
+}
+
 /// JSON Record (Response) Serialisation
 #[derive(Debug, Serialize, PartialEq, Eq)]
 pub struct HttpJsonRecord {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub request: Option<HttpJsonRequest>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub status: Option<HttpJsonStatus>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub header: Option<HashMap<String, JsonHeadersValue>>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub body: Option<String>,
+    pub body: Option<HttpJsonBody>,
+    /// `base64` for binary bodies
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub body_encoding: Option<&'static str>,
+}
+
+/// JSON Record (Body) Serialisation, split array elements are kept as JSON
+#[derive(Debug, Serialize, PartialEq, Eq)]
+#[serde(untagged)]
+pub enum HttpJsonBody {
+    Text(String),
+    Json(serde_json::Value),
 }
 
 /// JSON Record (Header Values) Serialisation
@@ -63,14 +93,38 @@ impl TryFrom<&mut HttpResponseRecord> for HttpJsonRecord {
             }),
         };
 
+        let webhook_request = resp_record.request.as_ref().map(|request| HttpJsonRequest {
+            method: request.method.to_owned(),
+            path: Some(request.path.to_owned()),
+            version: Some(request.version.to_owned()),
+            url: None,
+            fetched_at: None,
+            elapsed_ms: None,
+        });
+        let polling_request = resp_record.fetch.as_ref().map(|fetch| HttpJsonRequest {
+            method: fetch.method.to_owned(),
+            path: None,
+            version: None,
+            url: Some(fetch.url.to_owned()),
+            fetched_at: Some(humantime::format_rfc3339_millis(fetch.fetched_at).to_string()),
+            elapsed_ms: Some(duration_ms(fetch.elapsed)),
+        });
+        let request_rec = webhook_request.or(polling_request);
+
         Ok(HttpJsonRecord {
+            request: request_rec,
             status: status_rec,
             header: json_headers,
-            body: resp_record.body.to_owned(),
+            body: resp_record.body.to_owned().map(HttpJsonBody::Text),
+            body_encoding: None,
         })
     }
 }
 
+fn duration_ms(duration: Duration) -> u64 {
+    u64::try_from(duration.as_millis()).unwrap_or(u64::MAX)
+}
+
 use crate::formatter::to_json::JsonHeadersValue::{Many, One};
 use std::collections::hash_map::Entry;
 
@@ -1,16 +1,39 @@
 use clap::Parser;
+use fluvio_connectors_common::de;
 use fluvio_connectors_common::opt::{CommonConnectorOpt, GetOpts};
 use humantime::parse_duration;
 use schemars::JsonSchema;
+use serde::Deserialize;
+use std::path::PathBuf;
 use std::time::Duration;
 
-#[derive(Parser, Debug, JsonSchema, Clone)]
+use crate::auth::HttpAuthOpt;
+use crate::change::ChangeDetection;
+use crate::client::HttpClientOpt;
+use crate::endpoints::EndpointConfig;
+use crate::error::Error;
+use crate::formatter::BodyEncoding;
+use crate::pagination::PaginationStrategy;
+use crate::retry::ClientErrorAction;
+use crate::schedule::MissedTicks;
+use crate::stream::StreamFormat;
+use crate::webhook::WebhookOpt;
+
+#[derive(Parser, Debug, JsonSchema, Clone, Deserialize)]
 pub struct HttpOpt {
-    /// Endpoint for the http connector
-    #[clap(long)]
-    pub endpoint: String,
+    /// Endpoint for the http connector. May contain `{{ variable }}` placeholders,
+    /// see the request templates section of the README. Not needed in webhook mode
+    #[clap(long, required_unless_present_any = &["webhook-addr", "endpoints"])]
+    pub endpoint: Option<String>,
 
-    /// HTTP body for the request
+    /// Endpoints polled concurrently instead of `endpoint`, each an object with
+    /// its own `endpoint`, `name`, `method`, `headers`, `body`, `interval` and `topic`.
+    /// The options of the connector apply to every endpoint
+    #[clap(long, conflicts_with_all = &["endpoint", "webhook-addr"])]
+    #[serde(default, deserialize_with = "de::vec_json")]
+    pub endpoints: Vec<EndpointConfig>,
+
+    /// HTTP body for the request, may contain `{{ variable }}` placeholders
     #[clap(long)]
     pub body: Option<String>,
 
@@ -25,10 +48,29 @@ pub struct HttpOpt {
     /// Time to wait before sending
     /// Ex: '150ms', '20s'
     #[clap(long, parse(try_from_str = parse_duration), default_value = "10s")]
+    #[serde(deserialize_with = "de::duration")]
     pub interval: Duration,
 
-    /// Headers to include in the HTTP request, in "Key=Value" format
+    /// Cron expression the requests are sent on instead of every `interval`, e.g.
+    /// `0 9 * * Mon-Fri` for every weekday at 09:00. Five fields, or six with the
+    /// seconds first
+    #[clap(long)]
+    pub schedule: Option<String>,
+
+    /// Time zone of `schedule`, e.g. `Europe/Paris`
+    #[clap(long, default_value = "UTC")]
+    pub schedule_timezone: String,
+
+    /// What happens to the `interval` or `schedule` ticks that went by while a poll
+    /// was still running: catch-up | skip
+    #[clap(long, default_value = "catch-up")]
+    #[serde(deserialize_with = "de::from_str")]
+    pub missed_ticks: MissedTicks,
+
+    /// Headers to include in the HTTP request, in "Key=Value" format. Values may
+    /// contain `{{ variable }}` placeholders
     #[clap(long = "header", alias = "headers")]
+    #[serde(default, deserialize_with = "de::key_values")]
     pub headers: Vec<String>,
 
     /// DEPRECATED: Response output parts: body | full
@@ -43,11 +85,136 @@ pub struct HttpOpt {
     #[clap(long, default_value = "text")]
     pub output_type: String,
 
+    /// How bodies are put in the records: auto | text | raw | base64. `auto` decodes
+    /// textual content types, e.g. `text/*` or JSON, and keeps the bytes of the others.
+    /// Bytes are base64 encoded in `json` records
+    #[clap(long, default_value = "auto")]
+    #[serde(deserialize_with = "de::from_str")]
+    pub body_encoding: BodyEncoding,
+
+    /// Keep the request open and produce a record per event streamed in the
+    /// response, instead of sending a request every `interval`
+    #[clap(long, parse(try_from_str), default_value = "false")]
+    pub stream: bool,
+
+    /// How the streamed events are delimited: auto | sse | ndjson | chunk.
+    /// `auto` picks `sse` for `text/event-stream` responses and `ndjson` otherwise
+    #[clap(long, default_value = "auto")]
+    #[serde(deserialize_with = "de::from_str")]
+    pub stream_format: StreamFormat,
+
+    /// How the next page of a response is requested: none | link | cursor | page | offset.
+    /// Every page is fetched on each poll, and produced as its own record
+    #[clap(long, default_value = "none")]
+    #[serde(deserialize_with = "de::from_str")]
+    pub pagination: PaginationStrategy,
+
+    /// Query parameter holding the cursor, page number or offset. Defaults to
+    /// `cursor`, `page` or `offset` depending on `pagination`
+    #[clap(long)]
+    pub pagination_param: Option<String>,
+
+    /// JSON pointer to the cursor of the next page in the response body, e.g.
+    /// `/meta/next_cursor`. Either a cursor value or the URL of the next page
+    #[clap(long)]
+    pub pagination_cursor_pointer: Option<String>,
+
+    /// JSON pointer to the array of items in the response body, pages without items
+    /// are the last ones. Defaults to the whole body
+    #[clap(long, default_value = "")]
+    pub pagination_items_pointer: String,
+
+    /// Number of items of a full page, a page with fewer items is the last one.
+    /// Offsets are increased by the number of items of the page if not set
+    #[clap(long)]
+    pub pagination_page_size: Option<u64>,
+
+    /// Maximum number of pages fetched on each poll
+    #[clap(long, default_value = "100")]
+    pub max_pages: u32,
+
+    /// Produce each item of the `pagination_items_pointer` array as its own record,
+    /// instead of each page. Not applied in `stream` mode or with `split_pointer`
+    #[clap(long, parse(try_from_str), default_value = "false")]
+    pub pagination_item_records: bool,
+
+    /// JSON pointer to an array in the response body, e.g. `/items`. Each element
+    /// is produced as its own record instead of the whole response
+    #[clap(long)]
+    pub split_pointer: Option<String>,
+
+    /// JSON pointer to the record key within each split element, e.g. `/id`
+    #[clap(long)]
+    pub split_key_pointer: Option<String>,
+
+    /// Only produce responses when the resource changed: none | conditional | hash.
+    /// `conditional` sends `If-None-Match` and `If-Modified-Since` requests, `hash`
+    /// compares the SHA-256 of the body
+    #[clap(long, default_value = "none")]
+    #[serde(deserialize_with = "de::from_str")]
+    pub change_detection: ChangeDetection,
+
+    /// File the validators of the last produced responses are kept in, so a restarted
+    /// connector doesn't produce unchanged responses again
+    #[clap(long)]
+    pub change_detection_path: Option<PathBuf>,
+
+    /// File the last poll time and response the templates are rendered from are kept
+    /// in, so a restarted connector resumes from them instead of the defaults
+    #[clap(long)]
+    pub template_state_path: Option<PathBuf>,
+
+    /// Time to wait for the response of a request, network errors and timeouts are
+    /// retried as 5xx responses. Not applied in `stream` mode
+    /// Ex: '30s', '2m'
+    #[clap(long, parse(try_from_str = parse_duration))]
+    #[serde(default, deserialize_with = "de::option_duration")]
+    pub request_timeout: Option<Duration>,
+
+    /// What is done with 4xx responses other than 429: produce | skip | dead-letter
+    #[clap(long, default_value = "produce")]
+    #[serde(deserialize_with = "de::from_str")]
+    pub client_error: ClientErrorAction,
+
+    #[clap(flatten)]
+    #[schemars(flatten)]
+    #[serde(flatten)]
+    pub client: HttpClientOpt,
+
+    #[clap(flatten)]
+    #[schemars(flatten)]
+    #[serde(flatten)]
+    pub authentication: HttpAuthOpt,
+
     #[clap(flatten)]
     #[schemars(flatten)]
+    #[serde(flatten)]
+    pub webhook: WebhookOpt,
+
+    #[clap(flatten)]
+    #[schemars(flatten)]
+    #[serde(flatten)]
     pub common: CommonConnectorOpt,
 }
 
+impl HttpOpt {
+    /// Checks what clap enforces on the command line, for the options of a config
+    pub fn validate(&self) -> crate::error::Result<()> {
+        let webhook = self.webhook.webhook_addr.is_some();
+        if !self.endpoints.is_empty() && (self.endpoint.is_some() || webhook) {
+            return Err(Error::Endpoints(
+                "endpoints conflicts with endpoint and webhook_addr".to_string(),
+            ));
+        }
+        if self.endpoint.is_none() && self.endpoints.is_empty() && !webhook {
+            return Err(Error::Endpoints(
+                "either endpoint, endpoints or webhook_addr is required".to_string(),
+            ));
+        }
+        Ok(())
+    }
+}
+
 impl GetOpts for HttpOpt {
     type Opt = HttpOpt;
     fn name() -> &'static str {
@@ -61,5 +228,16 @@ impl GetOpts for HttpOpt {
     }
 }
 
+pub mod auth;
+pub mod change;
+pub mod client;
+pub mod endpoints;
 pub mod error;
 pub mod formatter;
+pub mod pagination;
+pub mod retry;
+pub mod schedule;
+pub mod source;
+pub mod stream;
+pub mod template;
+pub mod webhook;
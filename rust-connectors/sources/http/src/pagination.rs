@@ -0,0 +1,395 @@
+//! Walking paged responses: `Link` headers, cursors in the body, page and offset parameters
+
+use std::fmt;
+use std::str::FromStr;
+
+use reqwest::header::{HeaderMap, LINK};
+use reqwest::Url;
+use schemars::JsonSchema;
+use serde_json::Value;
+
+use crate::error::{Error, Result};
+use crate::HttpOpt;
+
+/// How the request for the next page is built from the current response
+#[derive(Debug, Clone, Copy, PartialEq, Eq, JsonSchema)]
+pub enum PaginationStrategy {
+    /// Only the first page is fetched
+    None,
+    /// URL of the `Link` header entry with `rel="next"` (RFC 5988)
+    Link,
+    /// Cursor read from the body, sent in a query parameter or used as the URL
+    Cursor,
+    /// Page number query parameter, increased by one
+    Page,
+    /// Offset query parameter, increased by the number of items on the page
+    Offset,
+}
+
+impl PaginationStrategy {
+    fn default_param(self) -> &'static str {
+        match self {
+            Self::Cursor => "cursor",
+            Self::Offset => "offset",
+            _ => "page",
+        }
+    }
+}
+
+impl FromStr for PaginationStrategy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "none" => Ok(Self::None),
+            "link" => Ok(Self::Link),
+            "cursor" => Ok(Self::Cursor),
+            "page" => Ok(Self::Page),
+            "offset" => Ok(Self::Offset),
+            _ => Err(format!(
+                "unknown pagination {s}, expected none, link, cursor, page or offset"
+            )),
+        }
+    }
+}
+
+impl fmt::Display for PaginationStrategy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let strategy = match self {
+            Self::None => "none",
+            Self::Link => "link",
+            Self::Cursor => "cursor",
+            Self::Page => "page",
+            Self::Offset => "offset",
+        };
+        f.write_str(strategy)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Pagination {
+    strategy: PaginationStrategy,
+    param: String,
+    cursor_pointer: String,
+    items_pointer: String,
+    page_size: Option<u64>,
+    pub max_pages: u32,
+}
+
+impl Pagination {
+    pub fn new(opts: &HttpOpt) -> Result<Self> {
+        let strategy = opts.pagination;
+        let cursor_pointer = match (strategy, &opts.pagination_cursor_pointer) {
+            (PaginationStrategy::Cursor, None) => {
+                return Err(Error::Pagination(
+                    "cursor pagination requires pagination_cursor_pointer".to_string(),
+                ))
+            }
+            (_, cursor_pointer) => cursor_pointer.clone().unwrap_or_default(),
+        };
+        Ok(Self {
+            strategy,
+            param: opts
+                .pagination_param
+                .clone()
+                .unwrap_or_else(|| strategy.default_param().to_string()),
+            cursor_pointer,
+            items_pointer: opts.pagination_items_pointer.clone(),
+            page_size: opts.pagination_page_size,
+            max_pages: opts.max_pages,
+        })
+    }
+
+    /// URL of the page following the response of `url`, `None` on the last page
+    pub fn next_url(&self, url: &Url, headers: &HeaderMap, body: &str) -> Option<Url> {
+        match self.strategy {
+            PaginationStrategy::None => None,
+            PaginationStrategy::Link => next_link(headers).and_then(|next| url.join(&next).ok()),
+            PaginationStrategy::Cursor => {
+                let body: Value = serde_json::from_str(body).ok()?;
+                let cursor = match body.pointer(&self.cursor_pointer)? {
+                    Value::String(cursor) if cursor.is_empty() => return None,
+                    Value::String(cursor) => cursor.clone(),
+                    Value::Number(cursor) => cursor.to_string(),
+                    _ => return None,
+                };
+                match Url::parse(&cursor) {
+                    Ok(next) => Some(next),
+                    Err(_) => Some(with_param(url, &self.param, &cursor)),
+                }
+            }
+            PaginationStrategy::Page => {
+                self.items(body)?;
+                let page = self.param_value(url).unwrap_or(1);
+                Some(with_param(url, &self.param, &(page + 1).to_string()))
+            }
+            PaginationStrategy::Offset => {
+                let items = self.items(body)?;
+                let offset = self.param_value(url).unwrap_or(0);
+                let next = offset + self.page_size.unwrap_or(items);
+                Some(with_param(url, &self.param, &next.to_string()))
+            }
+        }
+    }
+
+    /// Number of items on the page, `None` when it is the last one
+    fn items(&self, body: &str) -> Option<u64> {
+        let body: Value = serde_json::from_str(body).ok()?;
+        let items = body.pointer(&self.items_pointer)?.as_array()?.len() as u64;
+        match self.page_size {
+            _ if items == 0 => None,
+            Some(page_size) if items < page_size => None,
+            _ => Some(items),
+        }
+    }
+
+    fn param_value(&self, url: &Url) -> Option<u64> {
+        url.query_pairs()
+            .find(|(name, _)| *name == self.param)
+            .and_then(|(_, value)| value.parse().ok())
+    }
+}
+
+/// Target of the `rel="next"` entry of the `Link` header
+fn next_link(headers: &HeaderMap) -> Option<String> {
+    headers
+        .get_all(LINK)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(parse_links)
+        .find_map(|link| {
+            let is_next = link.params.iter().any(|(name, value)| {
+                name == "rel"
+                    && value
+                        .split_ascii_whitespace()
+                        .any(|rel| rel.eq_ignore_ascii_case("next"))
+            });
+            is_next.then_some(link.target)
+        })
+}
+
+/// Entry of a `Link` header: `<target>; name=value; name="quoted value"`
+#[derive(Debug, PartialEq, Eq)]
+struct Link {
+    target: String,
+    /// Parameters with their name lowercased, and their value unquoted
+    params: Vec<(String, String)>,
+}
+
+/// Parses the entries of a `Link` header value (RFC 8288). Commas and semicolons may
+/// appear in the targets and in quoted values, entries that can't be parsed are skipped.
+fn parse_links(value: &str) -> Vec<Link> {
+    let mut links = Vec::new();
+    let mut chars = value.chars().peekable();
+    loop {
+        while chars.next_if(|c| c.is_whitespace() || *c == ',').is_some() {}
+        if chars.peek().is_none() {
+            break;
+        }
+        if chars.next_if_eq(&'<').is_none() {
+            skip_entry(&mut chars);
+            continue;
+        }
+        let target: String = chars.by_ref().take_while(|c| *c != '>').collect();
+        let mut params = Vec::new();
+        loop {
+            while chars.next_if(|c| c.is_whitespace()).is_some() {}
+            if chars.next_if_eq(&';').is_none() {
+                break;
+            }
+            while chars.next_if(|c| c.is_whitespace()).is_some() {}
+            let mut name = String::new();
+            while let Some(c) =
+                chars.next_if(|c| !matches!(c, '=' | ';' | ',') && !c.is_whitespace())
+            {
+                name.push(c.to_ascii_lowercase());
+            }
+            while chars.next_if(|c| c.is_whitespace()).is_some() {}
+            let mut param_value = String::new();
+            if chars.next_if_eq(&'=').is_some() {
+                while chars.next_if(|c| c.is_whitespace()).is_some() {}
+                if chars.next_if_eq(&'"').is_some() {
+                    while let Some(c) = chars.next() {
+                        match c {
+                            '"' => break,
+                            '\\' => param_value.extend(chars.next()),
+                            c => param_value.push(c),
+                        }
+                    }
+                } else {
+                    while let Some(c) =
+                        chars.next_if(|c| !matches!(c, ';' | ',') && !c.is_whitespace())
+                    {
+                        param_value.push(c);
+                    }
+                }
+            }
+            if !name.is_empty() {
+                params.push((name, param_value));
+            }
+        }
+        skip_entry(&mut chars);
+        links.push(Link { target, params });
+    }
+    links
+}
+
+/// Skips what is left of the current entry, up to the comma ending it
+fn skip_entry(chars: &mut std::iter::Peekable<std::str::Chars>) {
+    let mut quoted = false;
+    while let Some(c) = chars.next_if(|c| quoted || *c != ',') {
+        match c {
+            '"' => quoted = !quoted,
+            '\\' if quoted => {
+                chars.next();
+            }
+            _ => {}
+        }
+    }
+}
+
+fn with_param(url: &Url, param: &str, value: &str) -> Url {
+    let mut next = url.clone();
+    let pairs: Vec<(String, String)> = url
+        .query_pairs()
+        .filter(|(name, _)| name != param)
+        .map(|(name, value)| (name.into_owned(), value.into_owned()))
+        .collect();
+    next.query_pairs_mut()
+        .clear()
+        .extend_pairs(pairs)
+        .append_pair(param, value);
+    next
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pagination(strategy: PaginationStrategy) -> Pagination {
+        Pagination {
+            strategy,
+            param: strategy.default_param().to_string(),
+            cursor_pointer: "/meta/next".to_string(),
+            items_pointer: "/items".to_string(),
+            page_size: None,
+            max_pages: 10,
+        }
+    }
+
+    #[test]
+    fn test_next_link() {
+        //given
+        let url = Url::parse("https://api.example.com/repos?per_page=2").unwrap();
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            LINK,
+            "</repos?page=1>; rel=\"prev\", </repos?page=3>; rel=\"next\""
+                .parse()
+                .unwrap(),
+        );
+
+        //when
+        let next = pagination(PaginationStrategy::Link).next_url(&url, &headers, "");
+        let last = pagination(PaginationStrategy::Link).next_url(&url, &HeaderMap::new(), "");
+
+        //then
+        assert_eq!(
+            next.map(String::from).as_deref(),
+            Some("https://api.example.com/repos?page=3")
+        );
+        assert_eq!(last, None);
+    }
+
+    #[test]
+    fn test_parse_links() {
+        //given
+        let value = r#"<https://api.example.com/items?ids=1,2;3>; rel="prev first"; title="a, \"b\"; c", <https://api.example.com/items?page=3> ; REL = Next ,invalid; rel=next, </items?page=9>; rel=last"#;
+
+        //when
+        let links = parse_links(value);
+
+        //then
+        assert_eq!(
+            links,
+            vec![
+                Link {
+                    target: "https://api.example.com/items?ids=1,2;3".to_string(),
+                    params: vec![
+                        ("rel".to_string(), "prev first".to_string()),
+                        ("title".to_string(), r#"a, "b"; c"#.to_string()),
+                    ],
+                },
+                Link {
+                    target: "https://api.example.com/items?page=3".to_string(),
+                    params: vec![("rel".to_string(), "Next".to_string())],
+                },
+                Link {
+                    target: "/items?page=9".to_string(),
+                    params: vec![("rel".to_string(), "last".to_string())],
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_next_cursor() {
+        //given
+        let url = Url::parse("https://api.example.com/events?limit=2&cursor=a").unwrap();
+        let pagination = pagination(PaginationStrategy::Cursor);
+
+        //when
+        let next = pagination.next_url(&url, &HeaderMap::new(), r#"{"meta":{"next":"b"}}"#);
+        let next_url = pagination.next_url(
+            &url,
+            &HeaderMap::new(),
+            r#"{"meta":{"next":"https://api.example.com/events?after=b"}}"#,
+        );
+        let last = pagination.next_url(&url, &HeaderMap::new(), r#"{"meta":{"next":null}}"#);
+
+        //then
+        assert_eq!(
+            next.map(String::from).as_deref(),
+            Some("https://api.example.com/events?limit=2&cursor=b")
+        );
+        assert_eq!(
+            next_url.map(String::from).as_deref(),
+            Some("https://api.example.com/events?after=b")
+        );
+        assert_eq!(last, None);
+    }
+
+    #[test]
+    fn test_next_page_and_offset() {
+        //given
+        let url = Url::parse("https://api.example.com/items").unwrap();
+        let page = pagination(PaginationStrategy::Page);
+        let mut offset = pagination(PaginationStrategy::Offset);
+        offset.page_size = Some(3);
+        let full_page = r#"{"items":[1,2,3]}"#;
+
+        //when
+        let second_page = page.next_url(&url, &HeaderMap::new(), full_page).unwrap();
+        let third_page = page.next_url(&second_page, &HeaderMap::new(), full_page);
+        let second_offset = offset.next_url(&url, &HeaderMap::new(), full_page).unwrap();
+
+        //then
+        assert_eq!(second_page.as_str(), "https://api.example.com/items?page=2");
+        assert_eq!(
+            third_page.map(String::from).as_deref(),
+            Some("https://api.example.com/items?page=3")
+        );
+        assert_eq!(
+            page.next_url(&url, &HeaderMap::new(), r#"{"items":[]}"#),
+            None
+        );
+        assert_eq!(
+            second_offset.as_str(),
+            "https://api.example.com/items?offset=3"
+        );
+        assert_eq!(
+            offset.next_url(&second_offset, &HeaderMap::new(), r#"{"items":[4]}"#),
+            None
+        );
+    }
+}
@@ -0,0 +1,151 @@
+//! Failed requests: retried server errors and rate limits, handling of client errors
+
+use std::fmt;
+use std::str::FromStr;
+use std::time::{Duration, SystemTime};
+
+use fluvio_connectors_common::dead_letter::{now_millis, DeadLetterBytes, DeadLetterRecord};
+use reqwest::header::{HeaderMap, RETRY_AFTER};
+use reqwest::{StatusCode, Url};
+use schemars::JsonSchema;
+
+/// What is done with 4xx responses, other than 429 which is retried
+#[derive(Debug, Clone, Copy, PartialEq, Eq, JsonSchema)]
+pub enum ClientErrorAction {
+    /// Produced as any other response
+    Produce,
+    /// Dropped with a warning
+    Skip,
+    /// Produced to `dead_letter_topic`
+    DeadLetter,
+}
+
+impl FromStr for ClientErrorAction {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "produce" => Ok(Self::Produce),
+            "skip" => Ok(Self::Skip),
+            "dead-letter" => Ok(Self::DeadLetter),
+            _ => Err(format!(
+                "unknown client error action {s}, expected produce, skip or dead-letter"
+            )),
+        }
+    }
+}
+
+impl fmt::Display for ClientErrorAction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let action = match self {
+            Self::Produce => "produce",
+            Self::Skip => "skip",
+            Self::DeadLetter => "dead-letter",
+        };
+        f.write_str(action)
+    }
+}
+
+/// Server errors and rate limited requests are sent again
+pub fn is_retryable_status(status: StatusCode) -> bool {
+    status.is_server_error() || status == StatusCode::TOO_MANY_REQUESTS
+}
+
+/// Client errors handled by `ClientErrorAction`
+pub fn is_client_error(status: StatusCode) -> bool {
+    status.is_client_error() && !is_retryable_status(status)
+}
+
+/// Delay asked by the server before sending the request again, in seconds or as
+/// an HTTP date
+pub fn retry_after(headers: &HeaderMap) -> Option<Duration> {
+    let retry_after = headers.get(RETRY_AFTER)?.to_str().ok()?.trim();
+    match retry_after.parse() {
+        Ok(seconds) => Some(Duration::from_secs(seconds)),
+        Err(_) => {
+            let retry_at = httpdate::parse_http_date(retry_after).ok()?;
+            Some(
+                retry_at
+                    .duration_since(SystemTime::now())
+                    .unwrap_or_default(),
+            )
+        }
+    }
+}
+
+/// Dead-letter record of a client error response to `url`, meant for `topic`
+pub fn dead_letter_record(
+    topic: &str,
+    url: &Url,
+    status: StatusCode,
+    body: &[u8],
+) -> DeadLetterRecord {
+    DeadLetterRecord {
+        error: format!("HTTP status {status}"),
+        topic: topic.to_string(),
+        partition: None,
+        offset: None,
+        timestamp: None,
+        url: Some(url.to_string()),
+        status: Some(status.as_u16()),
+        failed_at: now_millis(),
+        key: None,
+        value: DeadLetterBytes::new(body),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_status_classes() {
+        assert!(is_retryable_status(StatusCode::SERVICE_UNAVAILABLE));
+        assert!(is_retryable_status(StatusCode::TOO_MANY_REQUESTS));
+        assert!(!is_retryable_status(StatusCode::NOT_FOUND));
+        assert!(is_client_error(StatusCode::NOT_FOUND));
+        assert!(!is_client_error(StatusCode::TOO_MANY_REQUESTS));
+        assert!(!is_client_error(StatusCode::NOT_MODIFIED));
+    }
+
+    #[test]
+    fn test_retry_after() {
+        //given
+        let mut seconds = HeaderMap::new();
+        seconds.insert(RETRY_AFTER, "120".parse().unwrap());
+        let mut date = HeaderMap::new();
+        let in_a_minute = httpdate::fmt_http_date(SystemTime::now() + Duration::from_secs(61));
+        date.insert(RETRY_AFTER, in_a_minute.parse().unwrap());
+        let mut past_date = HeaderMap::new();
+        past_date.insert(
+            RETRY_AFTER,
+            "Wed, 21 Oct 2015 07:28:00 GMT".parse().unwrap(),
+        );
+
+        //then
+        assert_eq!(retry_after(&seconds), Some(Duration::from_secs(120)));
+        let until_date = retry_after(&date).unwrap();
+        assert!(until_date > Duration::from_secs(59) && until_date <= Duration::from_secs(61));
+        assert_eq!(retry_after(&past_date), Some(Duration::ZERO));
+        assert_eq!(retry_after(&HeaderMap::new()), None);
+    }
+
+    #[test]
+    fn test_dead_letter_record() {
+        //given
+        let url = Url::parse("https://api.github.com/repos/infinyon/missing").unwrap();
+
+        //when
+        let record = dead_letter_record("github", &url, StatusCode::NOT_FOUND, b"Not Found");
+        let json = serde_json::to_value(&record).unwrap();
+
+        //then
+        assert_eq!(json["error"], "HTTP status 404 Not Found");
+        assert_eq!(json["topic"], "github");
+        assert_eq!(json["url"], url.as_str());
+        assert_eq!(json["status"], 404);
+        assert_eq!(json["value"]["encoding"], "utf8");
+        assert_eq!(json["value"]["data"], "Not Found");
+        assert!(json.get("offset").is_none());
+    }
+}
@@ -0,0 +1,292 @@
+//! When the endpoint is polled: every `interval`, or on the ticks of a cron `schedule`
+
+use std::fmt;
+use std::str::FromStr;
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use chrono_tz::Tz;
+use schemars::JsonSchema;
+use tokio::time::{Interval, MissedTickBehavior};
+
+use crate::error::{Error, Result};
+use crate::HttpOpt;
+
+/// What happens to the ticks that went by while a poll was still running
+#[derive(Debug, Clone, Copy, PartialEq, Eq, JsonSchema)]
+pub enum MissedTicks {
+    /// Polls right away once for every missed tick
+    CatchUp,
+    /// Waits for the next tick to come
+    Skip,
+}
+
+impl FromStr for MissedTicks {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "catch-up" => Ok(Self::CatchUp),
+            "skip" => Ok(Self::Skip),
+            _ => Err(format!(
+                "unknown missed ticks {s}, expected catch-up or skip"
+            )),
+        }
+    }
+}
+
+impl fmt::Display for MissedTicks {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let missed_ticks = match self {
+            Self::CatchUp => "catch-up",
+            Self::Skip => "skip",
+        };
+        f.write_str(missed_ticks)
+    }
+}
+
+/// Cron expression in a time zone
+#[derive(Debug, Clone)]
+pub struct CronSchedule {
+    schedule: cron::Schedule,
+    timezone: Tz,
+    missed_ticks: MissedTicks,
+}
+
+impl CronSchedule {
+    /// Parses `schedule` if set. Expressions have five fields, or six with the
+    /// seconds first, and days of the week numbered from 0 for Sunday as in standard cron.
+    pub fn new(opts: &HttpOpt) -> Result<Option<Self>> {
+        let expression = match opts.schedule {
+            Some(ref expression) => expression,
+            None => return Ok(None),
+        };
+        let mut fields: Vec<String> = expression.split_whitespace().map(String::from).collect();
+        if fields.len() == 5 {
+            fields.insert(0, "0".to_string());
+        }
+        if let Some(day_of_week) = fields.get_mut(5) {
+            *day_of_week = day_of_week_names(day_of_week)?;
+        }
+        let expression = fields.join(" ");
+        let schedule = expression
+            .parse()
+            .map_err(|e| Error::Schedule(format!("invalid schedule {expression}: {e}")))?;
+        let timezone = opts
+            .schedule_timezone
+            .parse()
+            .map_err(|e| Error::Schedule(format!("invalid schedule_timezone: {e}")))?;
+        Ok(Some(Self {
+            schedule,
+            timezone,
+            missed_ticks: opts.missed_ticks,
+        }))
+    }
+
+    /// Tick following `last`, the previous tick, when it is `now`
+    fn next_tick(&self, last: DateTime<Utc>, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        let after = match self.missed_ticks {
+            MissedTicks::CatchUp => last,
+            MissedTicks::Skip => last.max(now),
+        };
+        let next = self
+            .schedule
+            .after(&after.with_timezone(&self.timezone))
+            .next()?;
+        Some(next.with_timezone(&Utc))
+    }
+}
+
+const DAYS_OF_WEEK: [&str; 7] = ["Sun", "Mon", "Tue", "Wed", "Thu", "Fri", "Sat"];
+
+/// Standard cron `day_of_week` field, numbered from 0 or 7 for Sunday, with the numbers
+/// replaced by names as the `cron` crate numbers days from 1 for Sunday
+fn day_of_week_names(day_of_week: &str) -> Result<String> {
+    let name = |day: &str| match day.parse::<usize>() {
+        Ok(number) if number <= 7 => Ok(DAYS_OF_WEEK[number % 7].to_string()),
+        Ok(_) => Err(Error::Schedule(format!("invalid day of week {day}"))),
+        Err(_) => Ok(day.to_string()),
+    };
+    let index = |day: &str| {
+        day.parse::<usize>().ok().or_else(|| {
+            let day = day.to_ascii_lowercase();
+            DAYS_OF_WEEK
+                .iter()
+                .position(|name| day.starts_with(&name.to_ascii_lowercase()))
+        })
+    };
+
+    let items = day_of_week.split(',').map(|item| {
+        let (range, step) = match item.split_once('/') {
+            Some((range, step)) => (range, Some(step)),
+            None => (item, None),
+        };
+        let step_suffix = step.map(|step| format!("/{step}")).unwrap_or_default();
+        match range.split_once('-') {
+            // Sunday ends the week for the crate, so a range up to 7 stops at Saturday
+            // and Sunday is added when the range steps on it
+            Some((start, "7")) => {
+                let steps_on_sunday = match (index(start), step.map(str::parse::<usize>)) {
+                    (Some(_), None) => true,
+                    (Some(start), Some(Ok(step))) => step > 0 && (7 - start) % step == 0,
+                    _ => false,
+                };
+                let sunday = if steps_on_sunday { ",Sun" } else { "" };
+                Ok(format!("{}-Sat{step_suffix}{sunday}", name(start)?))
+            }
+            Some((start, end)) => Ok(format!("{}-{}{step_suffix}", name(start)?, name(end)?)),
+            None => Ok(format!("{}{step_suffix}", name(range)?)),
+        }
+    });
+    Ok(items.collect::<Result<Vec<_>>>()?.join(","))
+}
+
+/// Ticks of an `interval` or of a cron `schedule`
+pub enum Ticker {
+    Interval(Interval),
+    Cron {
+        schedule: Box<CronSchedule>,
+        last: DateTime<Utc>,
+    },
+}
+
+impl Ticker {
+    pub fn new(
+        interval: Duration,
+        schedule: Option<CronSchedule>,
+        missed_ticks: MissedTicks,
+    ) -> Self {
+        match schedule {
+            Some(schedule) => Self::Cron {
+                schedule: Box::new(schedule),
+                last: Utc::now(),
+            },
+            None => {
+                let mut interval = tokio::time::interval(interval);
+                interval.set_missed_tick_behavior(match missed_ticks {
+                    MissedTicks::CatchUp => MissedTickBehavior::Burst,
+                    MissedTicks::Skip => MissedTickBehavior::Skip,
+                });
+                Self::Interval(interval)
+            }
+        }
+    }
+
+    /// Waits for the next tick. Returns false once a schedule has no tick left.
+    pub async fn tick(&mut self) -> bool {
+        match self {
+            Self::Interval(interval) => {
+                interval.tick().await;
+                true
+            }
+            Self::Cron { schedule, last } => {
+                let now = Utc::now();
+                let next = match schedule.next_tick(*last, now) {
+                    Some(next) => next,
+                    None => return false,
+                };
+                if let Ok(wait) = (next - now).to_std() {
+                    let next = next.with_timezone(&schedule.timezone);
+                    tracing::debug!(%next, "Waiting for next tick");
+                    tokio::time::sleep(wait).await;
+                }
+                *last = next;
+                true
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+    use clap::Parser;
+
+    use super::*;
+
+    fn schedule(expression: &str, timezone: &str, missed_ticks: MissedTicks) -> CronSchedule {
+        let opts = HttpOpt::parse_from([
+            "http-source",
+            "--endpoint=http://localhost",
+            "--fluvio-topic=test",
+            &format!("--schedule={expression}"),
+            &format!("--schedule-timezone={timezone}"),
+            &format!("--missed-ticks={missed_ticks}"),
+        ]);
+        CronSchedule::new(&opts).unwrap().unwrap()
+    }
+
+    #[test]
+    fn test_standard_days_of_week() {
+        //given
+        let weekdays = schedule("0 9 * * 1-5", "UTC", MissedTicks::Skip);
+        // Saturday 2023-01-07
+        let saturday = Utc.with_ymd_and_hms(2023, 1, 7, 0, 0, 0).unwrap();
+
+        //when
+        let ticks: Vec<_> = std::iter::successors(weekdays.next_tick(saturday, saturday), |tick| {
+            weekdays.next_tick(*tick, *tick)
+        })
+        .take(6)
+        .map(|tick| tick.format("%a %d").to_string())
+        .collect();
+
+        //then
+        assert_eq!(
+            ticks,
+            ["Mon 09", "Tue 10", "Wed 11", "Thu 12", "Fri 13", "Mon 16"]
+        );
+        assert_eq!(day_of_week_names("0").unwrap(), "Sun");
+        assert_eq!(day_of_week_names("7,Mon").unwrap(), "Sun,Mon");
+        assert_eq!(day_of_week_names("5-7").unwrap(), "Fri-Sat,Sun");
+        assert_eq!(day_of_week_names("1-7/2").unwrap(), "Mon-Sat/2,Sun");
+        assert_eq!(day_of_week_names("2-7/2").unwrap(), "Tue-Sat/2");
+        assert_eq!(day_of_week_names("*/2").unwrap(), "*/2");
+        assert!(day_of_week_names("8").is_err());
+        assert!(matches!(
+            CronSchedule::new(&HttpOpt::parse_from([
+                "http-source",
+                "--endpoint=http://localhost",
+                "--fluvio-topic=test",
+                "--schedule=0 9 * * 8",
+            ])),
+            Err(Error::Schedule(_))
+        ));
+    }
+
+    #[test]
+    fn test_next_tick_in_timezone() {
+        //given
+        let weekdays = schedule("0 9 * * Mon-Fri", "Europe/Paris", MissedTicks::CatchUp);
+        // Friday 2023-01-06 10:00 in Paris
+        let friday = Utc.with_ymd_and_hms(2023, 1, 6, 9, 0, 0).unwrap();
+
+        //when
+        let next = weekdays.next_tick(friday, friday).unwrap();
+
+        //then
+        // Monday 09:00 in Paris
+        assert_eq!(next, Utc.with_ymd_and_hms(2023, 1, 9, 8, 0, 0).unwrap());
+    }
+
+    #[test]
+    fn test_missed_ticks() {
+        //given
+        let hourly = schedule("0 * * * *", "UTC", MissedTicks::CatchUp);
+        let hourly_skip = schedule("0 * * * *", "UTC", MissedTicks::Skip);
+        let last = Utc.with_ymd_and_hms(2023, 1, 6, 9, 0, 0).unwrap();
+        let now = Utc.with_ymd_and_hms(2023, 1, 6, 11, 30, 0).unwrap();
+
+        //then
+        assert_eq!(
+            hourly.next_tick(last, now).unwrap(),
+            Utc.with_ymd_and_hms(2023, 1, 6, 10, 0, 0).unwrap()
+        );
+        assert_eq!(
+            hourly_skip.next_tick(last, now).unwrap(),
+            Utc.with_ymd_and_hms(2023, 1, 6, 12, 0, 0).unwrap()
+        );
+        assert_eq!("skip".parse(), Ok(MissedTicks::Skip));
+        assert!("burst".parse::<MissedTicks>().is_err());
+    }
+}
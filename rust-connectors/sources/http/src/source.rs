@@ -0,0 +1,571 @@
+//! Fetching the endpoint and producing its responses to Fluvio
+
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::{Instant, SystemTime};
+
+use fluvio_connectors_common::dead_letter::DeadLetterProducer;
+use fluvio_connectors_common::fluvio::{RecordKey, TopicProducer};
+use fluvio_connectors_common::health::ConnectorHealth;
+use fluvio_connectors_common::metrics::{ConnectorMetrics, FORMAT_ERROR, UPSTREAM_ERROR};
+use fluvio_connectors_common::retry::RetryPolicy;
+use fluvio_connectors_common::shutdown::ShutdownSignal;
+use hyper::body::Bytes;
+use reqwest::{StatusCode, Url};
+use serde_json::Value;
+
+use crate::auth::Authenticator;
+use crate::change::ChangeDetector;
+use crate::error::{Error, Result};
+use crate::formatter::{BodyEncoding, HttpFetch, HttpResponseRecord};
+use crate::pagination::Pagination;
+use crate::retry::{
+    dead_letter_record, is_client_error, is_retryable_status, retry_after, ClientErrorAction,
+};
+use crate::schedule::{CronSchedule, Ticker};
+use crate::stream::{LineSplitter, SseParser, StreamFormat, Utf8Chunks};
+use crate::template::{RenderedRequest, RequestTemplate, TemplateContext, TemplateState};
+use crate::HttpOpt;
+
+const LAST_EVENT_ID_HEADER: &str = "Last-Event-ID";
+
+/// Body of a response, decoded as text unless its encoding keeps the bytes
+enum ResponseBody {
+    Text(String),
+    Binary(Bytes),
+}
+
+impl ResponseBody {
+    fn text(&self) -> Option<&str> {
+        match self {
+            Self::Text(text) => Some(text),
+            Self::Binary(_) => None,
+        }
+    }
+
+    fn as_bytes(&self) -> &[u8] {
+        match self {
+            Self::Text(text) => text.as_bytes(),
+            Self::Binary(bytes) => bytes,
+        }
+    }
+}
+
+pub struct HttpSource {
+    opts: HttpOpt,
+    client: reqwest::Client,
+    method: reqwest::Method,
+    template: RequestTemplate,
+    template_state: Mutex<TemplateState>,
+    schedule: Option<CronSchedule>,
+    pagination: Pagination,
+    changes: ChangeDetector,
+    auth: Authenticator,
+    retry_policy: RetryPolicy,
+    producer: TopicProducer,
+    dead_letter: Option<DeadLetterProducer>,
+    metrics: Arc<ConnectorMetrics>,
+    health: Arc<ConnectorHealth>,
+    shutdown: ShutdownSignal,
+}
+
+impl HttpSource {
+    pub fn new(
+        opts: HttpOpt,
+        producer: TopicProducer,
+        metrics: Arc<ConnectorMetrics>,
+        health: Arc<ConnectorHealth>,
+        shutdown: ShutdownSignal,
+    ) -> Result<Self> {
+        let method = opts
+            .method
+            .parse()
+            .map_err(|_| Error::Method(opts.method.clone()))?;
+        let template = RequestTemplate::new(&opts)?;
+        let template_state = TemplateState::load(opts.template_state_path.as_deref())
+            .map_err(Error::TemplateState)?;
+        let schedule = CronSchedule::new(&opts)?;
+        let pagination = Pagination::new(&opts)?;
+        let changes =
+            ChangeDetector::open(opts.change_detection, opts.change_detection_path.clone())
+                .map_err(Error::ChangeDetection)?;
+        let client = opts.client.build()?;
+        let auth = Authenticator::new(opts.authentication.clone(), client.clone())?;
+        if opts.client_error == ClientErrorAction::DeadLetter
+            && opts.common.dead_letter_topic.is_none()
+        {
+            return Err(Error::ClientError(
+                "dead-letter requires dead_letter_topic".to_string(),
+            ));
+        }
+        let retry_policy = opts.common.retry_common.retry_policy();
+        Ok(Self {
+            opts,
+            client,
+            method,
+            template,
+            template_state: Mutex::new(template_state),
+            schedule,
+            pagination,
+            changes,
+            auth,
+            retry_policy,
+            producer,
+            dead_letter: None,
+            metrics,
+            health,
+            shutdown,
+        })
+    }
+
+    /// Producer of the `client_error` dead-letter topic
+    pub fn with_dead_letter(mut self, dead_letter: DeadLetterProducer) -> Self {
+        self.dead_letter = Some(dead_letter);
+        self
+    }
+
+    /// Polls or streams the endpoint until shutdown, then flushes the producer
+    pub async fn run(&self) -> Result<()> {
+        if self.opts.stream {
+            self.stream().await?;
+        } else {
+            self.poll().await?;
+        }
+        tracing::info!("Shutting down, flushing producer");
+        self.producer.flush().await.map_err(Error::Fluvio)
+    }
+
+    /// Renders the endpoint, headers and body templates for a poll starting at `now`
+    fn render(&self, now: SystemTime) -> Result<RenderedRequest> {
+        let state = self
+            .template_state
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        self.template.render(&TemplateContext {
+            now,
+            last_poll: state.last_poll,
+            last_response: state.last_response.clone(),
+            secrets: &self.opts.common.secrets,
+        })
+    }
+
+    fn request(&self, url: &Url, rendered: &RenderedRequest) -> reqwest::RequestBuilder {
+        let mut req = self.client.request(self.method.clone(), url.clone());
+        req = req.header("user-agent", self.opts.user_agent.clone());
+
+        for (key, value) in &rendered.headers {
+            req = req.header(key, value);
+        }
+
+        if let Some(ref body) = rendered.body {
+            req = req.body(body.clone());
+        }
+        match self.opts.request_timeout {
+            Some(timeout) if !self.opts.stream => req.timeout(timeout),
+            _ => req,
+        }
+    }
+
+    /// Sends the request built by `build` until it gets a response which is neither a
+    /// server error nor rate limited, waiting as asked by `Retry-After` or with backoff
+    async fn fetch<F>(&self, build: F) -> Result<reqwest::Response>
+    where
+        F: Fn() -> reqwest::RequestBuilder,
+    {
+        let mut shutdown = self.shutdown.clone();
+        let mut attempt = 1;
+        loop {
+            let (err, backoff) = match self.send_request(&build).await {
+                Ok(response) if is_retryable_status(response.status()) => {
+                    self.metrics.error(UPSTREAM_ERROR);
+                    let backoff = retry_after(response.headers());
+                    (Error::Status(response.status()), backoff)
+                }
+                Ok(response) => return Ok(response),
+                Err(err @ Error::Request(_)) => (err, None),
+                Err(err) => return Err(err),
+            };
+            if attempt >= self.retry_policy.max_attempts || shutdown.is_requested() {
+                return Err(err);
+            }
+            let backoff = backoff.unwrap_or_else(|| self.retry_policy.backoff(attempt));
+            tracing::warn!(
+                attempt,
+                max_attempts = self.retry_policy.max_attempts,
+                ?backoff,
+                "Retrying after error: {:?}",
+                err
+            );
+            shutdown.until(tokio::time::sleep(backoff)).await;
+            attempt += 1;
+        }
+    }
+
+    /// Sends the request built by `build`, again with a new access token if the
+    /// current one got rejected
+    async fn send_request<F>(&self, build: F) -> Result<reqwest::Response>
+    where
+        F: Fn() -> reqwest::RequestBuilder,
+    {
+        let response = self.send(self.auth.authenticate(build()).await?).await?;
+        if response.status() == StatusCode::UNAUTHORIZED && self.auth.invalidate().await {
+            tracing::info!("Access token rejected, fetching a new one");
+            return self.send(self.auth.authenticate(build()).await?).await;
+        }
+        Ok(response)
+    }
+
+    async fn send(&self, req: reqwest::RequestBuilder) -> Result<reqwest::Response> {
+        let response = req.send().await.map_err(|e| {
+            self.metrics.error(UPSTREAM_ERROR);
+            self.health.set_upstream_connected(false);
+            Error::Request(e)
+        })?;
+        self.health.set_upstream_connected(true);
+        Ok(response)
+    }
+
+    fn formatter(&self, response: &reqwest::Response) -> Result<HttpResponseRecord> {
+        let mut formatter = HttpResponseRecord::try_from(response).map_err(Error::Record)?;
+        formatter
+            .configure_output(&self.opts.output_type, &self.opts.output_parts)
+            .map_err(Error::Record)?;
+        Ok(formatter)
+    }
+
+    /// Reads the body of `response`, as text unless `encoding` keeps the bytes
+    async fn read_body(
+        &self,
+        response: reqwest::Response,
+        encoding: BodyEncoding,
+    ) -> Result<ResponseBody> {
+        let body = match encoding {
+            BodyEncoding::Auto | BodyEncoding::Text => {
+                response.text().await.map(ResponseBody::Text)
+            }
+            BodyEncoding::Raw | BodyEncoding::Base64 => {
+                response.bytes().await.map(ResponseBody::Binary)
+            }
+        };
+        body.map_err(|e| {
+            self.metrics.error(UPSTREAM_ERROR);
+            Error::ResponseBody(e)
+        })
+    }
+
+    /// Produces a response body, binary ones as a single record
+    async fn produce_response(
+        &self,
+        formatter: &mut HttpResponseRecord,
+        body: &ResponseBody,
+        encoding: BodyEncoding,
+    ) -> Result<()> {
+        match body {
+            ResponseBody::Text(text) => self.produce_body(formatter, text).await,
+            ResponseBody::Binary(bytes) => {
+                self.produce(None, formatter.record_bytes(bytes, encoding))
+                    .await
+            }
+        }
+    }
+
+    /// Produces `body` as a single record, or each element of the `split_pointer` array,
+    /// or of the items of the page with `pagination_item_records`
+    async fn produce_body(&self, formatter: &mut HttpResponseRecord, body: &str) -> Result<()> {
+        let split_pointer = match self.opts.split_pointer {
+            Some(ref split_pointer) => split_pointer,
+            None if self.opts.pagination_item_records && !self.opts.stream => {
+                &self.opts.pagination_items_pointer
+            }
+            None => {
+                return self
+                    .produce(None, formatter.record(Some(body)).into_bytes())
+                    .await
+            }
+        };
+        let key_pointer = self.opts.split_key_pointer.as_deref();
+        let records = match formatter.split_records(body, split_pointer, key_pointer) {
+            Ok(records) => records,
+            Err(err) => {
+                tracing::error!(%err, "Dropping response");
+                self.metrics.error(FORMAT_ERROR);
+                self.metrics.record_dropped();
+                return Ok(());
+            }
+        };
+        for (key, record_out) in records {
+            self.produce(key, record_out.into_bytes()).await?;
+        }
+        Ok(())
+    }
+
+    /// Handles a 4xx response according to `client_error`. Returns whether it is
+    /// produced as any other response.
+    async fn client_error(&self, url: &Url, status: StatusCode, body: &str) -> Result<bool> {
+        match (self.opts.client_error, &self.dead_letter) {
+            (ClientErrorAction::Produce, _) => return Ok(true),
+            (ClientErrorAction::DeadLetter, Some(dead_letter)) => {
+                tracing::warn!(%url, %status, "Sending response to dead-letter topic");
+                let record =
+                    dead_letter_record(dead_letter.source_topic(), url, status, body.as_bytes());
+                dead_letter
+                    .send_record(&record)
+                    .await
+                    .map_err(|err| Error::DeadLetter(format!("{err:#}")))?;
+            }
+            _ => tracing::warn!(%url, %status, "Skipping response"),
+        }
+        self.metrics.record_dropped();
+        Ok(false)
+    }
+
+    async fn produce(&self, key: Option<String>, record_out: Vec<u8>) -> Result<()> {
+        tracing::debug!(?key, record_out = %String::from_utf8_lossy(&record_out), "Producing");
+
+        let record_len = record_out.len();
+        let started = Instant::now();
+        self.producer
+            .send(key.map_or(RecordKey::NULL, RecordKey::from), record_out)
+            .await
+            .map_err(Error::Fluvio)?;
+        self.metrics.record_written(record_len, started.elapsed());
+        self.health.record_succeeded();
+        Ok(())
+    }
+
+    /// Sends a request every `interval` or on each tick of `schedule`, producing one
+    /// record per response
+    async fn poll(&self) -> Result<()> {
+        let mut ticker = Ticker::new(
+            self.opts.interval,
+            self.schedule.clone(),
+            self.opts.missed_ticks,
+        );
+        let mut shutdown = self.shutdown.clone();
+
+        while let Some(true) = shutdown.until(ticker.tick()).await {
+            match self.poll_pages().await {
+                Ok(()) => {}
+                Err(err) if err.is_fatal() => return Err(err),
+                Err(err) => tracing::error!(?err, "Poll failed"),
+            }
+        }
+        Ok(())
+    }
+
+    /// Fetches the endpoint and the pages following it, up to `max_pages`. The state
+    /// the templates are rendered from only moves on once every page got handled.
+    async fn poll_pages(&self) -> Result<()> {
+        let started = SystemTime::now();
+        let rendered = self.render(started)?;
+        let mut url = rendered.url.clone();
+        let mut last_response = None;
+        for page in 1.. {
+            let change_key = self.change_key(page);
+            let fetched_at = SystemTime::now();
+            let fetch_started = Instant::now();
+            let response = self
+                .fetch(|| {
+                    self.changes
+                        .conditional_request(&change_key, self.request(&url, &rendered))
+                })
+                .await?;
+            let mut formatter = self.formatter(&response)?;
+            let status = response.status();
+            let headers = response.headers().clone();
+            let encoding = self.opts.body_encoding.resolve(&headers);
+
+            let response_body = self.read_body(response, encoding).await?;
+            let body_text = response_body.text();
+            self.metrics.record_read(response_body.as_bytes().len());
+            formatter.fetch = Some(HttpFetch {
+                method: self.method.to_string(),
+                url: url.to_string(),
+                fetched_at,
+                elapsed: fetch_started.elapsed(),
+            });
+
+            if is_client_error(status)
+                && !self
+                    .client_error(
+                        &url,
+                        status,
+                        &String::from_utf8_lossy(response_body.as_bytes()),
+                    )
+                    .await?
+            {
+                return Ok(());
+            }
+            if let Some(Ok(value)) = body_text.map(serde_json::from_str::<Value>) {
+                last_response = Some(value);
+            }
+
+            // a 304 has no body or headers to read the next page from, it is the one
+            // of the unchanged response
+            let next_url = if status == StatusCode::NOT_MODIFIED {
+                self.changes.next_url(&change_key)
+            } else {
+                self.pagination
+                    .next_url(&url, &headers, body_text.unwrap_or_default())
+            };
+
+            match self
+                .changes
+                .check(&change_key, status, &headers, response_body.as_bytes())
+            {
+                Some(validators) => {
+                    self.produce_response(&mut formatter, &response_body, encoding)
+                        .await?;
+                    self.changes
+                        .commit(&change_key, validators, next_url.as_ref())
+                        .await
+                        .map_err(Error::ChangeDetection)?;
+                }
+                None => tracing::debug!(%url, "Unchanged response, skipping"),
+            }
+
+            match next_url {
+                Some(_) if page >= self.pagination.max_pages => {
+                    tracing::warn!(
+                        max_pages = self.pagination.max_pages,
+                        "Stopping pagination, max_pages reached"
+                    );
+                    break;
+                }
+                Some(next) => url = next,
+                None => break,
+            }
+        }
+
+        let saved = {
+            let mut state = self
+                .template_state
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner);
+            state.last_poll = Some(started);
+            if last_response.is_some() {
+                state.last_response = last_response;
+            }
+            TemplateState {
+                last_poll: state.last_poll,
+                last_response: state.last_response.clone(),
+            }
+        };
+        if let Some(ref path) = self.opts.template_state_path {
+            saved.save(path).await.map_err(Error::TemplateState)?;
+        }
+        Ok(())
+    }
+
+    /// Key of the change detection state of the `page`th page of a poll: the endpoint
+    /// before its templates are rendered, so it is the same on every poll
+    fn change_key(&self, page: u32) -> String {
+        let endpoint = self.opts.endpoint.as_deref().unwrap_or_default();
+        match page {
+            1 => endpoint.to_string(),
+            page => format!("{endpoint}#page={page}"),
+        }
+    }
+
+    /// Keeps a request open, producing one record per event of the response. The
+    /// request is sent again with backoff when the response ends or fails.
+    async fn stream(&self) -> Result<()> {
+        let retry_policy = self.opts.common.retry_common.retry_policy();
+        let mut sse = SseParser::default();
+        let mut reconnects = 0;
+        let mut shutdown = self.shutdown.clone();
+        while !shutdown.is_requested() {
+            let connected_at = Instant::now();
+            match self.stream_response(&mut sse).await {
+                Ok(()) => tracing::info!("Stream ended"),
+                Err(err) if err.is_fatal() => return Err(err),
+                Err(err) => tracing::error!(?err, "Stream failed"),
+            }
+            sse.reset();
+            if shutdown.is_requested() {
+                break;
+            }
+            self.health.set_upstream_connected(false);
+
+            // a stream that stayed up for a while starts the backoff over
+            if connected_at.elapsed() > retry_policy.max_backoff {
+                reconnects = 0;
+            }
+            reconnects += 1;
+            let backoff = sse
+                .retry
+                .unwrap_or_else(|| retry_policy.backoff(reconnects));
+            tracing::info!("Reconnecting after {backoff:?}");
+            shutdown.until(tokio::time::sleep(backoff)).await;
+        }
+        Ok(())
+    }
+
+    async fn stream_response(&self, sse: &mut SseParser) -> Result<()> {
+        let fetched_at = SystemTime::now();
+        let fetch_started = Instant::now();
+        let rendered = self.render(fetched_at)?;
+        let build = || {
+            let req = self.request(&rendered.url, &rendered);
+            match sse.last_event_id {
+                Some(ref last_event_id) => req.header(LAST_EVENT_ID_HEADER, last_event_id),
+                None => req,
+            }
+        };
+        let mut response = self
+            .send_request(build)
+            .await?
+            .error_for_status()
+            .map_err(Error::Request)?;
+        let format = self.opts.stream_format.resolve(response.headers());
+        tracing::info!(%format, "Streaming response");
+        let encoding = self.opts.body_encoding.resolve(response.headers());
+        let mut formatter = self.formatter(&response)?;
+        formatter.fetch = Some(HttpFetch {
+            method: self.method.to_string(),
+            url: rendered.url.to_string(),
+            fetched_at,
+            elapsed: fetch_started.elapsed(),
+        });
+
+        let mut lines = LineSplitter::default();
+        let mut text = Utf8Chunks::default();
+        let mut shutdown = self.shutdown.clone();
+        while let Some(chunk) = shutdown.until(response.chunk()).await {
+            let chunk = chunk.map_err(|e| {
+                self.metrics.error(UPSTREAM_ERROR);
+                Error::ResponseBody(e)
+            })?;
+            let end_of_body = chunk.is_none();
+            if let (Some(chunk), StreamFormat::Chunk, BodyEncoding::Raw | BodyEncoding::Base64) =
+                (&chunk, format, encoding)
+            {
+                self.metrics.record_read(chunk.len());
+                self.produce(None, formatter.record_bytes(chunk, encoding))
+                    .await?;
+                continue;
+            }
+            let events = match (chunk, format) {
+                (Some(chunk), StreamFormat::Chunk) => vec![text.push(&chunk)],
+                (Some(chunk), StreamFormat::Sse) => lines
+                    .push(&chunk)
+                    .iter()
+                    .filter_map(|line| sse.feed(line))
+                    .map(|event| event.data)
+                    .collect(),
+                (Some(chunk), _) => lines.push(&chunk),
+                // an event not followed by an empty line is incomplete
+                (None, StreamFormat::Sse) => Vec::new(),
+                (None, StreamFormat::Chunk) => text.finish().into_iter().collect(),
+                (None, _) => lines.finish().into_iter().collect(),
+            };
+            for event in events.iter().filter(|event| !event.trim().is_empty()) {
+                self.metrics.record_read(event.len());
+                self.produce_body(&mut formatter, event).await?;
+            }
+            if end_of_body {
+                break;
+            }
+        }
+        Ok(())
+    }
+}
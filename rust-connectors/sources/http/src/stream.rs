@@ -0,0 +1,307 @@
+//! Streaming responses: Server-Sent Events, newline-delimited JSON and chunks
+
+use std::fmt;
+use std::str::FromStr;
+use std::time::Duration;
+
+use reqwest::header::{HeaderMap, CONTENT_TYPE};
+use schemars::JsonSchema;
+
+const SSE_CONTENT_TYPE: &str = "text/event-stream";
+
+/// How the events of a streamed response are delimited
+#[derive(Debug, Clone, Copy, PartialEq, Eq, JsonSchema)]
+pub enum StreamFormat {
+    /// `sse` for `text/event-stream` responses, `ndjson` otherwise
+    Auto,
+    /// Server-Sent Events, one record per event data
+    Sse,
+    /// One record per non-empty line
+    Ndjson,
+    /// One record per chunk received
+    Chunk,
+}
+
+impl StreamFormat {
+    /// Resolves `Auto` from the content type of the response
+    pub fn resolve(self, headers: &HeaderMap) -> Self {
+        match self {
+            Self::Auto => {
+                let content_type = headers
+                    .get(CONTENT_TYPE)
+                    .and_then(|content_type| content_type.to_str().ok())
+                    .unwrap_or_default();
+                if content_type.starts_with(SSE_CONTENT_TYPE) {
+                    Self::Sse
+                } else {
+                    Self::Ndjson
+                }
+            }
+            format => format,
+        }
+    }
+}
+
+impl FromStr for StreamFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "auto" => Ok(Self::Auto),
+            "sse" => Ok(Self::Sse),
+            "ndjson" => Ok(Self::Ndjson),
+            "chunk" => Ok(Self::Chunk),
+            _ => Err(format!(
+                "unknown stream format {s}, expected auto, sse, ndjson or chunk"
+            )),
+        }
+    }
+}
+
+impl fmt::Display for StreamFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let format = match self {
+            Self::Auto => "auto",
+            Self::Sse => "sse",
+            Self::Ndjson => "ndjson",
+            Self::Chunk => "chunk",
+        };
+        f.write_str(format)
+    }
+}
+
+/// Splits a body into lines as its chunks arrive, a line can span several chunks
+#[derive(Debug, Default)]
+pub struct LineSplitter {
+    pending: Vec<u8>,
+}
+
+impl LineSplitter {
+    /// Complete lines ending in `chunk`, without their `\n` or `\r\n`
+    pub fn push(&mut self, chunk: &[u8]) -> Vec<String> {
+        self.pending.extend_from_slice(chunk);
+        let mut lines = Vec::new();
+        while let Some(end) = self.pending.iter().position(|byte| *byte == b'\n') {
+            let line: Vec<u8> = self.pending.drain(..=end).collect();
+            lines.push(to_line(&line[..end]));
+        }
+        lines
+    }
+
+    /// The last line, when the body doesn't end with a newline
+    pub fn finish(&mut self) -> Option<String> {
+        if self.pending.is_empty() {
+            return None;
+        }
+        let line = std::mem::take(&mut self.pending);
+        Some(to_line(&line))
+    }
+}
+
+/// Decodes the chunks of a body as UTF-8, a character can span several chunks
+#[derive(Debug, Default)]
+pub struct Utf8Chunks {
+    pending: Vec<u8>,
+}
+
+impl Utf8Chunks {
+    /// Text of `chunk`, the bytes of a character it ends in the middle of being kept for
+    /// the next chunk
+    pub fn push(&mut self, chunk: &[u8]) -> String {
+        self.pending.extend_from_slice(chunk);
+        let complete = self.pending.len() - incomplete_tail(&self.pending);
+        let text: Vec<u8> = self.pending.drain(..complete).collect();
+        String::from_utf8_lossy(&text).into_owned()
+    }
+
+    /// The bytes left at the end of the body, not a complete character
+    pub fn finish(&mut self) -> Option<String> {
+        if self.pending.is_empty() {
+            return None;
+        }
+        let tail = std::mem::take(&mut self.pending);
+        Some(String::from_utf8_lossy(&tail).into_owned())
+    }
+}
+
+/// Length of the character `bytes` end in the middle of, if any
+fn incomplete_tail(bytes: &[u8]) -> usize {
+    // a character is at most 4 bytes, so an incomplete one starts in the last 3
+    for (len, byte) in bytes.iter().rev().take(3).enumerate() {
+        let len = len + 1;
+        let expected = match byte {
+            // continuation byte, the start is further back
+            0x80..=0xBF => continue,
+            0xC0..=0xDF => 2,
+            0xE0..=0xEF => 3,
+            0xF0..=0xF7 => 4,
+            _ => return 0,
+        };
+        return if len < expected { len } else { 0 };
+    }
+    0
+}
+
+fn to_line(bytes: &[u8]) -> String {
+    let bytes = bytes.strip_suffix(b"\r").unwrap_or(bytes);
+    String::from_utf8_lossy(bytes).into_owned()
+}
+
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct SseEvent {
+    pub event: Option<String>,
+    pub id: Option<String>,
+    pub data: String,
+}
+
+/// Server-Sent Events parser, fed with the lines of the stream.
+///
+/// The last event ID and reconnection time are kept across connections, so the
+/// stream can be resumed where it dropped.
+#[derive(Debug, Default)]
+pub struct SseParser {
+    data: Option<String>,
+    event: Option<String>,
+    pub last_event_id: Option<String>,
+    pub retry: Option<Duration>,
+}
+
+impl SseParser {
+    /// Returns the event dispatched by `line`, if any
+    pub fn feed(&mut self, line: &str) -> Option<SseEvent> {
+        if line.is_empty() {
+            let event = self.event.take();
+            return self.data.take().map(|data| SseEvent {
+                event,
+                id: self.last_event_id.clone(),
+                data,
+            });
+        }
+        let (field, value) = match line.split_once(':') {
+            // comments start with a colon
+            Some(("", _)) => return None,
+            Some((field, value)) => (field, value.strip_prefix(' ').unwrap_or(value)),
+            None => (line, ""),
+        };
+        match field {
+            "data" => match self.data {
+                Some(ref mut data) => {
+                    data.push('\n');
+                    data.push_str(value);
+                }
+                None => self.data = Some(value.to_string()),
+            },
+            "event" => self.event = Some(value.to_string()),
+            "id" if !value.contains('\0') => self.last_event_id = Some(value.to_string()),
+            "retry" => {
+                if let Ok(millis) = value.parse() {
+                    self.retry = Some(Duration::from_millis(millis));
+                }
+            }
+            _ => {}
+        }
+        None
+    }
+
+    /// Drops the event being read when the connection dropped in the middle of it
+    pub fn reset(&mut self) {
+        self.data = None;
+        self.event = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_lines_across_chunks() {
+        //given
+        let mut lines = LineSplitter::default();
+
+        //when
+        let first = lines.push(b"{\"a\":1}\n{\"b\"");
+        let second = lines.push(b":2}\r\n\n{\"c\":3}");
+        let last = lines.finish();
+
+        //then
+        assert_eq!(first, vec!["{\"a\":1}"]);
+        assert_eq!(second, vec!["{\"b\":2}", ""]);
+        assert_eq!(last.as_deref(), Some("{\"c\":3}"));
+        assert_eq!(lines.finish(), None);
+    }
+
+    #[test]
+    fn test_decode_characters_across_chunks() {
+        //given
+        let mut chunks = Utf8Chunks::default();
+        let text = "prix: 5€, 🦀".as_bytes();
+
+        //when
+        let first = chunks.push(&text[..8]);
+        let second = chunks.push(&text[8..14]);
+        let third = chunks.push(&text[14..]);
+        let incomplete = chunks.push(&text[12..14]);
+
+        //then
+        assert_eq!(first, "prix: 5");
+        assert_eq!(second, "€, ");
+        assert_eq!(third, "🦀");
+        assert_eq!(incomplete, "");
+        assert_eq!(chunks.finish().as_deref(), Some("\u{FFFD}"));
+        assert_eq!(chunks.finish(), None);
+    }
+
+    #[test]
+    fn test_parse_sse_events() {
+        //given
+        let mut sse = SseParser::default();
+        let stream = ": keep-alive\n\nretry: 3000\nevent: update\nid: 7\ndata: first\ndata:second\n\ndata: {\"n\":8}\n\n";
+
+        //when
+        let events: Vec<SseEvent> = stream
+            .split('\n')
+            .filter_map(|line| sse.feed(line))
+            .collect();
+
+        //then
+        assert_eq!(
+            events,
+            vec![
+                SseEvent {
+                    event: Some("update".to_string()),
+                    id: Some("7".to_string()),
+                    data: "first\nsecond".to_string(),
+                },
+                SseEvent {
+                    event: None,
+                    id: Some("7".to_string()),
+                    data: "{\"n\":8}".to_string(),
+                },
+            ]
+        );
+        assert_eq!(sse.last_event_id.as_deref(), Some("7"));
+        assert_eq!(sse.retry, Some(Duration::from_secs(3)));
+    }
+
+    #[test]
+    fn test_resolve_auto_format() {
+        //given
+        let mut sse_headers = HeaderMap::new();
+        sse_headers.insert(
+            CONTENT_TYPE,
+            "text/event-stream; charset=utf-8".parse().unwrap(),
+        );
+
+        //then
+        assert_eq!(StreamFormat::Auto.resolve(&sse_headers), StreamFormat::Sse);
+        assert_eq!(
+            StreamFormat::Auto.resolve(&HeaderMap::new()),
+            StreamFormat::Ndjson
+        );
+        assert_eq!(
+            StreamFormat::Chunk.resolve(&sse_headers),
+            StreamFormat::Chunk
+        );
+    }
+}
@@ -0,0 +1,419 @@
+//! Templated requests: `{{ variable }}` placeholders in the endpoint, headers and body,
+//! rendered before every poll
+
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::str::FromStr;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use fluvio_connectors_common::config::SecretString;
+use fluvio_connectors_common::fs::write_atomic;
+use reqwest::Url;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::error::{Error, Result};
+use crate::HttpOpt;
+
+const OPEN: &str = "{{";
+const CLOSE: &str = "}}";
+/// Put before `{{` for a literal `{{`
+const ESCAPE: &str = "\\";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Variable {
+    /// Time of the poll, in RFC 3339
+    Now,
+    /// Time of the poll, in seconds since UNIX epoch
+    NowUnix,
+    /// Time of the poll, in milliseconds since UNIX epoch
+    NowUnixMs,
+    /// Time the last successful poll started, in RFC 3339
+    LastPoll,
+    /// Time the last successful poll started, in seconds since UNIX epoch
+    LastPollUnix,
+    /// Time the last successful poll started, in milliseconds since UNIX epoch
+    LastPollUnixMs,
+    /// Value at a JSON pointer of the last response of the last successful poll
+    Response(String),
+    /// Secret of the connector config, or environment variable
+    Env(String),
+}
+
+impl FromStr for Variable {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "now" => Ok(Self::Now),
+            "now_unix" => Ok(Self::NowUnix),
+            "now_unix_ms" => Ok(Self::NowUnixMs),
+            "last_poll" => Ok(Self::LastPoll),
+            "last_poll_unix" => Ok(Self::LastPollUnix),
+            "last_poll_unix_ms" => Ok(Self::LastPollUnixMs),
+            _ => match s.split_once(':') {
+                Some(("response", pointer)) => Ok(Self::Response(pointer.to_string())),
+                Some(("env", name)) => Ok(Self::Env(name.to_string())),
+                _ => Err(format!("unknown template variable `{s}`")),
+            },
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Part {
+    Text(String),
+    Variable {
+        variable: Variable,
+        /// Used while the variable has no value, e.g. `last_poll` on the first poll
+        default: Option<String>,
+    },
+}
+
+/// What the variables of the next poll are rendered from, kept in a local JSON file if
+/// a path is given
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct TemplateState {
+    #[serde(default, with = "humantime_serde")]
+    pub last_poll: Option<SystemTime>,
+    #[serde(default)]
+    pub last_response: Option<Value>,
+}
+
+impl TemplateState {
+    /// State saved in `path`, or the initial one if there is no such file
+    pub fn load(path: Option<&Path>) -> std::io::Result<Self> {
+        let path = match path {
+            Some(path) => path,
+            None => return Ok(Self::default()),
+        };
+        match std::fs::read(path) {
+            Ok(contents) => Ok(serde_json::from_slice(&contents)?),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+                tracing::info!(
+                    "template state file {} not found, starting from the defaults",
+                    path.display()
+                );
+                Ok(Self::default())
+            }
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Saves the state in `path`
+    pub async fn save(&self, path: &Path) -> std::io::Result<()> {
+        write_atomic(path, serde_json::to_vec(self)?).await
+    }
+}
+
+/// What the variables are rendered from
+#[derive(Debug, Clone)]
+pub struct TemplateContext<'a> {
+    pub now: SystemTime,
+    pub last_poll: Option<SystemTime>,
+    pub last_response: Option<Value>,
+    /// Secrets of the connector config, read before the environment variables
+    pub secrets: &'a BTreeMap<String, SecretString>,
+}
+
+impl TemplateContext<'_> {
+    fn value(&self, variable: &Variable) -> Option<String> {
+        match variable {
+            Variable::Now => Some(rfc3339(self.now)),
+            Variable::NowUnix => Some((unix_millis(self.now) / 1000).to_string()),
+            Variable::NowUnixMs => Some(unix_millis(self.now).to_string()),
+            Variable::LastPoll => self.last_poll.map(rfc3339),
+            Variable::LastPollUnix => self.last_poll.map(|t| (unix_millis(t) / 1000).to_string()),
+            Variable::LastPollUnixMs => self.last_poll.map(|t| unix_millis(t).to_string()),
+            Variable::Response(pointer) => match self.last_response.as_ref()?.pointer(pointer)? {
+                Value::Null => None,
+                Value::String(value) => Some(value.clone()),
+                value => Some(value.to_string()),
+            },
+            Variable::Env(name) => match self.secrets.get(name) {
+                Some(secret) => Some(secret[..].to_string()),
+                None => std::env::var(name).ok(),
+            },
+        }
+    }
+}
+
+fn rfc3339(time: SystemTime) -> String {
+    humantime::format_rfc3339_seconds(time).to_string()
+}
+
+fn unix_millis(time: SystemTime) -> u128 {
+    time.duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis()
+}
+
+/// A string with `{{ variable }}` or `{{ variable | default }}` placeholders, `\{{` being
+/// a literal `{{`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Template {
+    parts: Vec<Part>,
+}
+
+impl FromStr for Template {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = Vec::new();
+        let mut text = String::new();
+        let mut rest = s;
+        while let Some(start) = rest.find(OPEN) {
+            let placeholder = &rest[start + OPEN.len()..];
+            if let Some(escaped) = rest[..start].strip_suffix(ESCAPE) {
+                text.push_str(escaped);
+                text.push_str(OPEN);
+                rest = placeholder;
+                continue;
+            }
+            text.push_str(&rest[..start]);
+            if !text.is_empty() {
+                parts.push(Part::Text(std::mem::take(&mut text)));
+            }
+            let end = placeholder
+                .find(CLOSE)
+                .ok_or_else(|| format!("unclosed placeholder in `{s}`"))?;
+            let (variable, default) = match placeholder[..end].split_once('|') {
+                Some((variable, default)) => (variable, Some(default.trim().to_string())),
+                None => (&placeholder[..end], None),
+            };
+            parts.push(Part::Variable {
+                variable: variable.trim().parse()?,
+                default,
+            });
+            rest = &placeholder[end + CLOSE.len()..];
+        }
+        text.push_str(rest);
+        if !text.is_empty() {
+            parts.push(Part::Text(text));
+        }
+        Ok(Self { parts })
+    }
+}
+
+impl Template {
+    /// The text of the template if it has no placeholders
+    pub fn as_static(&self) -> Option<&str> {
+        match self.parts[..] {
+            [] => Some(""),
+            [Part::Text(ref text)] => Some(text.as_str()),
+            _ => None,
+        }
+    }
+
+    /// Renders the template, passing the value of every variable through `escape`
+    pub fn render<E>(&self, context: &TemplateContext, escape: E) -> Result<String, String>
+    where
+        E: Fn(&str) -> String,
+    {
+        let mut rendered = String::new();
+        for part in &self.parts {
+            match part {
+                Part::Text(text) => rendered.push_str(text),
+                Part::Variable { variable, default } => {
+                    let value = context
+                        .value(variable)
+                        .or_else(|| default.clone())
+                        .ok_or_else(|| format!("no value for {variable:?} and no default"))?;
+                    rendered.push_str(&escape(&value));
+                }
+            }
+        }
+        Ok(rendered)
+    }
+}
+
+/// Percent-encodes everything but the unreserved characters of RFC 3986, so values
+/// can be used in a path segment or a query parameter
+fn url_escape(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for byte in value.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                escaped.push(byte as char)
+            }
+            _ => escaped.push_str(&format!("%{byte:02X}")),
+        }
+    }
+    escaped
+}
+
+/// Endpoint, headers and body of the request sent on every poll
+#[derive(Debug)]
+pub struct RequestTemplate {
+    endpoint: Template,
+    headers: Vec<Template>,
+    body: Option<Template>,
+}
+
+/// Request of a poll, once its templates are rendered
+#[derive(Debug, Clone)]
+pub struct RenderedRequest {
+    pub url: Url,
+    pub headers: Vec<(String, String)>,
+    pub body: Option<String>,
+}
+
+impl RequestTemplate {
+    pub fn new(opts: &HttpOpt) -> Result<Self> {
+        let parse = |template: &str| template.parse::<Template>().map_err(Error::Template);
+        let endpoint = parse(opts.endpoint.as_deref().unwrap_or_default())?;
+        if let Some(url) = endpoint.as_static() {
+            Url::parse(url).map_err(|_| Error::Endpoint(url.to_string()))?;
+        }
+        Ok(Self {
+            endpoint,
+            headers: opts
+                .headers
+                .iter()
+                .map(|header| parse(header))
+                .collect::<Result<_>>()?,
+            body: opts.body.as_deref().map(parse).transpose()?,
+        })
+    }
+
+    pub fn render(&self, context: &TemplateContext) -> Result<RenderedRequest> {
+        let endpoint = self
+            .endpoint
+            .render(context, url_escape)
+            .map_err(Error::Template)?;
+        let url = Url::parse(&endpoint).map_err(|_| Error::Endpoint(endpoint.clone()))?;
+
+        let mut headers = Vec::with_capacity(self.headers.len());
+        for header in &self.headers {
+            let header = header
+                .render(context, str::to_string)
+                .map_err(Error::Template)?;
+            if let Some((key, value)) = header.split_once(':') {
+                headers.push((key.to_string(), value.to_string()));
+            }
+        }
+
+        let body = match self.body {
+            Some(ref body) => Some(
+                body.render(context, str::to_string)
+                    .map_err(Error::Template)?,
+            ),
+            None => None,
+        };
+        Ok(RenderedRequest { url, headers, body })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    static NO_SECRETS: BTreeMap<String, SecretString> = BTreeMap::new();
+
+    fn context() -> TemplateContext<'static> {
+        TemplateContext {
+            now: UNIX_EPOCH + Duration::from_secs(1_672_531_200),
+            last_poll: Some(UNIX_EPOCH + Duration::from_secs(1_672_531_140)),
+            last_response: Some(serde_json::json!({"cursor": "a&b", "count": 3})),
+            secrets: &NO_SECRETS,
+        }
+    }
+
+    #[test]
+    fn test_render_template() {
+        //given
+        let template: Template =
+            "/events?since={{ last_poll }}&until={{now_unix}}&cursor={{ response:/cursor }}"
+                .parse()
+                .unwrap();
+
+        //when
+        let url = template.render(&context(), url_escape).unwrap();
+        let text = template.render(&context(), str::to_string).unwrap();
+
+        //then
+        assert_eq!(
+            url,
+            "/events?since=2022-12-31T23%3A59%3A00Z&until=1672531200&cursor=a%26b"
+        );
+        assert_eq!(
+            text,
+            "/events?since=2022-12-31T23:59:00Z&until=1672531200&cursor=a&b"
+        );
+        assert_eq!(template.as_static(), None);
+        assert_eq!(
+            "{\"a\": 1}".parse::<Template>().unwrap().as_static(),
+            Some("{\"a\": 1}")
+        );
+    }
+
+    #[test]
+    fn test_render_escaped_placeholders() {
+        //given
+        let template: Template = r#"{"query": "\{{ user(id: {{ env:GRAPHQL_USER_ID | 1 }}) }}"}"#
+            .parse()
+            .unwrap();
+
+        //when
+        let rendered = template.render(&context(), str::to_string);
+
+        //then
+        assert_eq!(rendered.unwrap(), r#"{"query": "{{ user(id: 1) }}"}"#);
+        assert_eq!(
+            r"/\{{ now }}".parse::<Template>().unwrap().as_static(),
+            Some("/{{ now }}")
+        );
+    }
+
+    #[test]
+    fn test_render_defaults() {
+        //given
+        let template: Template =
+            r#"{"since": "{{ last_poll | 2023-01-01T00:00:00Z }}", "page": {{response:/next}}}"#
+                .parse()
+                .unwrap();
+        let first_poll = TemplateContext {
+            last_poll: None,
+            last_response: None,
+            ..context()
+        };
+
+        //then
+        assert!(template.render(&first_poll, str::to_string).is_err());
+        let template: Template = r#"{"since": "{{ last_poll | 2023-01-01T00:00:00Z }}", "page": {{response:/count | 1}}}"#
+            .parse()
+            .unwrap();
+        assert_eq!(
+            template.render(&first_poll, str::to_string).unwrap(),
+            r#"{"since": "2023-01-01T00:00:00Z", "page": 1}"#
+        );
+        assert_eq!(
+            template.render(&context(), str::to_string).unwrap(),
+            r#"{"since": "2022-12-31T23:59:00Z", "page": 3}"#
+        );
+        assert!("{{ yesterday }}".parse::<Template>().is_err());
+        assert!("{{ now ".parse::<Template>().is_err());
+    }
+
+    #[test]
+    fn test_render_secrets() {
+        //given
+        let template: Template = "Token {{ env:EVENTS_API_TOKEN }}".parse().unwrap();
+        let secrets = BTreeMap::from([(
+            "EVENTS_API_TOKEN".to_string(),
+            SecretString::from("s3cr3t".to_string()),
+        )]);
+        let context = TemplateContext {
+            secrets: &secrets,
+            ..context()
+        };
+
+        //when
+        let rendered = template.render(&context, str::to_string);
+
+        //then
+        assert_eq!(rendered.unwrap(), "Token s3cr3t");
+    }
+}
@@ -0,0 +1,445 @@
+//! Webhook mode: POST requests received on a port are produced instead of polling
+
+use std::convert::Infallible;
+use std::fmt;
+use std::net::SocketAddr;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use base64::Engine;
+use clap::Parser;
+use fluvio_connectors_common::de;
+use fluvio_connectors_common::fluvio::{RecordKey, TopicProducer};
+use fluvio_connectors_common::health::ConnectorHealth;
+use fluvio_connectors_common::metrics::{ConnectorMetrics, FORMAT_ERROR, UPSTREAM_ERROR};
+use fluvio_connectors_common::shutdown::ShutdownSignal;
+use hmac::{Hmac, Mac};
+use humantime::parse_duration;
+use hyper::body::HttpBody;
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{Body, Method, Request, Response, Server, StatusCode};
+use schemars::JsonSchema;
+use serde::Deserialize;
+use sha2::Sha256;
+
+use crate::error::{Error, Result};
+use crate::formatter::HttpResponseRecord;
+use crate::HttpOpt;
+
+/// Upper bound of the body of a webhook
+const MAX_BODY_SIZE: usize = 10 * 1024 * 1024;
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// How webhooks are signed, all with HMAC-SHA256
+#[derive(Debug, Clone, Copy, PartialEq, Eq, JsonSchema)]
+pub enum WebhookSignature {
+    /// `sha256=<hex>` of the body, as sent by GitHub
+    Github,
+    /// `t=<timestamp>,v1=<hex>` of the timestamp and body, as sent by Stripe
+    Stripe,
+    /// Hex of the body
+    Hex,
+    /// Base64 of the body
+    Base64,
+}
+
+impl FromStr for WebhookSignature {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "github" => Ok(Self::Github),
+            "stripe" => Ok(Self::Stripe),
+            "hex" => Ok(Self::Hex),
+            "base64" => Ok(Self::Base64),
+            _ => Err(format!(
+                "unknown webhook signature {s}, expected github, stripe, hex or base64"
+            )),
+        }
+    }
+}
+
+impl fmt::Display for WebhookSignature {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let signature = match self {
+            Self::Github => "github",
+            Self::Stripe => "stripe",
+            Self::Hex => "hex",
+            Self::Base64 => "base64",
+        };
+        f.write_str(signature)
+    }
+}
+
+impl WebhookSignature {
+    fn default_header(&self) -> &'static str {
+        match self {
+            Self::Github => "X-Hub-Signature-256",
+            Self::Stripe => "Stripe-Signature",
+            Self::Hex | Self::Base64 => "X-Signature",
+        }
+    }
+
+    /// Whether `signature`, the value of the signature header, signs `body` with
+    /// `secret`. Stripe signatures older than `tolerance` are rejected.
+    fn verify(
+        &self,
+        secret: &str,
+        signature: &str,
+        body: &[u8],
+        now: SystemTime,
+        tolerance: Duration,
+    ) -> bool {
+        let signature = signature.trim();
+        let expected = match self {
+            Self::Github => signature
+                .strip_prefix("sha256=")
+                .and_then(|hex| hex::decode(hex).ok()),
+            Self::Hex => hex::decode(signature).ok(),
+            Self::Base64 => base64::engine::general_purpose::STANDARD
+                .decode(signature)
+                .ok(),
+            Self::Stripe => return verify_stripe(secret, signature, body, now, tolerance),
+        };
+        match expected {
+            Some(expected) => hmac(secret, &[body]).verify_slice(&expected).is_ok(),
+            None => false,
+        }
+    }
+}
+
+fn hmac(secret: &str, parts: &[&[u8]]) -> HmacSha256 {
+    let mut mac =
+        HmacSha256::new_from_slice(secret.as_bytes()).expect("HMAC takes keys of any size");
+    for part in parts {
+        mac.update(part);
+    }
+    mac
+}
+
+fn verify_stripe(
+    secret: &str,
+    signature: &str,
+    body: &[u8],
+    now: SystemTime,
+    tolerance: Duration,
+) -> bool {
+    let mut timestamp = None;
+    let mut signatures = Vec::new();
+    for (key, value) in signature.split(',').filter_map(|part| part.split_once('=')) {
+        match key.trim() {
+            "t" => timestamp = Some(value.trim()),
+            "v1" => signatures.extend(hex::decode(value.trim()).ok()),
+            _ => {}
+        }
+    }
+    let timestamp = match timestamp {
+        Some(timestamp) => timestamp,
+        None => return false,
+    };
+    let signed_at = match timestamp.parse() {
+        Ok(seconds) => UNIX_EPOCH + Duration::from_secs(seconds),
+        Err(_) => return false,
+    };
+    let age = match now.duration_since(signed_at) {
+        Ok(age) => age,
+        Err(err) => err.duration(),
+    };
+    if age > tolerance {
+        return false;
+    }
+    signatures.iter().any(|signature| {
+        hmac(secret, &[timestamp.as_bytes(), b".", body])
+            .verify_slice(signature)
+            .is_ok()
+    })
+}
+
+#[derive(Parser, Debug, JsonSchema, Clone, Deserialize)]
+pub struct WebhookOpt {
+    /// Address webhooks are received on, e.g. `0.0.0.0:8080`. POST requests to
+    /// `webhook_paths` are produced instead of polling `endpoint`
+    #[clap(long)]
+    pub webhook_addr: Option<SocketAddr>,
+
+    /// Paths webhooks are received on, requests to other paths are answered 404
+    #[clap(long = "webhook-path", alias = "webhook-paths", default_value = "/")]
+    pub webhook_paths: Vec<String>,
+
+    /// Secret webhooks are signed with. Unsigned webhooks are accepted if not set
+    #[clap(long, env = "FLUVIO_HTTP_WEBHOOK_SECRET", hide_env_values = true)]
+    pub webhook_secret: Option<String>,
+
+    /// How webhooks are signed: github | stripe | hex | base64
+    #[clap(long, default_value = "github")]
+    #[serde(deserialize_with = "de::from_str")]
+    pub webhook_signature: WebhookSignature,
+
+    /// Header holding the signature. Defaults to `X-Hub-Signature-256` for github,
+    /// `Stripe-Signature` for stripe and `X-Signature` otherwise
+    #[clap(long)]
+    pub webhook_signature_header: Option<String>,
+
+    /// Maximum age of the timestamp of stripe signatures
+    /// Ex: '5m', '1h'
+    #[clap(long, parse(try_from_str = parse_duration), default_value = "5m")]
+    #[serde(deserialize_with = "de::duration")]
+    pub webhook_tolerance: Duration,
+
+    /// Status answered once the record of a webhook is acknowledged by Fluvio
+    #[clap(long, default_value = "200")]
+    pub webhook_status: u16,
+}
+
+/// Serves `webhook_addr`, producing one record per received webhook
+pub struct WebhookSource {
+    opts: HttpOpt,
+    addr: SocketAddr,
+    status: StatusCode,
+    producer: TopicProducer,
+    metrics: Arc<ConnectorMetrics>,
+    health: Arc<ConnectorHealth>,
+    shutdown: ShutdownSignal,
+}
+
+impl WebhookSource {
+    pub fn new(
+        opts: HttpOpt,
+        producer: TopicProducer,
+        metrics: Arc<ConnectorMetrics>,
+        health: Arc<ConnectorHealth>,
+        shutdown: ShutdownSignal,
+    ) -> Result<Self> {
+        let webhook = &opts.webhook;
+        let addr = webhook
+            .webhook_addr
+            .ok_or_else(|| Error::Webhook("webhook mode requires webhook_addr".to_string()))?;
+        let status = StatusCode::from_u16(webhook.webhook_status)
+            .map_err(|_| Error::Webhook(format!("invalid status {}", webhook.webhook_status)))?;
+        // the output options are checked once, instead of on every webhook
+        HttpResponseRecord::default()
+            .configure_output(&opts.output_type, &opts.output_parts)
+            .map_err(Error::Record)?;
+        Ok(Self {
+            opts,
+            addr,
+            status,
+            producer,
+            metrics,
+            health,
+            shutdown,
+        })
+    }
+
+    /// Serves webhooks until shutdown, then flushes the producer
+    pub async fn run(self) -> Result<()> {
+        let source = Arc::new(self);
+        let service = {
+            let source = source.clone();
+            make_service_fn(move |_conn| {
+                let source = source.clone();
+                async move {
+                    Ok::<_, Infallible>(service_fn(move |req| {
+                        let source = source.clone();
+                        async move { Ok::<_, Infallible>(source.receive(req).await) }
+                    }))
+                }
+            })
+        };
+
+        let mut shutdown = source.shutdown.clone();
+        let server = Server::try_bind(&source.addr)
+            .map_err(|e| Error::Webhook(format!("can't listen on {}: {e}", source.addr)))?
+            .serve(service)
+            .with_graceful_shutdown(async move { shutdown.requested().await });
+        tracing::info!(addr = %source.addr, paths = ?source.opts.webhook.webhook_paths, "Receiving webhooks");
+        source.health.set_upstream_connected(true);
+        if let Err(err) = server.await {
+            tracing::error!(%err, "Webhook server failed");
+        }
+
+        tracing::info!("Shutting down, flushing producer");
+        source.producer.flush().await.map_err(Error::Fluvio)
+    }
+
+    async fn receive(&self, req: Request<Body>) -> Response<Body> {
+        let (status, reason) = match self.handle(req).await {
+            Ok(()) => (self.status, None),
+            Err(rejected) => rejected,
+        };
+        if let Some(reason) = reason {
+            tracing::warn!(%status, "Rejecting webhook: {reason}");
+        }
+        let mut response = Response::new(Body::empty());
+        *response.status_mut() = status;
+        response
+    }
+
+    /// Produces the webhook, returning the status and reason it is rejected with
+    async fn handle(&self, req: Request<Body>) -> Result<(), (StatusCode, Option<String>)> {
+        let webhook = &self.opts.webhook;
+        if !webhook
+            .webhook_paths
+            .iter()
+            .any(|path| path == req.uri().path())
+        {
+            return Err((StatusCode::NOT_FOUND, None));
+        }
+        if req.method() != Method::POST {
+            return Err((StatusCode::METHOD_NOT_ALLOWED, None));
+        }
+
+        let (parts, mut body_stream) = req.into_parts();
+        let mut body = Vec::new();
+        while let Some(chunk) = body_stream.data().await {
+            let chunk = chunk.map_err(|e| {
+                self.metrics.error(UPSTREAM_ERROR);
+                (StatusCode::BAD_REQUEST, Some(e.to_string()))
+            })?;
+            if body.len() + chunk.len() > MAX_BODY_SIZE {
+                return Err((StatusCode::PAYLOAD_TOO_LARGE, None));
+            }
+            body.extend_from_slice(&chunk);
+        }
+        self.metrics.record_read(body.len());
+
+        if let Some(ref secret) = webhook.webhook_secret {
+            let header = webhook
+                .webhook_signature_header
+                .as_deref()
+                .unwrap_or_else(|| webhook.webhook_signature.default_header());
+            let signature = parts
+                .headers
+                .get(header)
+                .and_then(|signature| signature.to_str().ok())
+                .unwrap_or_default();
+            let verified = webhook.webhook_signature.verify(
+                secret,
+                signature,
+                &body,
+                SystemTime::now(),
+                webhook.webhook_tolerance,
+            );
+            if !verified {
+                self.metrics.record_dropped();
+                return Err((
+                    StatusCode::UNAUTHORIZED,
+                    Some(format!("invalid signature in {header}")),
+                ));
+            }
+        }
+
+        let mut formatter = HttpResponseRecord::try_from(&parts)
+            .and_then(|mut formatter| {
+                formatter.configure_output(&self.opts.output_type, &self.opts.output_parts)?;
+                Ok(formatter)
+            })
+            .map_err(|e| {
+                self.metrics.error(FORMAT_ERROR);
+                (StatusCode::INTERNAL_SERVER_ERROR, Some(e.to_string()))
+            })?;
+        let encoding = self.opts.body_encoding.resolve(&parts.headers);
+        let record_out = formatter.record_bytes(&body, encoding);
+        self.produce(record_out)
+            .await
+            .map_err(|e| (StatusCode::SERVICE_UNAVAILABLE, Some(format!("{e:?}"))))
+    }
+
+    /// Sends the record and waits for it to be acknowledged
+    async fn produce(&self, record_out: Vec<u8>) -> Result<()> {
+        tracing::debug!(record_out = %String::from_utf8_lossy(&record_out), "Producing");
+
+        let record_len = record_out.len();
+        let started = Instant::now();
+        self.producer
+            .send(RecordKey::NULL, record_out)
+            .await
+            .map_err(Error::Fluvio)?;
+        self.producer.flush().await.map_err(Error::Fluvio)?;
+        self.metrics.record_written(record_len, started.elapsed());
+        self.health.record_succeeded();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_verify_signatures() {
+        //given
+        let body = br#"{"action":"opened"}"#;
+        let hex = hex::encode(hmac("secret", &[body]).finalize().into_bytes());
+        let base64 = base64::engine::general_purpose::STANDARD
+            .encode(hmac("secret", &[body]).finalize().into_bytes());
+        let now = SystemTime::now();
+        let tolerance = Duration::from_secs(300);
+
+        //then
+        let github = WebhookSignature::Github;
+        assert!(github.verify("secret", &format!("sha256={hex}"), body, now, tolerance));
+        assert!(!github.verify("other", &format!("sha256={hex}"), body, now, tolerance));
+        assert!(!github.verify("secret", &hex, body, now, tolerance));
+        assert!(!github.verify("secret", "", body, now, tolerance));
+        assert!(WebhookSignature::Hex.verify("secret", &hex, body, now, tolerance));
+        assert!(!WebhookSignature::Hex.verify("secret", &hex, b"{}", now, tolerance));
+        assert!(WebhookSignature::Base64.verify("secret", &base64, body, now, tolerance));
+    }
+
+    #[test]
+    fn test_verify_stripe_signature() {
+        //given
+        let body = br#"{"type":"charge.succeeded"}"#;
+        let signed_at = UNIX_EPOCH + Duration::from_secs(1_672_531_200);
+        let signature = hex::encode(
+            hmac("whsec", &[b"1672531200", b".", body])
+                .finalize()
+                .into_bytes(),
+        );
+        let header = format!("t=1672531200,v1=00ff,v1={signature},v0=abc");
+        let tolerance = Duration::from_secs(300);
+        let stripe = WebhookSignature::Stripe;
+
+        //then
+        let soon_after = signed_at + Duration::from_secs(60);
+        assert!(stripe.verify("whsec", &header, body, soon_after, tolerance));
+        let too_late = signed_at + Duration::from_secs(600);
+        assert!(!stripe.verify("whsec", &header, body, too_late, tolerance));
+        assert!(!stripe.verify(
+            "whsec",
+            &format!("v1={signature}"),
+            body,
+            soon_after,
+            tolerance
+        ));
+        assert!(!stripe.verify("other", &header, body, soon_after, tolerance));
+    }
+
+    #[test]
+    fn test_webhook_only_opts() {
+        //given
+        let args = [
+            "http-source",
+            "--webhook-addr=0.0.0.0:8080",
+            "--webhook-path=/github",
+            "--fluvio-topic=webhooks",
+        ];
+
+        //when
+        let opts = HttpOpt::try_parse_from(args).unwrap();
+        let without_webhook = HttpOpt::try_parse_from(["http-source", "--fluvio-topic=webhooks"]);
+
+        //then
+        assert_eq!(opts.endpoint, None);
+        assert_eq!(
+            opts.webhook.webhook_addr,
+            Some("0.0.0.0:8080".parse().unwrap())
+        );
+        assert_eq!(opts.webhook.webhook_paths, ["/github"]);
+        assert!(opts.validate().is_ok());
+        assert!(without_webhook.is_err());
+    }
+}
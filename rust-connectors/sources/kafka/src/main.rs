@@ -1,14 +1,15 @@
 use std::sync::Arc;
+use std::time::Instant;
 
 use clap::Parser;
 use fluvio_connectors_common::metrics::ConnectorMetrics;
-use fluvio_connectors_common::monitoring::init_monitoring;
-use fluvio_connectors_common::opt::CommonConnectorOpt;
+use fluvio_connectors_common::opt::{parse_connector_opts, CommonConnectorOpt};
 use fluvio_connectors_common::{common_initialize, git_hash_version};
 use fluvio_future::tracing::info;
 use kafka::consumer::{Consumer, FetchOffset, GroupOffsetStorage};
 use schemars::schema_for;
 use schemars::JsonSchema;
+use serde::Deserialize;
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
@@ -24,7 +25,7 @@ async fn main() -> anyhow::Result<()> {
         println!("{}", serde_json::to_string_pretty(&schema).unwrap());
         return Ok(());
     }
-    let opts: KafkaOpt = KafkaOpt::from_args();
+    let opts: KafkaOpt = parse_connector_opts();
     opts.common.enable_logging();
     info!(
         connector_version = env!("CARGO_PKG_VERSION"),
@@ -36,7 +37,7 @@ async fn main() -> anyhow::Result<()> {
     Ok(())
 }
 
-#[derive(Parser, Debug, JsonSchema, Clone)]
+#[derive(Parser, Debug, JsonSchema, Clone, Deserialize)]
 pub struct KafkaOpt {
     #[clap(long, env = "KAFKA_URL", hide_env_values = true)]
     pub kafka_url: String,
@@ -49,21 +50,26 @@ pub struct KafkaOpt {
 
     #[clap(long, default_value = "0")]
     #[schemars(skip)]
+    #[serde(deserialize_with = "fluvio_connectors_common::de::from_str")]
     pub kafka_partition: i32,
 
     #[clap(flatten)]
     #[schemars(flatten)]
+    #[serde(flatten)]
     pub common: CommonConnectorOpt,
 }
 
 impl KafkaOpt {
     pub async fn execute(&self) -> anyhow::Result<()> {
+        let shutdown = self.common.install_shutdown_handler()?;
+        let health = self.common.health_common.create_health();
         let producer = self.common.create_producer("kafka").await?;
 
         let metrics = Arc::new(ConnectorMetrics::new(producer.metrics()));
-        init_monitoring(metrics);
+        self.common.init_monitoring(metrics.clone());
 
         info!("Connected to fluvio!");
+        health.set_downstream_connected(true);
         let kafka_topic = self
             .kafka_topic
             .as_ref()
@@ -81,15 +87,23 @@ impl KafkaOpt {
             .create()?;
 
         info!("Connected to kafka!");
-        loop {
+        health.set_upstream_connected(true);
+        while !shutdown.is_requested() {
             for ms in consumer.poll().unwrap().iter() {
                 for m in ms.messages() {
                     info!("Sending {:?} to fluvio", m);
+                    metrics.record_read(m.value.len());
+                    let started = Instant::now();
                     producer.send(m.key, m.value).await?;
+                    metrics.record_written(m.value.len(), started.elapsed());
+                    health.record_succeeded();
                 }
                 consumer.consume_messageset(ms)?;
             }
             consumer.commit_consumed()?;
         }
+        info!("Shutting down, flushing producer");
+        producer.flush().await?;
+        Ok(())
     }
 }
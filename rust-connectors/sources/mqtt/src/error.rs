@@ -29,8 +29,12 @@ pub enum MqttConnectorError {
     ParseError(#[from] ParseError),
     #[error("Anyhow Error: `{0:#?}`.")]
     Anyhow(#[from] AnyhowError),
+    #[error("IO Error: `{0}`.")]
+    Io(#[from] std::io::Error),
     #[error("Internal Channel Closed")]
     ChannelClosed,
+    #[error("Invalid configuration: {0}")]
+    Config(String),
 }
 
 impl From<MqttError> for MqttConnectorError {
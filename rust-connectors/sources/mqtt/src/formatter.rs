@@ -54,6 +54,7 @@ mod tests {
         let event = MqttEvent {
             mqtt_topic: "topic".to_string(),
             payload: b"hello world".to_vec(),
+            ack: None,
         };
 
         //when
@@ -73,6 +74,7 @@ mod tests {
         let event = MqttEvent {
             mqtt_topic: "topic".to_string(),
             payload: b"{\"key\":\"value\"}".to_vec(),
+            ack: None,
         };
 
         //when
@@ -97,6 +99,7 @@ mod tests {
         let event = MqttEvent {
             mqtt_topic: "topic".to_string(),
             payload: b"not json".to_vec(),
+            ack: None,
         };
 
         //when
@@ -1,23 +1,28 @@
-use async_std::channel::{self, Receiver, Sender};
+use async_std::channel::{self, Receiver, Sender, TrySendError};
 use async_std::task::spawn;
-use fluvio_connectors_common::fluvio::{RecordKey, TopicProducer};
-use fluvio_connectors_common::metrics::ConnectorMetrics;
-use fluvio_connectors_common::monitoring::init_monitoring;
+use fluvio_connectors_common::health::ConnectorHealth;
+use fluvio_connectors_common::metrics::{ConnectorMetrics, FORMAT_ERROR, UPSTREAM_ERROR};
+use fluvio_connectors_common::opt::parse_connector_opts;
+use fluvio_connectors_common::retry::RetryPolicy;
+use fluvio_connectors_common::shutdown::ShutdownSignal;
 use fluvio_connectors_common::{common_initialize, git_hash_version};
 
 mod error;
 mod formatter;
 mod opt;
+mod subscription;
 
+use async_trait::async_trait;
 use error::MqttConnectorError;
 use formatter::Formatter;
 use rumqttc::EventLoop;
 use tracing::log::warn;
 
 use crate::opt::{ConnectorDirection, MqttOpts};
-use clap::Parser;
+use crate::subscription::{Output, Router};
 use fluvio_future::tracing::{debug, error, info};
-use rumqttc::{v4::Packet, AsyncClient, Event, MqttOptions, QoS, Transport};
+use rumqttc::v4::{Packet, Publish, SubscribeFilter};
+use rumqttc::{AsyncClient, Event, MqttOptions, Outgoing, QoS, Transport};
 use rustls::ClientConfig;
 use schemars::schema_for;
 use serde::Deserialize;
@@ -30,67 +35,178 @@ use url::Url;
 
 const CHANNEL_BUFFER_SIZE: usize = 10000;
 const MIN_LOG_WARN_TIME: Duration = Duration::from_secs(5 * 60);
+/// Messages acknowledged at most in one go, in lossless mode
+const MAX_PENDING_ACKS: usize = 1000;
 
+/// Acknowledges the messages once produced, the MQTT client outside of tests
+#[async_trait]
+trait Acks: Send + Sync {
+    async fn ack(&self, publish: &Publish) -> Result<(), MqttConnectorError>;
+}
+
+#[async_trait]
+impl Acks for AsyncClient {
+    async fn ack(&self, publish: &Publish) -> Result<(), MqttConnectorError> {
+        Ok(AsyncClient::ack(self, publish).await?)
+    }
+}
+
+/// Outcome of queueing a message for `fluvio_loop`
+#[derive(Debug, PartialEq, Eq)]
+enum Enqueued {
+    Queued,
+    /// Queued after dropping the oldest message
+    DroppedOldest,
+    /// Shutdown requested while waiting for room
+    Shutdown,
+}
+
+/// Queues the messages of the broker. When the queue is full, the oldest message is
+/// dropped from `drop_oldest`, or without it the broker is not read until there is room.
 async fn mqtt_loop(
     tx: Sender<MqttEvent>,
-    rx: Receiver<MqttEvent>,
+    drop_oldest: Option<Receiver<MqttEvent>>,
     mut eventloop: EventLoop,
     should_exit: Arc<AtomicBool>,
+    mut shutdown: ShutdownSignal,
+    metrics: Arc<ConnectorMetrics>,
+    health: Arc<ConnectorHealth>,
 ) -> Result<(), MqttConnectorError> {
     let mut last_warn = Instant::now();
     let mut num_dropped_messages = 0u64;
     while !should_exit.load(std::sync::atomic::Ordering::Relaxed) {
         // eventloop.poll() docs state "Don't block while iterating"
-        let notification = match eventloop.poll().await {
-            Ok(notification) => notification,
-            Err(e) => {
+        let notification = match shutdown.until(eventloop.poll()).await {
+            Some(Ok(notification)) => {
+                health.set_upstream_connected(true);
+                notification
+            }
+            Some(Err(e)) => {
                 error!("Mqtt error {}", e);
+                metrics.error(UPSTREAM_ERROR);
+                health.set_upstream_connected(false);
                 return Err(MqttConnectorError::MqttConnection(e));
             }
+            None => return stop(tx, drop_oldest.is_none(), eventloop).await,
         };
 
-        if let Ok(mqtt_event) = MqttEvent::try_from(notification) {
-            if tx.is_full() {
-                num_dropped_messages += 1;
-                let elapsed = last_warn.elapsed();
-                if elapsed > MIN_LOG_WARN_TIME {
-                    warn!("Queue backed up. Dropped {num_dropped_messages} mqtt messages in last {elapsed:?}");
-                    last_warn = Instant::now();
-                    num_dropped_messages = 0;
-                }
-
-                _ = rx.try_recv()
+        // QoS 0 messages are never acknowledged
+        let ack = match notification {
+            Event::Incoming(Packet::Publish(ref publish))
+                if drop_oldest.is_none() && publish.qos != QoS::AtMostOnce =>
+            {
+                Some(publish.clone())
             }
-            match tx.try_send(mqtt_event) {
-                Ok(_) => {}
-                Err(e) => match e {
-                    async_std::channel::TrySendError::Full(_) => {
-                        unreachable!();
-                    }
-                    async_std::channel::TrySendError::Closed(_) => {
-                        error!("Channel closed");
-                        should_exit.store(true, std::sync::atomic::Ordering::Relaxed);
-                        return Err(MqttConnectorError::ChannelClosed);
+            _ => None,
+        };
+        if let Ok(mut mqtt_event) = MqttEvent::try_from(notification) {
+            mqtt_event.ack = ack;
+            metrics.record_read(mqtt_event.payload.len());
+            match enqueue(&tx, drop_oldest.as_ref(), mqtt_event, &mut shutdown).await {
+                Ok(Enqueued::Queued) => {}
+                Ok(Enqueued::DroppedOldest) => {
+                    num_dropped_messages += 1;
+                    metrics.record_dropped();
+                    let elapsed = last_warn.elapsed();
+                    if elapsed > MIN_LOG_WARN_TIME {
+                        warn!("Queue backed up. Dropped {num_dropped_messages} mqtt messages in last {elapsed:?}");
+                        last_warn = Instant::now();
+                        num_dropped_messages = 0;
                     }
-                },
+                }
+                Ok(Enqueued::Shutdown) => {
+                    return stop(tx, drop_oldest.is_none(), eventloop).await;
+                }
+                Err(e) => {
+                    error!("Channel closed");
+                    should_exit.store(true, std::sync::atomic::Ordering::Relaxed);
+                    return Err(e);
+                }
             }
+            metrics.set_queued_records(tx.len());
         }
     }
     info!("Exit signal received, exiting");
     Ok(())
 }
 
-async fn fluvio_loop(
+/// Queues `mqtt_event`. When the queue is full, the oldest message is dropped from
+/// `drop_oldest`, or without it the message waits for room.
+async fn enqueue(
+    tx: &Sender<MqttEvent>,
+    drop_oldest: Option<&Receiver<MqttEvent>>,
+    mqtt_event: MqttEvent,
+    shutdown: &mut ShutdownSignal,
+) -> Result<Enqueued, MqttConnectorError> {
+    let rx = match drop_oldest {
+        Some(rx) => rx,
+        None => {
+            return match shutdown.until(tx.send(mqtt_event)).await {
+                Some(Ok(())) => Ok(Enqueued::Queued),
+                Some(Err(_)) => Err(MqttConnectorError::ChannelClosed),
+                None => Ok(Enqueued::Shutdown),
+            };
+        }
+    };
+    let dropped = tx.is_full() && rx.try_recv().is_ok();
+    match tx.try_send(mqtt_event) {
+        Ok(()) if dropped => Ok(Enqueued::DroppedOldest),
+        Ok(()) => Ok(Enqueued::Queued),
+        Err(TrySendError::Full(_)) => unreachable!(),
+        Err(TrySendError::Closed(_)) => Err(MqttConnectorError::ChannelClosed),
+    }
+}
+
+/// Stops reading the broker. In lossless mode it is still polled until `fluvio_loop`
+/// disconnects, so the acknowledgments of the messages it flushed reach the broker.
+async fn stop(
+    tx: Sender<MqttEvent>,
+    lossless: bool,
+    mut eventloop: EventLoop,
+) -> Result<(), MqttConnectorError> {
+    info!("Shutdown requested, no longer polling mqtt");
+    // dropping the sender lets fluvio_loop drain the queue and stop
+    drop(tx);
+    if lossless {
+        // messages received meanwhile are not acknowledged, the broker sends them again
+        while !matches!(
+            eventloop.poll().await?,
+            Event::Outgoing(Outgoing::Disconnect)
+        ) {}
+    }
+    Ok(())
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn fluvio_loop<O: Output, A: Acks>(
     rx: Receiver<MqttEvent>,
-    producer: TopicProducer,
+    mut output: O,
     formatter: Box<dyn Formatter + Sync + Send>,
+    retry_policy: RetryPolicy,
+    acks: Option<A>,
     should_exit: Arc<AtomicBool>,
+    shutdown: ShutdownSignal,
+    metrics: Arc<ConnectorMetrics>,
+    health: Arc<ConnectorHealth>,
 ) -> Result<(), MqttConnectorError> {
     let mut last_warn = Instant::now();
     let mut num_dropped_messages = 0u64;
+    // messages acknowledged once their records are flushed, in the order they came in
+    let mut pending_acks = Vec::new();
     while !should_exit.load(std::sync::atomic::Ordering::Relaxed) {
+        if !pending_acks.is_empty() && (rx.is_empty() || pending_acks.len() >= MAX_PENDING_ACKS) {
+            ack_flushed(&output, &acks, &mut pending_acks).await?;
+        }
         let mqtt_event = match rx.recv().await {
-            Ok(mqtt_event) => mqtt_event,
+            Ok(mqtt_event) => {
+                metrics.set_queued_records(rx.len());
+                mqtt_event
+            }
+            Err(_) if shutdown.is_requested() => {
+                info!("Queue drained, flushing producers");
+                ack_flushed(&output, &acks, &mut pending_acks).await?;
+                return Ok(());
+            }
             Err(_) => {
                 error!("Channel closed");
                 should_exit.store(true, std::sync::atomic::Ordering::Relaxed);
@@ -98,16 +214,43 @@ async fn fluvio_loop(
             }
         };
 
+        let topic = match output.topic(&mqtt_event.mqtt_topic) {
+            Ok(topic) => topic,
+            Err(e) => {
+                error!(mqtt_topic = %mqtt_event.mqtt_topic, "Routing failed, dropping message: {e}");
+                metrics.error(FORMAT_ERROR);
+                metrics.record_dropped();
+                pending_acks.extend(mqtt_event.ack);
+                continue;
+            }
+        };
         match formatter.to_string(&mqtt_event) {
             Ok(fluvio_record) => {
                 debug!("Record before smartstream {}", fluvio_record);
-                if let Err(e) = producer.send(RecordKey::NULL, fluvio_record).await {
-                    error!("Fluvio error! {}", e);
-                    producer.clear_errors().await;
-                    fluvio_future::timer::sleep(Duration::from_secs(5)).await;
+                let started = Instant::now();
+                match output.produce(&topic, &fluvio_record, &retry_policy).await {
+                    Ok(()) => {
+                        metrics.record_written(fluvio_record.len(), started.elapsed());
+                        health.record_succeeded();
+                        pending_acks.extend(mqtt_event.ack);
+                    }
+                    Err(e) => {
+                        error!(%topic, "Fluvio error! {:#}", e);
+                        metrics.record_failed(&e);
+                        health.record_failed(&e);
+                        if acks.is_some() {
+                            // left unacknowledged, the broker sends it again on reconnect
+                            should_exit.store(true, std::sync::atomic::Ordering::Relaxed);
+                            return Err(MqttConnectorError::Anyhow(e));
+                        }
+                        metrics.record_dropped();
+                    }
                 }
             }
             Err(_) => {
+                metrics.error(FORMAT_ERROR);
+                metrics.record_dropped();
+                pending_acks.extend(mqtt_event.ack);
                 num_dropped_messages += 1;
                 let elapsed = last_warn.elapsed();
                 if elapsed > MIN_LOG_WARN_TIME {
@@ -122,6 +265,21 @@ async fn fluvio_loop(
     Ok(())
 }
 
+/// Flushes the producers, then acknowledges the messages whose records were sent
+async fn ack_flushed<O: Output, A: Acks>(
+    output: &O,
+    acks: &Option<A>,
+    pending_acks: &mut Vec<Publish>,
+) -> Result<(), MqttConnectorError> {
+    output.flush().await?;
+    if let Some(client) = acks {
+        for publish in pending_acks.drain(..) {
+            client.ack(&publish).await?;
+        }
+    }
+    Ok(())
+}
+
 fn main() -> Result<(), MqttConnectorError> {
     common_initialize!();
     let arguments: Vec<String> = std::env::args().collect();
@@ -138,7 +296,7 @@ fn main() -> Result<(), MqttConnectorError> {
             println!("{}", serde_json::to_string(&mqtt_schema).unwrap());
             return Ok(());
         }
-        _ => MqttOpts::from_args(),
+        _ => parse_connector_opts(),
     };
     // Enable logging, setting default RUST_LOG if not given
     opts.common.enable_logging();
@@ -153,14 +311,25 @@ fn main() -> Result<(), MqttConnectorError> {
 
     async_global_executor::block_on(async move {
         let mqtt_timeout_seconds = Duration::from_secs(opts.timeout.unwrap_or(60));
-        let mqtt_topic = opts.mqtt_topic;
+        let subscriptions = opts.subscriptions();
+
+        opts.validate().map_err(MqttConnectorError::Config)?;
+        let mut url = Url::parse(&opts.mqtt_url)?;
+
+        // a persistent session is only resumed by a client with the same id
+        if !opts.clean_session
+            && opts.client_id.is_none()
+            && !url.query_pairs().any(|(key, _)| key == "client_id")
+        {
+            return Err(MqttConnectorError::Config(
+                "clean_session false requires a client_id".to_string(),
+            ));
+        }
 
         let client_id = opts
             .client_id
             .unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
 
-        let mut url = Url::parse(&opts.mqtt_url)?;
-
         if !url.query_pairs().any(|(key, _)| key == "client_id") {
             url.query_pairs_mut().append_pair("client_id", &client_id);
         }
@@ -172,12 +341,27 @@ fn main() -> Result<(), MqttConnectorError> {
                 timout=&opts.timeout,
                 mqtt_url=%url_without_password,
                 fluvio_topic=%opts.common.fluvio_topic,
-                %mqtt_topic,
+                ?subscriptions,
                 %client_id
             );
         }
         let mut mqttoptions = MqttOptions::try_from(url.clone())?;
         mqttoptions.set_keep_alive(mqtt_timeout_seconds);
+        if !opts.clean_session {
+            mqttoptions.set_clean_session(false);
+        }
+        if opts.lossless {
+            mqttoptions.set_manual_acks(true);
+            if opts.clean_session {
+                warn!("lossless without clean_session false: messages not acknowledged before a reconnect are lost along with the session");
+            }
+            if subscriptions
+                .iter()
+                .any(|subscription| subscription.qos == 0)
+            {
+                warn!("lossless with QoS 0 subscriptions: their messages are not sent again by the broker");
+            }
+        }
         if url.scheme() == "mqtts" || url.scheme() == "ssl" {
             let mut root_cert_store = rustls::RootCertStore::empty();
             for cert in
@@ -195,28 +379,96 @@ fn main() -> Result<(), MqttConnectorError> {
             mqttoptions.set_transport(Transport::tls_with_config(client_config.into()));
         }
 
+        let shutdown = opts.common.install_shutdown_handler()?;
+        // Unlike the metrics, the health outlives reconnects
+        let health = opts.common.health_common.create_health();
+        let retry_policy = opts.common.retry_common.retry_policy();
+        let mut reconnects = 0;
         loop {
-            let producer = opts.common.create_producer("mqtt").await?;
+            // one connection shared by the producers of all the routed topics
+            let fluvio = opts.common.connect_fluvio("mqtt").await?;
+            let producer = opts.common.create_topic_producer(&fluvio).await?;
             info!("Connected to Fluvio");
+            health.set_downstream_connected(true);
 
             // This will restart counters.
-            let metrics = Arc::new(ConnectorMetrics::new(producer.metrics()));
-            init_monitoring(metrics);
+            let metrics = Arc::new(ConnectorMetrics::new(fluvio.metrics()));
+            opts.common.init_monitoring(metrics.clone());
 
             let formatter = formatter::from_output_type(&opts.payload_output_type);
-            let (client, eventloop) = AsyncClient::new(mqttoptions.clone(), 10);
+            // acknowledgments are queued along with the requests, as many as the queued
+            // messages so acknowledging never waits for mqtt_loop, itself waiting for room
+            let requests_cap = if opts.lossless {
+                CHANNEL_BUFFER_SIZE
+            } else {
+                10
+            };
+            let (client, eventloop) = AsyncClient::new(mqttoptions.clone(), requests_cap);
+            let router = Router::new(subscriptions.clone(), opts.common.clone(), fluvio, producer);
             client
-                .subscribe(mqtt_topic.clone(), QoS::AtMostOnce)
+                .subscribe_many(subscriptions.iter().map(|subscription| {
+                    SubscribeFilter::new(subscription.topic_filter.clone(), subscription.qos())
+                }))
                 .await?;
             let (tx, rx) = channel::bounded(CHANNEL_BUFFER_SIZE);
             let should_exit = Arc::new(AtomicBool::default());
-            let mqtt_jh = spawn(mqtt_loop(tx, rx.clone(), eventloop, should_exit.clone()));
-            let fluvio_jh = spawn(fluvio_loop(rx, producer, formatter, should_exit));
+            let drop_oldest = if opts.lossless {
+                None
+            } else {
+                Some(rx.clone())
+            };
+            let acks = if opts.lossless {
+                Some(client.clone())
+            } else {
+                None
+            };
+            let mqtt_jh = spawn(mqtt_loop(
+                tx,
+                drop_oldest,
+                eventloop,
+                should_exit.clone(),
+                shutdown.clone(),
+                metrics.clone(),
+                health.clone(),
+            ));
+            let connected_at = Instant::now();
+            let disconnect = acks.clone();
+            let fluvio_loop = fluvio_loop(
+                rx,
+                router,
+                formatter,
+                retry_policy.clone(),
+                acks,
+                should_exit,
+                shutdown.clone(),
+                metrics,
+                health.clone(),
+            );
+            let fluvio_jh = spawn(async move {
+                let result = fluvio_loop.await;
+                // lets mqtt_loop stop once the last acknowledgments are sent
+                if let Some(client) = disconnect {
+                    if let Err(e) = client.disconnect().await {
+                        warn!("Could not disconnect from the broker: {e}");
+                    }
+                }
+                result
+            });
             let mqtt_result = mqtt_jh.await;
             let fluvio_result = fluvio_jh.await;
             info!("loops exited with status mqtt: {mqtt_result:?} fluvio: {fluvio_result:?}");
-            info!("reconnecting after 5 seconds");
-            fluvio_future::timer::sleep(Duration::from_secs(5)).await;
+            if shutdown.is_requested() {
+                info!("MQTT source connector stopped");
+                return Ok(());
+            }
+            // a connection that stayed up for a while starts the backoff over
+            if connected_at.elapsed() > retry_policy.max_backoff {
+                reconnects = 0;
+            }
+            reconnects += 1;
+            let backoff = retry_policy.backoff(reconnects);
+            info!("reconnecting after {backoff:?}");
+            fluvio_future::timer::sleep(backoff).await;
         }
     })
 }
@@ -234,6 +486,9 @@ struct MySchema {
 struct MqttEvent {
     mqtt_topic: String,
     payload: Vec<u8>,
+    /// Message acknowledged once produced, in lossless mode
+    #[serde(skip)]
+    ack: Option<Publish>,
 }
 impl TryFrom<Event> for MqttEvent {
     type Error = String;
@@ -242,8 +497,176 @@ impl TryFrom<Event> for MqttEvent {
             Event::Incoming(Packet::Publish(p)) => Ok(Self {
                 mqtt_topic: p.topic,
                 payload: p.payload.to_vec(),
+                ack: None,
             }),
             _ => Err("We don't support this event type!".to_string()),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::sync::Mutex;
+
+    use crate::opt::OutputType;
+
+    /// What was produced, flushed and acknowledged, in order
+    #[derive(Default)]
+    struct Log(Mutex<Vec<String>>);
+
+    impl Log {
+        fn push(&self, entry: String) {
+            self.0.lock().unwrap().push(entry);
+        }
+
+        fn entries(&self) -> Vec<String> {
+            self.0.lock().unwrap().clone()
+        }
+    }
+
+    /// Fails to route `unroutable`, to create the producer of `invalid` and to send to `down`
+    struct FakeOutput(Arc<Log>);
+
+    #[async_trait]
+    impl Output for FakeOutput {
+        fn topic(&self, mqtt_topic: &str) -> Result<String, String> {
+            match mqtt_topic {
+                "unroutable" => Err("invalid topic name".to_string()),
+                mqtt_topic => Ok(mqtt_topic.replace('/', "-")),
+            }
+        }
+
+        async fn produce(
+            &mut self,
+            topic: &str,
+            _record: &str,
+            _retry_policy: &RetryPolicy,
+        ) -> anyhow::Result<()> {
+            match topic {
+                "invalid" => Err(anyhow::anyhow!("topic creation refused")),
+                "down" => Err(anyhow::anyhow!("cluster down")),
+                _ => {
+                    self.0.push(format!("send {topic}"));
+                    Ok(())
+                }
+            }
+        }
+
+        async fn flush(&self) -> anyhow::Result<()> {
+            self.0.push("flush".to_string());
+            Ok(())
+        }
+    }
+
+    struct FakeAcks(Arc<Log>);
+
+    #[async_trait]
+    impl Acks for FakeAcks {
+        async fn ack(&self, publish: &Publish) -> Result<(), MqttConnectorError> {
+            self.0.push(format!("ack {}", publish.pkid));
+            Ok(())
+        }
+    }
+
+    fn event(mqtt_topic: &str, pkid: u16) -> MqttEvent {
+        let mut publish = Publish::new(mqtt_topic, QoS::AtLeastOnce, "{}");
+        publish.pkid = pkid;
+        MqttEvent {
+            mqtt_topic: mqtt_topic.to_string(),
+            payload: b"{}".to_vec(),
+            ack: Some(publish),
+        }
+    }
+
+    /// Runs `fluvio_loop` in lossless mode over the queued `events`, until the queue is closed
+    fn run_lossless(events: Vec<MqttEvent>) -> (Result<(), MqttConnectorError>, Vec<String>) {
+        let log = Arc::new(Log::default());
+        let (tx, rx) = channel::bounded(CHANNEL_BUFFER_SIZE);
+        for event in events {
+            tx.try_send(event).unwrap();
+        }
+        drop(tx);
+        let result = async_global_executor::block_on(fluvio_loop(
+            rx,
+            FakeOutput(log.clone()),
+            formatter::from_output_type(&OutputType::Binary),
+            RetryPolicy::default(),
+            Some(FakeAcks(log.clone())),
+            Arc::new(AtomicBool::default()),
+            ShutdownSignal::default(),
+            Arc::new(ConnectorMetrics::default()),
+            Arc::new(ConnectorHealth::default()),
+        ));
+        (result, log.entries())
+    }
+
+    #[test]
+    fn test_lossless_acks_after_flush() {
+        //when
+        let (result, log) = run_lossless(vec![event("sensors/a", 1), event("sensors/b", 2)]);
+
+        //then
+        assert!(matches!(result, Err(MqttConnectorError::ChannelClosed)));
+        assert_eq!(
+            log,
+            vec![
+                "send sensors-a",
+                "send sensors-b",
+                "flush",
+                "ack 1",
+                "ack 2"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_lossless_drops_unroutable_messages() {
+        //when
+        let (_, log) = run_lossless(vec![event("unroutable", 1), event("sensors/a", 2)]);
+
+        //then
+        assert_eq!(log, vec!["send sensors-a", "flush", "ack 1", "ack 2"]);
+    }
+
+    #[test]
+    fn test_lossless_exits_on_fluvio_error() {
+        for failing in ["down", "invalid"] {
+            //when
+            let (result, log) = run_lossless(vec![
+                event("sensors/a", 1),
+                event(failing, 2),
+                event("sensors/b", 3),
+            ]);
+
+            //then
+            assert!(matches!(result, Err(MqttConnectorError::Anyhow(_))));
+            assert_eq!(log, vec!["send sensors-a"]);
+        }
+    }
+
+    #[test]
+    fn test_enqueue_backpressure() {
+        //given
+        let (tx, rx) = channel::bounded(1);
+        let mut shutdown = ShutdownSignal::default();
+
+        async_global_executor::block_on(async {
+            //when
+            let first = enqueue(&tx, None, event("sensors/a", 1), &mut shutdown).await;
+            let blocked = async_std::future::timeout(
+                Duration::from_millis(50),
+                enqueue(&tx, None, event("sensors/b", 2), &mut shutdown),
+            )
+            .await;
+            let dropping = enqueue(&tx, Some(&rx), event("sensors/c", 3), &mut shutdown).await;
+
+            //then
+            assert_eq!(first.unwrap(), Enqueued::Queued);
+            assert!(blocked.is_err());
+            assert_eq!(dropping.unwrap(), Enqueued::DroppedOldest);
+            assert_eq!(rx.try_recv().unwrap().mqtt_topic, "sensors/c");
+        });
+    }
+}
@@ -1,13 +1,16 @@
 use clap::Parser;
 use fluvio_connectors_common::opt::CommonConnectorOpt;
 use schemars::JsonSchema;
+use serde::de::{self, Deserializer};
 use serde::Deserialize;
 use serde::Serialize;
 use serde_json::Value;
 use std::fmt::{Display, Formatter};
 use std::str::FromStr;
 
-#[derive(Parser, Debug, JsonSchema)]
+use crate::subscription::{parse_qos, Subscription};
+
+#[derive(Parser, Debug, JsonSchema, Deserialize)]
 pub(crate) struct MqttOpts {
     #[clap(long)]
     pub timeout: Option<u64>,
@@ -15,14 +18,38 @@ pub(crate) struct MqttOpts {
     #[clap(short, long, env = "MQTT_URL", hide_env_values = true)]
     pub mqtt_url: String,
 
-    #[clap(long)]
-    pub mqtt_topic: String,
+    /// MQTT topic filter subscribed with `qos` and produced to `fluvio_topic`
+    #[clap(long, required_unless_present = "subscriptions")]
+    pub mqtt_topic: Option<String>,
+
+    /// QoS level `mqtt_topic` is subscribed with: 0, 1 or 2
+    #[clap(long, parse(try_from_str = parse_qos), default_value = "0")]
+    #[serde(deserialize_with = "deserialize_qos")]
+    pub qos: u8,
+
+    /// Subscriptions instead of `mqtt_topic`, each a JSON object with its own
+    /// `topic_filter`, `qos` and `topic` the messages are routed to
+    #[clap(long, conflicts_with = "mqtt-topic")]
+    #[serde(default, deserialize_with = "deserialize_subscriptions")]
+    pub subscriptions: Vec<Subscription>,
 
     #[clap(long)]
     pub client_id: Option<String>,
 
+    /// Whether the broker discards the session on disconnect. With `false`, the
+    /// subscriptions and the QoS 1 and 2 messages not acknowledged yet are kept for the
+    /// next connection with the same `client_id`, which is then required
+    #[clap(long, parse(try_from_str), default_value = "true")]
+    pub clean_session: bool,
+
+    /// Acknowledge QoS 1 and 2 messages only once produced and flushed to Fluvio, and
+    /// stop reading from the broker while the queue is full instead of dropping messages
+    #[clap(long, parse(try_from_str), default_value = "false")]
+    pub lossless: bool,
+
     #[clap(flatten)]
     #[schemars(flatten)]
+    #[serde(flatten)]
     pub common: CommonConnectorOpt,
 
     /// Record payload output type
@@ -30,6 +57,47 @@ pub(crate) struct MqttOpts {
     pub payload_output_type: OutputType,
 }
 
+impl MqttOpts {
+    /// The `subscriptions`, or the one of `mqtt_topic`
+    pub fn subscriptions(&self) -> Vec<Subscription> {
+        match self.mqtt_topic {
+            Some(ref mqtt_topic) => vec![Subscription::new(mqtt_topic.clone(), self.qos)],
+            None => self.subscriptions.clone(),
+        }
+    }
+
+    /// Checks what clap enforces on the command line, for the options of a config
+    pub fn validate(&self) -> Result<(), String> {
+        match (&self.mqtt_topic, self.subscriptions.is_empty()) {
+            (Some(_), false) => Err("mqtt_topic conflicts with subscriptions".to_string()),
+            (None, true) => Err("either mqtt_topic or subscriptions is required".to_string()),
+            _ => Ok(()),
+        }
+    }
+}
+
+fn deserialize_qos<'de, D: Deserializer<'de>>(deserializer: D) -> Result<u8, D::Error> {
+    match Value::deserialize(deserializer)? {
+        Value::String(qos) => parse_qos(&qos),
+        qos => parse_qos(&qos.to_string()),
+    }
+    .map_err(de::Error::custom)
+}
+
+/// Subscriptions given as JSON objects or as their text, validated like on the command line
+fn deserialize_subscriptions<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Vec<Subscription>, D::Error> {
+    Vec::<Value>::deserialize(deserializer)?
+        .into_iter()
+        .map(|subscription| match subscription {
+            Value::String(subscription) => subscription.parse(),
+            subscription => subscription.to_string().parse(),
+        })
+        .collect::<Result<_, _>>()
+        .map_err(de::Error::custom)
+}
+
 #[derive(Debug, Serialize)]
 #[allow(dead_code)] // The other variants aren't used but are part of the spec.
 pub(crate) enum ConnectorDirection {
@@ -81,4 +149,36 @@ mod tests {
         assert_eq!(type2.to_string(), json_type_value);
         Ok(())
     }
+
+    #[test]
+    fn test_lossless_opts() -> anyhow::Result<()> {
+        //given
+        let args = [
+            "mqtt-source",
+            "--mqtt-url=mqtt://localhost:1883",
+            "--mqtt-topic=sensors/#",
+            "--qos=1",
+            "--client-id=fluvio-sensors",
+            "--clean-session=false",
+            "--lossless=true",
+            "--fluvio-topic=sensors",
+        ];
+
+        //when
+        let opts = MqttOpts::try_parse_from(args)?;
+        let invalid_qos = MqttOpts::try_parse_from(args.map(|arg| match arg {
+            "--qos=1" => "--qos=3",
+            arg => arg,
+        }));
+
+        //then
+        assert_eq!(
+            opts.subscriptions(),
+            vec![Subscription::new("sensors/#".to_string(), 1)]
+        );
+        assert!(!opts.clean_session);
+        assert!(opts.lossless);
+        assert!(invalid_qos.is_err());
+        Ok(())
+    }
 }
@@ -0,0 +1,515 @@
+//! Subscriptions to several MQTT topic filters, each routed to its own Fluvio topic
+
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+
+use async_trait::async_trait;
+use fluvio_connectors_common::fluvio::{Fluvio, RecordKey, TopicProducer};
+use fluvio_connectors_common::opt::CommonConnectorOpt;
+use fluvio_connectors_common::retry::RetryPolicy;
+use rumqttc::QoS;
+use schemars::JsonSchema;
+use serde::Deserialize;
+use tracing::{info, warn};
+
+const OPEN: &str = "{{";
+const CLOSE: &str = "}}";
+/// Prefix of shared subscriptions, `$share/<group>/<filter>`
+const SHARE_PREFIX: &str = "$share/";
+/// Longest name of a Fluvio topic
+const MAX_TOPIC_LEN: usize = 63;
+/// Topics a producer is kept for, the least recently used one is dropped past it
+const MAX_PRODUCERS: usize = 100;
+/// Time during which a topic whose producer failed to connect is not connected again
+const FAILED_PRODUCER_RETRY: Duration = Duration::from_secs(60);
+
+/// One of the `subscriptions`, given as a JSON object
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct Subscription {
+    /// MQTT topic filter, may contain `+` and `#` wildcards
+    pub topic_filter: String,
+    /// QoS level the filter is subscribed with: 0, 1 or 2
+    #[serde(default)]
+    pub qos: u8,
+    /// Topic the messages are produced to, defaults to `fluvio_topic`. May contain
+    /// `{{ level:N }}` placeholders for the Nth level of the MQTT topic, starting at 0,
+    /// or `{{ mqtt_topic }}` for the whole MQTT topic
+    #[serde(default)]
+    pub topic: Option<String>,
+}
+
+impl FromStr for Subscription {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let subscription: Self =
+            serde_json::from_str(s).map_err(|e| format!("invalid subscription: {e}"))?;
+        if !is_valid_filter(&subscription.topic_filter) {
+            return Err(format!(
+                "invalid subscription: invalid topic filter {}",
+                subscription.topic_filter
+            ));
+        }
+        parse_qos(&subscription.qos.to_string())
+            .map_err(|e| format!("invalid subscription: {e}"))?;
+        if let Some(ref topic) = subscription.topic {
+            render(topic, "").map_err(|e| format!("invalid subscription: {e}"))?;
+        }
+        Ok(subscription)
+    }
+}
+
+impl Subscription {
+    /// Subscription of the `mqtt_topic` option, to `fluvio_topic`
+    pub fn new(topic_filter: String, qos: u8) -> Self {
+        Self {
+            topic_filter,
+            qos,
+            topic: None,
+        }
+    }
+
+    pub fn qos(&self) -> QoS {
+        match self.qos {
+            0 => QoS::AtMostOnce,
+            1 => QoS::AtLeastOnce,
+            _ => QoS::ExactlyOnce,
+        }
+    }
+
+    /// Whether a message published to `mqtt_topic` matches the topic filter
+    pub fn matches(&self, mqtt_topic: &str) -> bool {
+        let filter = match self.topic_filter.strip_prefix(SHARE_PREFIX) {
+            Some(shared) => shared.split_once('/').map_or("", |(_, filter)| filter),
+            None => &self.topic_filter,
+        };
+        // `$SYS/...` topics are only matched by filters starting with `$`
+        if mqtt_topic.starts_with('$') && !filter.starts_with('$') {
+            return false;
+        }
+        let mut levels = mqtt_topic.split('/');
+        for filter_level in filter.split('/') {
+            match (filter_level, levels.next()) {
+                ("#", _) => return true,
+                ("+", Some(_)) => {}
+                (filter_level, Some(level)) if filter_level == level => {}
+                _ => return false,
+            }
+        }
+        levels.next().is_none()
+    }
+}
+
+/// QoS level given as 0, 1 or 2
+pub(crate) fn parse_qos(s: &str) -> Result<u8, String> {
+    match s.parse() {
+        Ok(qos @ 0..=2) => Ok(qos),
+        _ => Err(format!("unknown qos {s}, expected 0, 1 or 2")),
+    }
+}
+
+/// Fluvio topic a message published to `mqtt_topic` is produced to: the one of the first
+/// subscription matching it, or `default_topic`
+pub(crate) fn fluvio_topic(
+    subscriptions: &[Subscription],
+    mqtt_topic: &str,
+    default_topic: &str,
+) -> Result<String, String> {
+    let topic = subscriptions
+        .iter()
+        .find(|subscription| subscription.matches(mqtt_topic))
+        .and_then(|subscription| subscription.topic.as_deref());
+    match topic {
+        Some(topic) => render(topic, mqtt_topic),
+        None => Ok(default_topic.to_string()),
+    }
+}
+
+/// Renders the placeholders of `topic` with the levels of `mqtt_topic`, turned into
+/// valid Fluvio topic names. The rendered topic must be a valid name as well.
+fn render(topic: &str, mqtt_topic: &str) -> Result<String, String> {
+    let mut rendered = String::with_capacity(topic.len());
+    let mut rest = topic;
+    while let Some(start) = rest.find(OPEN) {
+        rendered.push_str(&rest[..start]);
+        let after_open = &rest[start + OPEN.len()..];
+        let end = after_open
+            .find(CLOSE)
+            .ok_or_else(|| format!("unclosed placeholder in {topic}"))?;
+        let value = match after_open[..end].trim() {
+            "mqtt_topic" => mqtt_topic,
+            placeholder => {
+                let level = placeholder
+                    .strip_prefix("level:")
+                    .and_then(|level| level.trim().parse::<usize>().ok())
+                    .ok_or_else(|| format!("unknown placeholder {placeholder} in {topic}"))?;
+                // topics are only checked to be valid templates, without any level
+                match mqtt_topic.split('/').nth(level) {
+                    Some(value) => value,
+                    None if mqtt_topic.is_empty() => "",
+                    None => return Err(format!("no level {level} in {mqtt_topic}")),
+                }
+            }
+        };
+        rendered.extend(value.chars().map(|c| match c.to_ascii_lowercase() {
+            c @ ('a'..='z' | '0'..='9') => c,
+            _ => '-',
+        }));
+        rest = &after_open[end + CLOSE.len()..];
+    }
+    rendered.push_str(rest);
+    match rendered.chars().find(|c| !is_topic_char(*c)) {
+        Some(c) => Err(format!("invalid character {c:?} in topic {topic}")),
+        // templates are checked without any level, only their text can be
+        None if mqtt_topic.is_empty() => Ok(rendered),
+        None if rendered.len() > MAX_TOPIC_LEN => Err(format!(
+            "topic {rendered} is longer than {MAX_TOPIC_LEN} characters"
+        )),
+        None if rendered.is_empty() || rendered.starts_with('-') || rendered.ends_with('-') => Err(
+            format!("topic {rendered:?} must start and end with a letter or a digit"),
+        ),
+        None => Ok(rendered),
+    }
+}
+
+/// Fluvio topic names are made of lowercase letters, digits and `-`
+fn is_topic_char(c: char) -> bool {
+    matches!(c, 'a'..='z' | '0'..='9' | '-')
+}
+
+/// `#` may only be the last level, and wildcards take a whole level
+fn is_valid_filter(filter: &str) -> bool {
+    let levels: Vec<&str> = filter.split('/').collect();
+    !filter.is_empty()
+        && levels.iter().enumerate().all(|(i, level)| match *level {
+            "#" => i == levels.len() - 1,
+            "+" => true,
+            level => !level.contains(['#', '+']),
+        })
+}
+
+/// Producers of the Fluvio topics the messages are routed to, created through a single
+/// Fluvio connection when the first message for them arrives
+pub(crate) struct Router {
+    subscriptions: Vec<Subscription>,
+    common: CommonConnectorOpt,
+    fluvio: Fluvio,
+    producers: Producers<TopicProducer>,
+}
+
+impl Router {
+    /// Router to the topics of `subscriptions`, `producer` being the one of `fluvio_topic`
+    pub fn new(
+        subscriptions: Vec<Subscription>,
+        common: CommonConnectorOpt,
+        fluvio: Fluvio,
+        producer: TopicProducer,
+    ) -> Self {
+        let mut producers = Producers::new(MAX_PRODUCERS);
+        producers.insert(common.fluvio_topic.clone(), producer);
+        Self {
+            subscriptions,
+            common,
+            fluvio,
+            producers,
+        }
+    }
+
+    /// Fluvio topic of a message published to `mqtt_topic`
+    pub fn topic(&self, mqtt_topic: &str) -> Result<String, String> {
+        fluvio_topic(&self.subscriptions, mqtt_topic, &self.common.fluvio_topic)
+    }
+
+    /// Producer of the Fluvio `topic`, created if it is the first message for it. A topic
+    /// whose producer failed is not tried again for a while.
+    pub async fn producer(&mut self, topic: &str) -> anyhow::Result<&TopicProducer> {
+        if !self.producers.contains(topic) {
+            if let Some(error) = self.producers.failure(topic, Instant::now()) {
+                anyhow::bail!("producer of {topic} failed recently: {error}");
+            }
+            if self.producers.is_full() {
+                self.evict().await;
+            }
+            info!(%topic, "Creating producer");
+            let mut common = self.common.clone();
+            common.fluvio_topic = topic.to_string();
+            match common.create_topic_producer(&self.fluvio).await {
+                Ok(producer) => self.producers.insert(topic.to_string(), producer),
+                Err(e) => {
+                    self.producers
+                        .fail(topic.to_string(), format!("{e:#}"), Instant::now());
+                    return Err(e);
+                }
+            }
+        }
+        self.producers
+            .get(topic)
+            .ok_or_else(|| anyhow::anyhow!("no producer of {topic}"))
+    }
+
+    /// Drops the least recently used producer once its records are flushed
+    async fn evict(&mut self) {
+        let topic = match self.producers.least_recently_used() {
+            Some((topic, producer)) => match producer.flush().await {
+                Ok(()) => topic.to_string(),
+                Err(e) => {
+                    // kept until a flush of all the producers fails as well
+                    warn!(%topic, "Could not flush producer, keeping it: {e}");
+                    return;
+                }
+            },
+            None => return,
+        };
+        info!(%topic, "Dropping least recently used producer");
+        self.producers.remove(&topic);
+    }
+
+    pub async fn flush(&self) -> anyhow::Result<()> {
+        for producer in self.producers.values() {
+            producer.flush().await?;
+        }
+        Ok(())
+    }
+}
+
+/// Fluvio topics the records are produced to, a [`Router`] outside of tests
+#[async_trait]
+pub(crate) trait Output: Send {
+    /// Fluvio topic of a message published to `mqtt_topic`
+    fn topic(&self, mqtt_topic: &str) -> Result<String, String>;
+
+    /// Sends `record` to `topic`, retried with `retry_policy`
+    async fn produce(
+        &mut self,
+        topic: &str,
+        record: &str,
+        retry_policy: &RetryPolicy,
+    ) -> anyhow::Result<()>;
+
+    /// Flushes the records sent to all the topics
+    async fn flush(&self) -> anyhow::Result<()>;
+}
+
+#[async_trait]
+impl Output for Router {
+    fn topic(&self, mqtt_topic: &str) -> Result<String, String> {
+        Router::topic(self, mqtt_topic)
+    }
+
+    async fn produce(
+        &mut self,
+        topic: &str,
+        record: &str,
+        retry_policy: &RetryPolicy,
+    ) -> anyhow::Result<()> {
+        let producer = self.producer(topic).await?;
+        retry_policy
+            .retry(move || async move {
+                if let Err(e) = producer.send(RecordKey::NULL, record.to_string()).await {
+                    producer.clear_errors().await;
+                    return Err(e);
+                }
+                Ok(())
+            })
+            .await
+    }
+
+    async fn flush(&self) -> anyhow::Result<()> {
+        Router::flush(self).await
+    }
+}
+
+/// Producers by topic, along with the topics whose producer recently failed
+struct Producers<P> {
+    capacity: usize,
+    /// Producer of each topic, with the last time it was used
+    producers: HashMap<String, (P, u64)>,
+    /// Error of the topics whose producer failed, with when it did
+    failures: HashMap<String, (Instant, String)>,
+    uses: u64,
+}
+
+impl<P> Producers<P> {
+    fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            producers: HashMap::new(),
+            failures: HashMap::new(),
+            uses: 0,
+        }
+    }
+
+    fn contains(&self, topic: &str) -> bool {
+        self.producers.contains_key(topic)
+    }
+
+    fn is_full(&self) -> bool {
+        self.producers.len() >= self.capacity
+    }
+
+    /// Producer of `topic`, marked as the most recently used one
+    fn get(&mut self, topic: &str) -> Option<&P> {
+        self.uses += 1;
+        let uses = self.uses;
+        self.producers.get_mut(topic).map(|(producer, used)| {
+            *used = uses;
+            &*producer
+        })
+    }
+
+    fn insert(&mut self, topic: String, producer: P) {
+        self.failures.remove(&topic);
+        self.uses += 1;
+        self.producers.insert(topic, (producer, self.uses));
+    }
+
+    fn remove(&mut self, topic: &str) -> Option<P> {
+        self.producers.remove(topic).map(|(producer, _)| producer)
+    }
+
+    fn least_recently_used(&self) -> Option<(&str, &P)> {
+        self.producers
+            .iter()
+            .min_by_key(|(_, (_, used))| *used)
+            .map(|(topic, (producer, _))| (topic.as_str(), producer))
+    }
+
+    /// Error of the producer of `topic`, if it failed less than `FAILED_PRODUCER_RETRY` ago
+    fn failure(&self, topic: &str, now: Instant) -> Option<&str> {
+        self.failures
+            .get(topic)
+            .filter(|(failed_at, _)| now.duration_since(*failed_at) < FAILED_PRODUCER_RETRY)
+            .map(|(_, error)| error.as_str())
+    }
+
+    fn fail(&mut self, topic: String, error: String, now: Instant) {
+        self.failures
+            .retain(|_, (failed_at, _)| now.duration_since(*failed_at) < FAILED_PRODUCER_RETRY);
+        if self.failures.len() >= self.capacity {
+            let oldest = self
+                .failures
+                .iter()
+                .min_by_key(|(_, (failed_at, _))| *failed_at)
+                .map(|(topic, _)| topic.clone());
+            if let Some(oldest) = oldest {
+                self.failures.remove(&oldest);
+            }
+        }
+        self.failures.insert(topic, (now, error));
+    }
+
+    fn values(&self) -> impl Iterator<Item = &P> {
+        self.producers.values().map(|(producer, _)| producer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_subscription_parse() {
+        //given
+        let subscription =
+            r#"{"topic_filter": "sensors/+/temperature", "qos": 1, "topic": "temp-{{ level:1 }}"}"#;
+
+        //when
+        let subscription: Subscription = subscription.parse().unwrap();
+
+        //then
+        assert_eq!(subscription.qos(), QoS::AtLeastOnce);
+        assert_eq!(subscription.topic.as_deref(), Some("temp-{{ level:1 }}"));
+        assert!(r#"{"topic_filter": "sensors/#/temperature"}"#.parse::<Subscription>().is_err());
+        assert!(r#"{"topic_filter": "sensors/+", "qos": 3}"#.parse::<Subscription>().is_err());
+        assert!(r#"{"topic_filter": "sensors/+", "topic": "{{ level }}"}"#
+            .parse::<Subscription>()
+            .is_err());
+        assert!(
+            r#"{"topic_filter": "sensors/+", "retain": true}"#.parse::<Subscription>().is_err()
+        );
+        assert!(
+            r#"{"topic_filter": "sensors/+", "topic": "Temp-{{ level:1 }}"}"#
+                .parse::<Subscription>()
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_topic_filter_matches() {
+        //given
+        let single = Subscription::new("sensors/+/temperature".to_string(), 0);
+        let multi = Subscription::new("sensors/#".to_string(), 1);
+        let shared = Subscription::new("$share/group/alerts/+".to_string(), 2);
+        let all = Subscription::new("#".to_string(), 0);
+
+        //then
+        assert!(single.matches("sensors/kitchen/temperature"));
+        assert!(!single.matches("sensors/kitchen/humidity"));
+        assert!(!single.matches("sensors/kitchen/temperature/max"));
+        assert!(multi.matches("sensors"));
+        assert!(multi.matches("sensors/kitchen/humidity"));
+        assert!(shared.matches("alerts/fire"));
+        assert!(!shared.matches("alerts"));
+        assert!(all.matches("sensors/kitchen"));
+        assert!(!all.matches("$SYS/broker/uptime"));
+    }
+
+    #[test]
+    fn test_fluvio_topic_routing() {
+        //given
+        let subscriptions: Vec<Subscription> = [
+            r#"{"topic_filter": "sensors/+/temperature", "topic": "temp-{{ level:1 }}"}"#,
+            r#"{"topic_filter": "alerts/#", "qos": 2, "topic": "alerts"}"#,
+            r#"{"topic_filter": "devices/#", "topic": "{{mqtt_topic}}"}"#,
+            r#"{"topic_filter": "logs/#"}"#,
+        ]
+        .iter()
+        .map(|subscription| subscription.parse().unwrap())
+        .collect();
+        let route = |mqtt_topic| fluvio_topic(&subscriptions, mqtt_topic, "mqtt");
+
+        //then
+        assert_eq!(
+            route("sensors/Living_Room/temperature"),
+            Ok("temp-living-room".to_string())
+        );
+        assert_eq!(route("alerts/fire/floor-1"), Ok("alerts".to_string()));
+        assert_eq!(
+            route("devices/D1/status"),
+            Ok("devices-d1-status".to_string())
+        );
+        assert_eq!(route("logs/app"), Ok("mqtt".to_string()));
+        assert_eq!(route("unsubscribed"), Ok("mqtt".to_string()));
+        assert!(render("temp-{{ level:3 }}", "sensors/kitchen").is_err());
+        assert!(route("devices/").is_err());
+        assert!(render("{{ mqtt_topic }}", "/devices").is_err());
+        assert!(render("{{ mqtt_topic }}", &"d".repeat(MAX_TOPIC_LEN + 1)).is_err());
+        assert!(render("{{ mqtt_topic }}", &"d".repeat(MAX_TOPIC_LEN)).is_ok());
+    }
+
+    #[test]
+    fn test_producers_evict_and_cache_failures() {
+        //given
+        let mut producers = Producers::new(2);
+        let now = Instant::now();
+        producers.insert("a".to_string(), 1);
+        producers.insert("b".to_string(), 2);
+
+        //when
+        producers.get("a");
+        producers.fail("c".to_string(), "invalid topic".to_string(), now);
+
+        //then
+        assert!(producers.is_full());
+        assert_eq!(producers.least_recently_used(), Some(("b", &2)));
+        assert_eq!(producers.failure("c", now), Some("invalid topic"));
+        assert_eq!(producers.failure("c", now + FAILED_PRODUCER_RETRY), None);
+        assert_eq!(producers.failure("a", now), None);
+        assert_eq!(producers.remove("b"), Some(2));
+        producers.insert("c".to_string(), 3);
+        assert_eq!(producers.failure("c", now), None);
+        assert_eq!(producers.values().count(), 2);
+    }
+}
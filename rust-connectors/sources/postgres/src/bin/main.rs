@@ -1,5 +1,5 @@
 use adaptive_backoff::prelude::*;
-use clap::Parser;
+use fluvio_connectors_common::opt::parse_connector_opts;
 use fluvio_connectors_common::{common_initialize, git_hash_version};
 use postgres_source::{PgConnector, PgConnectorOpt};
 use schemars::schema_for;
@@ -32,7 +32,7 @@ async fn main() -> anyhow::Result<()> {
         return Ok(());
     }
 
-    let config: PgConnectorOpt = PgConnectorOpt::from_args();
+    let config: PgConnectorOpt = parse_connector_opts();
 
     info!(
         connector_version = env!("CARGO_PKG_VERSION"),
@@ -53,15 +53,20 @@ async fn run_connector(config: &PgConnectorOpt) -> anyhow::Result<()> {
         .max(std::time::Duration::from_secs(60))
         .build()
         .map_err(|err| anyhow::anyhow!("{}", err))?;
+    let shutdown = config.common.install_shutdown_handler()?;
+    let health = config.common.health_common.create_health();
 
-    loop {
+    while !shutdown.is_requested() {
         let mut connector = match PgConnector::new(config.clone()).await {
             Ok(connector) => {
                 backoff.reset();
                 connector
+                    .with_shutdown(shutdown.clone())
+                    .with_health(health.clone())
             }
             Err(e) => {
                 error!(%e,"error creating postgres connection");
+                health.set_upstream_connected(false);
                 wait_adapatitive_backoff(&mut backoff).await;
                 continue;
             }
@@ -70,8 +75,13 @@ async fn run_connector(config: &PgConnectorOpt) -> anyhow::Result<()> {
         if let Err(err) = connector.process_stream().await {
             error!(%err, "error handling postgres stream");
         }
+        if shutdown.is_requested() {
+            break;
+        }
         wait_adapatitive_backoff(&mut backoff).await;
     }
+    info!("Postgres source connector stopped");
+    Ok(())
 }
 
 async fn wait_adapatitive_backoff(backoff: &mut ExponentialBackoff) {
@@ -1,8 +1,9 @@
 use crate::convert::convert_replication_event;
 use crate::{Error, PgConnectorOpt};
 use fluvio_connectors_common::fluvio::{Fluvio, Offset, RecordKey, TopicProducer, TopicSpec};
-use fluvio_connectors_common::metrics::ConnectorMetrics;
-use fluvio_connectors_common::monitoring::init_monitoring;
+use fluvio_connectors_common::health::ConnectorHealth;
+use fluvio_connectors_common::metrics::{ConnectorMetrics, FORMAT_ERROR};
+use fluvio_connectors_common::shutdown::ShutdownSignal;
 use fluvio_model_postgres::{Column, LogicalReplicationMessage, ReplicationEvent};
 use once_cell::sync::Lazy;
 use postgres_protocol::message::backend::{
@@ -11,7 +12,7 @@ use postgres_protocol::message::backend::{
 use std::collections::BTreeMap;
 use std::pin::Pin;
 use std::sync::Arc;
-use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 use tokio_postgres::config::ReplicationMode;
 use tokio_postgres::replication::LogicalReplicationStream;
 use tokio_postgres::types::PgLsn;
@@ -34,6 +35,11 @@ pub struct PgConnector {
     lsn: Option<PgLsn>,
     /// Caches the schema for each new table we see, grouped by relation_id
     relations: BTreeMap<u32, Vec<Column>>,
+    /// Stops streaming replication changes once notified.
+    shutdown: ShutdownSignal,
+    metrics: Arc<ConnectorMetrics>,
+    /// Outlives the connector, which is recreated on every reconnect.
+    health: Arc<ConnectorHealth>,
 }
 
 impl PgConnector {
@@ -81,7 +87,7 @@ impl PgConnector {
         let producer = config.common.create_producer("postgres").await.unwrap();
 
         let metrics = Arc::new(ConnectorMetrics::new(producer.metrics()));
-        init_monitoring(metrics);
+        config.common.init_monitoring(metrics.clone());
 
         let (pg_client, conn) = config
             .url
@@ -99,8 +105,24 @@ impl PgConnector {
             producer,
             lsn,
             relations: BTreeMap::default(),
+            shutdown: ShutdownSignal::default(),
+            metrics,
+            health: Arc::default(),
         })
     }
+
+    pub fn with_shutdown(mut self, shutdown: ShutdownSignal) -> Self {
+        self.shutdown = shutdown;
+        self
+    }
+
+    pub fn with_health(mut self, health: Arc<ConnectorHealth>) -> Self {
+        health.set_upstream_connected(true);
+        health.set_downstream_connected(true);
+        self.health = health;
+        self
+    }
+
     pub async fn create_replication_slot(config: &PgConnectorOpt) -> anyhow::Result<()> {
         let (pg_client, conn) = config
             .url
@@ -178,7 +200,15 @@ impl PgConnector {
         let stream = LogicalReplicationStream::new(copy_stream);
         tokio::pin!(stream);
 
-        while let Some(replication_message) = stream.try_next().await? {
+        let mut shutdown = self.shutdown.clone();
+        while let Some(next) = shutdown.until(stream.try_next()).await {
+            let replication_message = match next.map_err(|err| {
+                self.health.set_upstream_connected(false);
+                err
+            })? {
+                Some(replication_message) => replication_message,
+                None => break,
+            };
             let result = self
                 .process_event(stream.as_mut(), replication_message, &mut last_lsn)
                 .await;
@@ -188,6 +218,8 @@ impl PgConnector {
             }
         }
 
+        tracing::info!("Replication stream ended, flushing producer");
+        self.producer.flush().await?;
         Ok(())
     }
 
@@ -199,10 +231,21 @@ impl PgConnector {
     ) -> anyhow::Result<()> {
         match event {
             ReplicationMessage::XLogData(xlog_data) => {
-                let event = convert_replication_event(&self.relations, &xlog_data)?;
+                let event =
+                    convert_replication_event(&self.relations, &xlog_data).map_err(|err| {
+                        self.metrics.error(FORMAT_ERROR);
+                        err
+                    })?;
                 let json = serde_json::to_string(&event)?;
 
+                // the replication stream hands over decoded messages, so the size
+                // read is the one of their JSON encoding
+                let json_len = json.len();
+                self.metrics.record_read(json_len);
+                let started = Instant::now();
                 self.producer.send(RecordKey::NULL, json).await?;
+                self.metrics.record_written(json_len, started.elapsed());
+                self.health.record_succeeded();
 
                 match event.message {
                     LogicalReplicationMessage::Relation(rel) => {
@@ -0,0 +1,57 @@
+use std::sync::Arc;
+
+use fluvio_connectors_common::git_hash_version;
+use fluvio_connectors_common::metrics::ConnectorMetrics;
+
+type Result<T, E = Box<dyn std::error::Error + Send + Sync + 'static>> = core::result::Result<T, E>;
+
+use ::websocket_source::WebSocketOpt;
+
+use ::websocket_source::source::WebSocketSource;
+use fluvio_connectors_common::opt::GetOpts;
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    let opts = if let Some(opts) = WebSocketOpt::get_opt() {
+        opts
+    } else {
+        return Ok(());
+    };
+
+    // Enable logging, setting default RUST_LOG if not given
+    opts.common.enable_logging();
+    if let Err(_) | Ok("") = std::env::var("RUST_LOG").as_deref() {
+        std::env::set_var("RUST_LOG", "websocket=info");
+    }
+
+    tracing::info!(
+        connector_version = env!("CARGO_PKG_VERSION"),
+        git_hash = git_hash_version(),
+        "Starting WebSocket source connector",
+    );
+
+    tracing::info!(
+        endpoint = %opts.endpoint,
+        topic = %opts.common.fluvio_topic,
+        subscription_messages = opts.subscription_messages.len(),
+        ping_interval = ?opts.ping_interval
+    );
+
+    let shutdown = opts.common.install_shutdown_handler()?;
+    let health = opts.common.health_common.create_health();
+    let producer = opts
+        .common
+        .create_producer("websocket")
+        .await
+        .expect("Failed to create producer");
+    tracing::info!("Connected to Fluvio");
+    health.set_downstream_connected(true);
+
+    let metrics = Arc::new(ConnectorMetrics::new(producer.metrics()));
+    opts.common.init_monitoring(metrics.clone());
+
+    WebSocketSource::new(opts, producer, metrics, health, shutdown)?
+        .run()
+        .await?;
+    Ok(())
+}
@@ -0,0 +1,26 @@
+pub type Result<T, E = Error> = core::result::Result<T, E>;
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("Invalid WebSocket endpoint {0}")]
+    Endpoint(String),
+    #[error("Invalid header {0}")]
+    Header(String),
+    #[error("WebSocket error")]
+    WebSocket(#[source] Box<tokio_tungstenite::tungstenite::Error>),
+    #[error("No message for {0:?}, connection timed out")]
+    Timeout(std::time::Duration),
+    #[error("Fluvio error: {0:#}")]
+    Fluvio(anyhow::Error),
+}
+
+impl Error {
+    pub fn websocket(err: tokio_tungstenite::tungstenite::Error) -> Self {
+        Self::WebSocket(Box::new(err))
+    }
+
+    /// Errors the connector can't recover from by connecting again
+    pub fn is_fatal(&self) -> bool {
+        matches!(self, Self::Endpoint(_) | Self::Header(_) | Self::Fluvio(_))
+    }
+}
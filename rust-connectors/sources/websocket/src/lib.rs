@@ -0,0 +1,58 @@
+use clap::Parser;
+use fluvio_connectors_common::de;
+use fluvio_connectors_common::opt::{CommonConnectorOpt, GetOpts};
+use humantime::parse_duration;
+use schemars::JsonSchema;
+use serde::Deserialize;
+use std::time::Duration;
+
+#[derive(Parser, Debug, JsonSchema, Clone, Deserialize)]
+pub struct WebSocketOpt {
+    /// WebSocket endpoint to connect to, e.g. `wss://stream.example.com/ws`
+    #[clap(long)]
+    pub endpoint: String,
+
+    /// Headers to include in the connection request, in "Key:Value" format
+    #[clap(long = "header", alias = "headers")]
+    #[serde(default, deserialize_with = "de::key_values")]
+    pub headers: Vec<String>,
+
+    /// Messages sent once connected, in order, e.g. to subscribe to channels
+    #[clap(long = "subscription-message", alias = "subscription-messages")]
+    #[serde(default)]
+    pub subscription_messages: Vec<String>,
+
+    /// Time between the pings sent to the server
+    /// Ex: '30s', '1m'
+    #[clap(long, parse(try_from_str = parse_duration), default_value = "30s")]
+    #[serde(deserialize_with = "de::duration")]
+    pub ping_interval: Duration,
+
+    /// Time without any message, pong included, after which the connection is
+    /// considered lost and opened again
+    /// Ex: '60s', '2m'
+    #[clap(long, parse(try_from_str = parse_duration), default_value = "60s")]
+    #[serde(deserialize_with = "de::duration")]
+    pub idle_timeout: Duration,
+
+    #[clap(flatten)]
+    #[schemars(flatten)]
+    #[serde(flatten)]
+    pub common: CommonConnectorOpt,
+}
+
+impl GetOpts for WebSocketOpt {
+    type Opt = WebSocketOpt;
+    fn name() -> &'static str {
+        env!("CARGO_PKG_NAME")
+    }
+    fn version() -> &'static str {
+        env!("CARGO_PKG_VERSION")
+    }
+    fn description() -> &'static str {
+        env!("CARGO_PKG_DESCRIPTION")
+    }
+}
+
+pub mod error;
+pub mod source;
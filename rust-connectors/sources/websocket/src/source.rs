@@ -0,0 +1,228 @@
+//! Connecting to the endpoint and producing its messages to Fluvio
+
+use std::sync::Arc;
+use std::time::Instant;
+
+use fluvio_connectors_common::fluvio::{RecordKey, TopicProducer};
+use fluvio_connectors_common::health::ConnectorHealth;
+use fluvio_connectors_common::metrics::{ConnectorMetrics, UPSTREAM_ERROR};
+use fluvio_connectors_common::shutdown::ShutdownSignal;
+use futures_util::{SinkExt, StreamExt};
+use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+use tokio_tungstenite::tungstenite::handshake::client::Request;
+use tokio_tungstenite::tungstenite::http::header::{HeaderName, HeaderValue};
+use tokio_tungstenite::tungstenite::Message;
+
+use crate::error::{Error, Result};
+use crate::WebSocketOpt;
+
+pub struct WebSocketSource {
+    opts: WebSocketOpt,
+    producer: TopicProducer,
+    metrics: Arc<ConnectorMetrics>,
+    health: Arc<ConnectorHealth>,
+    shutdown: ShutdownSignal,
+}
+
+impl WebSocketSource {
+    pub fn new(
+        opts: WebSocketOpt,
+        producer: TopicProducer,
+        metrics: Arc<ConnectorMetrics>,
+        health: Arc<ConnectorHealth>,
+        shutdown: ShutdownSignal,
+    ) -> Result<Self> {
+        client_request(&opts)?;
+        Ok(Self {
+            opts,
+            producer,
+            metrics,
+            health,
+            shutdown,
+        })
+    }
+
+    /// Produces the messages of the endpoint until shutdown, connecting again with
+    /// backoff when the connection is closed or lost, then flushes the producer
+    pub async fn run(&self) -> Result<()> {
+        let retry_policy = self.opts.common.retry_common.retry_policy();
+        let mut reconnects = 0;
+        let mut shutdown = self.shutdown.clone();
+        while !shutdown.is_requested() {
+            let connected_at = Instant::now();
+            match self.connect().await {
+                Ok(()) => tracing::info!("Connection closed"),
+                Err(err) if err.is_fatal() => return Err(err),
+                Err(err) => tracing::error!(?err, "Connection failed"),
+            }
+            if shutdown.is_requested() {
+                break;
+            }
+            self.health.set_upstream_connected(false);
+
+            // a connection that stayed up for a while starts the backoff over
+            if connected_at.elapsed() > retry_policy.max_backoff {
+                reconnects = 0;
+            }
+            reconnects += 1;
+            let backoff = retry_policy.backoff(reconnects);
+            tracing::info!("Reconnecting after {backoff:?}");
+            shutdown.until(tokio::time::sleep(backoff)).await;
+        }
+        tracing::info!("Shutting down, flushing producer");
+        self.producer.flush().await.map_err(Error::Fluvio)
+    }
+
+    /// Connects, sends the subscription messages, then produces every text and binary
+    /// message until the connection is closed
+    async fn connect(&self) -> Result<()> {
+        let (mut socket, response) = tokio_tungstenite::connect_async(client_request(&self.opts)?)
+            .await
+            .map_err(|e| {
+                self.metrics.error(UPSTREAM_ERROR);
+                Error::websocket(e)
+            })?;
+        tracing::info!(endpoint = %self.opts.endpoint, status = %response.status(), "Connected");
+        self.health.set_upstream_connected(true);
+
+        for message in &self.opts.subscription_messages {
+            tracing::debug!(%message, "Subscribing");
+            socket
+                .send(Message::Text(message.clone()))
+                .await
+                .map_err(Error::websocket)?;
+        }
+
+        let ping_interval = self.opts.ping_interval;
+        let mut ping =
+            tokio::time::interval_at(tokio::time::Instant::now() + ping_interval, ping_interval);
+        // reset on every message, so the connection is dropped right when it got idle
+        let idle = tokio::time::sleep(self.opts.idle_timeout);
+        tokio::pin!(idle);
+        let mut shutdown = self.shutdown.clone();
+        loop {
+            tokio::select! {
+                message = socket.next() => {
+                    let message = match message {
+                        Some(message) => message.map_err(|e| {
+                            self.metrics.error(UPSTREAM_ERROR);
+                            Error::websocket(e)
+                        })?,
+                        None => return Ok(()),
+                    };
+                    idle
+                        .as_mut()
+                        .reset(tokio::time::Instant::now() + self.opts.idle_timeout);
+                    match message {
+                        Message::Text(text) => self.produce(text.into_bytes()).await?,
+                        Message::Binary(data) => self.produce(data).await?,
+                        Message::Close(frame) => tracing::info!(?frame, "Closed by the server"),
+                        // pings are answered by tungstenite
+                        Message::Ping(_) | Message::Pong(_) | Message::Frame(_) => {}
+                    }
+                }
+                _ = &mut idle => {
+                    self.metrics.error(UPSTREAM_ERROR);
+                    return Err(Error::Timeout(self.opts.idle_timeout));
+                }
+                _ = ping.tick() => {
+                    socket.send(Message::Ping(Vec::new())).await.map_err(Error::websocket)?;
+                }
+                _ = shutdown.requested() => {
+                    if let Err(err) = socket.close(None).await {
+                        tracing::debug!(%err, "Closing connection failed");
+                    }
+                    return Ok(());
+                }
+            }
+        }
+    }
+
+    async fn produce(&self, record_out: Vec<u8>) -> Result<()> {
+        tracing::debug!(len = record_out.len(), "Producing");
+
+        let record_len = record_out.len();
+        self.metrics.record_read(record_len);
+        let started = Instant::now();
+        self.producer
+            .send(RecordKey::NULL, record_out)
+            .await
+            .map_err(Error::Fluvio)?;
+        self.metrics.record_written(record_len, started.elapsed());
+        self.health.record_succeeded();
+        Ok(())
+    }
+}
+
+/// Connection request to the endpoint, along with the configured headers
+fn client_request(opts: &WebSocketOpt) -> Result<Request> {
+    let mut request = opts
+        .endpoint
+        .as_str()
+        .into_client_request()
+        .map_err(|_| Error::Endpoint(opts.endpoint.clone()))?;
+    match request.uri().scheme_str() {
+        Some("ws") | Some("wss") => {}
+        _ => return Err(Error::Endpoint(opts.endpoint.clone())),
+    }
+    for header in &opts.headers {
+        let (key, value) = header
+            .split_once(':')
+            .ok_or_else(|| Error::Header(header.clone()))?;
+        let key = HeaderName::from_bytes(key.trim().as_bytes())
+            .map_err(|_| Error::Header(header.clone()))?;
+        let value =
+            HeaderValue::from_str(value.trim()).map_err(|_| Error::Header(header.clone()))?;
+        request.headers_mut().append(key, value);
+    }
+    Ok(request)
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::Parser;
+
+    use super::*;
+
+    #[test]
+    fn test_client_request() {
+        //given
+        let opts = WebSocketOpt::parse_from([
+            "websocket-source",
+            "--endpoint=wss://stream.example.com/ws?channel=trades",
+            "--header=Authorization: Bearer abc",
+            "--header=X-Api-Key:def",
+            "--fluvio-topic=trades",
+        ]);
+        let invalid_header = WebSocketOpt::parse_from([
+            "websocket-source",
+            "--endpoint=ws://localhost:8090",
+            "--header=Authorization",
+            "--fluvio-topic=trades",
+        ]);
+        let invalid_endpoint = WebSocketOpt::parse_from([
+            "websocket-source",
+            "--endpoint=http://localhost:8090",
+            "--fluvio-topic=trades",
+        ]);
+
+        //when
+        let request = client_request(&opts).unwrap();
+
+        //then
+        assert_eq!(
+            request.uri().to_string(),
+            "wss://stream.example.com/ws?channel=trades"
+        );
+        assert_eq!(request.headers()["authorization"], "Bearer abc");
+        assert_eq!(request.headers()["x-api-key"], "def");
+        assert!(matches!(
+            client_request(&invalid_header),
+            Err(Error::Header(_))
+        ));
+        assert!(matches!(
+            client_request(&invalid_endpoint),
+            Err(Error::Endpoint(_))
+        ));
+    }
+}
@@ -0,0 +1,41 @@
+#!/usr/bin/env bats
+
+setup() {
+    cargo build -p websocket-mock
+    ../../../target/debug/websocket-mock & disown
+    MOCK_PID=$!
+    FILE=$(mktemp --suffix .yaml)
+    cp ./tests/ticker-test-config.yaml $FILE
+    UUID=$(uuidgen)
+    TOPIC=${UUID}-topic
+    fluvio topic create $TOPIC || true
+
+    sed -i.BAK "s/websocket-connector/${UUID}/g" $FILE
+    IP_ADDRESS=$(ip route get 8.8.8.8 | awk -F"src " 'NR==1{split($2,a," ");print a[1]}')
+    sed -i.BAK "s/IP_ADDRESS/${IP_ADDRESS}/g" $FILE
+    cargo run --bin connector-run --manifest-path ../../../Cargo.toml -- apply  --config $FILE
+}
+
+teardown() {
+    cargo run --bin connector-run --manifest-path ../../../Cargo.toml -- delete  --config $FILE
+    fluvio topic delete $TOPIC
+    kill $MOCK_PID
+}
+
+@test "websocket-connector-ticker-test" {
+    count=1
+    echo "Starting consumer on topic $TOPIC"
+    sleep 13
+
+    # the mock closes the connection every 5 ticks, the ticks go on after reconnecting
+    fluvio consume -B -d $TOPIC | while read input; do
+        expected="Tick $count"
+        echo $input = $expected
+        [ "$input" = "$expected" ]
+        count=$(($count + 1))
+        if [ $count -eq 11 ]; then
+            break;
+        fi
+    done
+
+}
@@ -50,6 +50,15 @@ impl LocalOpt {
         for arg in &env_args {
             command = command.arg("--env").arg(arg);
         }
+        if let Some(monitoring) = config.monitoring.as_ref() {
+            for addr in [monitoring.metrics_addr(), monitoring.health_addr()]
+                .into_iter()
+                .flatten()
+            {
+                let port = addr.port();
+                command = command.arg("--publish").arg(format!("{port}:{port}"));
+            }
+        }
         command = command.arg(image).args(args);
         println!("Spawning with command: {command:?}");
         let mut child = command.spawn()?;
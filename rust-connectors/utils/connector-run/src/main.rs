@@ -4,25 +4,30 @@ mod local;
 mod print;
 
 use anyhow::Context;
-use itertools::Itertools;
 use std::collections::HashMap;
+use std::time::Duration;
 
 use apply::ApplyOpt;
 use clap::Parser;
 use delete::DeleteOpt;
-use fluvio_connectors_common::config::{ConnectorConfig, ManagedConnectorParameterValue};
+use fluvio_connectors_common::config::{ConnectorConfig, SecretSource, LIVENESS_PATH};
 use k8_types::{
     app::deployment::DeploymentSpec,
     core::pod::{
-        ConfigMapVolumeSource, ContainerSpec, ImagePullPolicy, KeyToPath, PodSecurityContext,
-        PodSpec, VolumeMount, VolumeSpec,
+        ConfigMapVolumeSource, ContainerPortSpec, ContainerSpec, ExecAction, ImagePullPolicy,
+        KeyToPath, PodSecurityContext, PodSpec, Probe, VolumeMount, VolumeSpec,
     },
-    Env, LabelProvider, LabelSelector, TemplateMeta, TemplateSpec,
+    Env, EnvVarSource, KeySelector, LabelProvider, LabelSelector, TemplateMeta, TemplateSpec,
 };
 use local::LocalOpt;
 use print::PrintOpt;
 
 const DEFAULT_CONNECTOR_NAME: &str = "fluvio-connector";
+/// Matches the default `--shutdown-grace-period` of the connectors
+const DEFAULT_SHUTDOWN_GRACE_PERIOD: Duration = Duration::from_secs(20);
+/// Extra time k8s waits after the connector grace period before killing the pod
+const TERMINATION_GRACE_PERIOD_MARGIN: Duration = Duration::from_secs(10);
+const HEALTH_PORT_NAME: &str = "health";
 
 #[tokio::main]
 async fn main() {
@@ -91,20 +96,50 @@ fn convert_to_k8_deployment(config: &ConnectorConfig) -> anyhow::Result<Deployme
 
     let volumes = vec![config_map_volume_spec];
 
-    let secrets = &config.secrets;
-    let env: Vec<Env> = secrets
-        .keys()
-        .zip(secrets.values())
-        .flat_map(|(key, value)| [Env::key_value(key, &(**value).to_string())])
+    let env = build_k8_envs(config)?;
+
+    let termination_grace_period = (config
+        .shutdown
+        .as_ref()
+        .and_then(|shutdown| shutdown.grace_period())
+        .unwrap_or(DEFAULT_SHUTDOWN_GRACE_PERIOD)
+        + TERMINATION_GRACE_PERIOD_MARGIN)
+        .as_secs();
+
+    let metrics_addr = config
+        .monitoring
+        .as_ref()
+        .and_then(|monitoring| monitoring.metrics_addr());
+    let health_addr = config
+        .monitoring
+        .as_ref()
+        .and_then(|monitoring| monitoring.health_addr());
+
+    let ports = metrics_addr
+        .map(|metrics_addr| ContainerPortSpec {
+            name: Some("metrics".to_string()),
+            container_port: metrics_addr.port(),
+            ..Default::default()
+        })
+        .into_iter()
+        .chain(health_addr.map(|health_addr| ContainerPortSpec {
+            name: Some(HEALTH_PORT_NAME.to_string()),
+            container_port: health_addr.port(),
+            ..Default::default()
+        }))
         .collect::<Vec<_>>();
 
+    // The probe is only set when the connector serves its health, otherwise k8s
+    // would restart it forever.
+    let liveness_probe = health_addr.map(|health_addr| liveness_probe(health_addr.port()));
+
     let template = TemplateSpec {
         metadata: Some(TemplateMeta::default().set_labels(vec![
             ("app", DEFAULT_CONNECTOR_NAME),
             ("connectorName", &config.name),
         ])),
         spec: PodSpec {
-            termination_grace_period_seconds: Some(10),
+            termination_grace_period_seconds: Some(termination_grace_period.try_into()?),
             security_context: Some(PodSecurityContext {
                 fs_group: Some(1000),
                 ..Default::default()
@@ -115,6 +150,8 @@ fn convert_to_k8_deployment(config: &ConnectorConfig) -> anyhow::Result<Deployme
                 image_pull_policy: Some(ImagePullPolicy::Never),
                 env,
                 volume_mounts,
+                ports,
+                liveness_probe,
                 args,
                 ..Default::default()
             }],
@@ -133,8 +170,29 @@ fn convert_to_k8_deployment(config: &ConnectorConfig) -> anyhow::Result<Deployme
         ..Default::default()
     })
 }
+
+/// k8-types has no HTTP probes, so the liveness endpoint is queried with the busybox
+/// `wget` of the connector image, which fails on a 503.
+fn liveness_probe(port: u16) -> Probe {
+    Probe {
+        exec: Some(ExecAction {
+            command: vec![
+                "wget".to_string(),
+                "-q".to_string(),
+                "-O".to_string(),
+                "/dev/null".to_string(),
+                format!("http://127.0.0.1:{port}{LIVENESS_PATH}"),
+            ],
+        }),
+        period_seconds: Some(10),
+        timeout_seconds: Some(5),
+        failure_threshold: Some(3),
+        ..Default::default()
+    }
+}
+
 fn build_envs(config: &ConnectorConfig) -> anyhow::Result<HashMap<String, String>> {
-    let secrets = &config.secrets;
+    let secrets = config.resolve_secrets()?;
     let env: HashMap<String, String> = HashMap::from_iter(
         secrets
             .iter()
@@ -143,54 +201,43 @@ fn build_envs(config: &ConnectorConfig) -> anyhow::Result<HashMap<String, String
     Ok(env)
 }
 
-fn build_args(config: &ConnectorConfig) -> anyhow::Result<Vec<String>> {
-    let parameters = &config.parameters;
-
-    let parameters: Vec<String> = parameters
-        .keys()
-        .zip(parameters.values())
-        .flat_map(|(key, values)| match &values {
-            ManagedConnectorParameterValue::String(value) => {
-                vec![format!("--{}={}", key.replace('_', "-"), value)]
-            }
-            ManagedConnectorParameterValue::Vec(values) => {
-                let mut args = Vec::new();
-                for value in values.iter() {
-                    args.push(format!("--{}={}", key.replace('_', "-"), value))
-                }
-                args
-            }
-            ManagedConnectorParameterValue::Map(map) => {
-                let mut args = Vec::new();
-                for (sub_key, value) in map.iter() {
-                    args.push(format!(
-                        "--{}={}:{}",
-                        key.replace('_', "-"),
-                        sub_key.replace('_', "-"),
-                        value
-                    ));
-                }
-                args
-            }
-        })
-        .chain(config.producer_parameters().into_iter())
-        .chain(config.consumer_parameters().into_iter())
-        .collect::<Vec<_>>();
-
-    // Prefixing the args with a "--" passed to the container is needed for an unclear reason.
-    let mut args = vec!["--".to_string(), format!("--fluvio-topic={}", config.topic)];
-    args.extend(parameters);
-
-    if let Some(ref transform_params) = config.transforms {
-        let transforms: Result<Vec<String>, serde_json::Error> = transform_params
-            .transforms
-            .iter()
-            .map(serde_json::to_string)
-            .map_ok(|transform| format!("--transform={transform}"))
-            .collect();
-        args.extend(transforms.context("unable serialize TransformParameters into arguments")?);
+/// Secrets stored in Kubernetes are referenced with `secretKeyRef` instead of
+/// inlining their value into the deployment. Secrets read from local files and
+/// environment variables are refused, so they never end up in the deployment spec.
+fn build_k8_envs(config: &ConnectorConfig) -> anyhow::Result<Vec<Env>> {
+    let mut env = Vec::with_capacity(config.secrets.len());
+    for (key, value) in &config.secrets {
+        match value.source() {
+            SecretSource::Kubernetes {
+                name,
+                key: secret_key,
+            } => env.push(Env {
+                name: key.clone(),
+                value: None,
+                value_from: Some(EnvVarSource::SecretKeyRef(KeySelector {
+                    name: name.to_string(),
+                    key: secret_key.unwrap_or(key).to_string(),
+                    ..Default::default()
+                })),
+            }),
+            SecretSource::Inline(value) => env.push(Env::key_value(key.as_str(), value)),
+            SecretSource::File(_) | SecretSource::Env(_) => anyhow::bail!(
+                "secret {key}: local secrets can't be deployed to Kubernetes, \
+                store it in a Kubernetes Secret and reference it with secret:<name>"
+            ),
+        }
     }
+    Ok(env)
+}
 
+fn build_args(config: &ConnectorConfig) -> anyhow::Result<Vec<String>> {
+    // Prefixing the args with a "--" passed to the container is needed for an unclear reason.
+    let mut args = vec!["--".to_string()];
+    args.extend(
+        config
+            .to_args()
+            .context("unable to convert the connector config into arguments")?,
+    );
     Ok(args)
 }
 
@@ -198,7 +245,7 @@ fn build_args(config: &ConnectorConfig) -> anyhow::Result<Vec<String>> {
 mod tests {
     use fluvio_connectors_common::config::ConnectorConfig;
 
-    use crate::build_args;
+    use crate::{build_args, build_k8_envs, convert_to_k8_deployment};
 
     #[test]
     fn test_build_args() {
@@ -212,6 +259,8 @@ create-topic: true
 parameters:
   mqtt_topic: "dummy_mqtt_topic"
   payload_output_type: json
+  mqtt_option:
+    keep_alive: 10s
 secrets:
   MQTT_URL: mqtt://dummy_url
 producer:
@@ -220,6 +269,18 @@ producer:
   compression: gzip
 consumer:
   partition: 0
+  partitions: [1, 2]
+  all-partitions: true
+retry:
+  max-attempts: 3
+  max-backoff: 10s
+shutdown:
+  grace-period: 45s
+monitoring:
+  metrics-addr: 0.0.0.0:9090
+  health-addr: 0.0.0.0:8080
+  unhealthy-after: 2m
+  max-idle: 1h
         "#;
 
         let config: ConnectorConfig = serde_yaml::from_str(contents).unwrap();
@@ -229,9 +290,83 @@ consumer:
         assert!(args.contains("--fluvio-topic=fluvio_topic"));
         assert!(args.contains("--mqtt-topic=dummy_mqtt_topic"));
         assert!(args.contains("--payload-output-type=json"));
+        assert!(args.contains("--mqtt-option=keep-alive:10s"));
         assert!(args.contains("--producer-linger 1000ms"));
         assert!(args.contains("--producer-batch-size 10mb"));
         assert!(args.contains("--producer-compression gzip"));
         assert!(args.contains("--consumer-partition 0"));
+        assert!(args.contains("--consumer-partition 1 --consumer-partition 2"));
+        assert!(args.contains("--consumer-all-partitions"));
+        assert!(args.contains("--retry-max-attempts 3"));
+        assert!(args.contains("--retry-max-backoff 10000ms"));
+        assert!(args.contains("--shutdown-grace-period 45000ms"));
+        assert!(args.contains("--metrics-addr 0.0.0.0:9090"));
+        assert!(args.contains("--health-addr 0.0.0.0:8080"));
+        assert!(args.contains("--health-unhealthy-after 120000ms"));
+        assert!(args.contains("--health-max-idle 3600000ms"));
+    }
+
+    #[test]
+    fn test_liveness_probe() {
+        let contents = r#"
+version: latest
+name: connector_name
+type: mqtt-source
+topic: fluvio_topic
+monitoring:
+  health-addr: 0.0.0.0:8080
+        "#;
+
+        let config: ConnectorConfig = serde_yaml::from_str(contents).unwrap();
+        let deployment = serde_json::to_value(convert_to_k8_deployment(&config).unwrap()).unwrap();
+        let container = &deployment["template"]["spec"]["containers"][0];
+        assert_eq!(container["ports"][0]["containerPort"], 8080);
+        assert_eq!(
+            container["livenessProbe"]["exec"]["command"][4],
+            "http://127.0.0.1:8080/live"
+        );
+    }
+
+    #[test]
+    fn test_build_k8_envs() {
+        let contents = r#"
+version: latest
+name: connector_name
+type: http-source
+topic: fluvio_topic
+secrets:
+  INLINE: inline-value
+  LITERAL: literal:env:not-a-variable
+  URL: secret:http-credentials
+  PASSWORD: secret:http-credentials/pass
+        "#;
+
+        let config: ConnectorConfig = serde_yaml::from_str(contents).unwrap();
+        let env = serde_json::to_value(build_k8_envs(&config).unwrap()).unwrap();
+        let secret_ref = |index: usize| &env[index]["valueFrom"]["secretKeyRef"];
+        assert_eq!(env[0]["name"], "INLINE");
+        assert_eq!(env[0]["value"], "inline-value");
+        assert_eq!(env[1]["name"], "LITERAL");
+        assert_eq!(env[1]["value"], "env:not-a-variable");
+        assert_eq!(env[2]["name"], "PASSWORD");
+        assert_eq!(secret_ref(2)["name"], "http-credentials");
+        assert_eq!(secret_ref(2)["key"], "pass");
+        assert_eq!(env[3]["name"], "URL");
+        assert_eq!(secret_ref(3)["name"], "http-credentials");
+        assert_eq!(secret_ref(3)["key"], "URL");
+        assert!(env[3]["value"].is_null());
+    }
+
+    #[test]
+    fn test_build_k8_envs_refuses_local_secrets() {
+        for secret in ["env:CONNECTOR_RUN_TEST_TOKEN", "file:/run/secrets/token"] {
+            let contents = format!(
+                "version: latest\nname: connector_name\ntype: http-source\ntopic: fluvio_topic\nsecrets:\n  TOKEN: {secret}\n"
+            );
+
+            let config: ConnectorConfig = serde_yaml::from_str(&contents).unwrap();
+            let err = build_k8_envs(&config).unwrap_err();
+            assert!(err.to_string().contains("secret TOKEN"));
+        }
     }
 }
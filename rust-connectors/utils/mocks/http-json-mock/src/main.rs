@@ -23,6 +23,8 @@ async fn main() -> tide::Result<()> {
     app.at("/get").get(get_request);
     app.at("/time").get(get_time_request);
     app.at("/post").post(post_request);
+    app.at("/paged").get(paged_request);
+    app.at("/sse").get(tide::sse::endpoint(sse_request));
     app.listen("0.0.0.0:8080").await?;
     Ok(())
 }
@@ -53,3 +55,37 @@ async fn post_request(mut req: Request<State>) -> tide::Result {
     let value = state.post_count.fetch_add(1, Ordering::Relaxed) + 1;
     Ok(format!("Hello, {name}! - {value}").into())
 }
+
+const LAST_PAGE: u32 = 3;
+
+#[derive(Debug, Deserialize)]
+struct PageQuery {
+    page: Option<u32>,
+}
+
+/// Links each page to the next one in the `Link` header, up to `LAST_PAGE`
+async fn paged_request(req: Request<State>) -> tide::Result {
+    let page = req.query::<PageQuery>()?.page.unwrap_or(1);
+    let mut response = tide::Response::new(200);
+    response.set_body(format!("Page {page} of {LAST_PAGE}"));
+    if page < LAST_PAGE {
+        let next = page + 1;
+        response.insert_header("Link", format!("</paged?page={next}>; rel=\"next\""));
+    }
+    Ok(response)
+}
+
+/// Sends a few events then closes the stream, resuming after `Last-Event-ID` on reconnect
+async fn sse_request(req: Request<State>, sender: tide::sse::Sender) -> tide::Result<()> {
+    let last_event_id: u32 = req
+        .header("Last-Event-ID")
+        .and_then(|id| id.as_str().parse().ok())
+        .unwrap_or(0);
+    for value in last_event_id + 1..=last_event_id + 3 {
+        let id = value.to_string();
+        sender
+            .send("message", format!("Hello, Fluvio! - {value}"), Some(&id))
+            .await?;
+    }
+    Ok(())
+}
@@ -0,0 +1,71 @@
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use futures_util::{SinkExt, StreamExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio_tungstenite::tungstenite::handshake::server::{ErrorResponse, Request, Response};
+use tokio_tungstenite::tungstenite::http::StatusCode;
+use tokio_tungstenite::tungstenite::{Message, Result};
+
+const TICKER_PATH: &str = "/ticker";
+const SUBSCRIBE_MESSAGE: &str = r#"{"subscribe":"ticker"}"#;
+/// Ticks sent before closing the connection, so clients have to reconnect
+const TICKS_PER_CONNECTION: u32 = 5;
+
+#[tokio::main]
+async fn main() -> std::io::Result<()> {
+    let ticks = Arc::new(AtomicU32::new(0));
+    let listener = TcpListener::bind("0.0.0.0:8090").await?;
+    loop {
+        let (stream, _) = listener.accept().await?;
+        let ticks = ticks.clone();
+        tokio::spawn(async move {
+            if let Err(err) = ticker(stream, ticks).await {
+                println!("ticker connection failed: {err}");
+            }
+        });
+    }
+}
+
+/// Only accepts connections to `TICKER_PATH`
+// the error response type is the one of the tungstenite callback
+#[allow(clippy::result_large_err)]
+fn check_path(request: &Request, response: Response) -> Result<Response, ErrorResponse> {
+    if request.uri().path() == TICKER_PATH {
+        Ok(response)
+    } else {
+        let mut not_found = ErrorResponse::new(Some("not found".to_string()));
+        *not_found.status_mut() = StatusCode::NOT_FOUND;
+        Err(not_found)
+    }
+}
+
+/// Sends `Tick <n>` every 200ms once subscribed, alternating text and binary
+/// messages, and closes the connection every `TICKS_PER_CONNECTION` ticks. The tick
+/// count goes on across connections.
+async fn ticker(stream: TcpStream, ticks: Arc<AtomicU32>) -> Result<()> {
+    let mut socket = tokio_tungstenite::accept_hdr_async(stream, check_path).await?;
+
+    // wait for the subscription, answering pings meanwhile
+    while let Some(message) = socket.next().await {
+        if let Message::Text(text) = message? {
+            if text == SUBSCRIBE_MESSAGE {
+                break;
+            }
+        }
+    }
+
+    let mut interval = tokio::time::interval(Duration::from_millis(200));
+    for _ in 0..TICKS_PER_CONNECTION {
+        interval.tick().await;
+        let tick = ticks.fetch_add(1, Ordering::Relaxed) + 1;
+        let message = format!("Tick {tick}");
+        let message = match tick % 2 {
+            0 => Message::Binary(message.into_bytes()),
+            _ => Message::Text(message),
+        };
+        socket.send(message).await?;
+    }
+    socket.close(None).await
+}
@@ -5,6 +5,7 @@ use std::env;
 use std::process::Command;
 
 use fluvio_connectors_common::common_initialize;
+use fluvio_connectors_common::opt::parse_connector_opts;
 
 mod produce;
 use produce::produce;
@@ -47,7 +48,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     println!("ARGS {args:?}");
 
     // Otherwise parse the CLI flags to determine the running mode
-    let opts = TestConnectorOpts::from_args();
+    let opts: TestConnectorOpts = parse_connector_opts();
     println!("{opts:?}");
 
     // If we provide a test name then we are running `fluvio-test`
@@ -1,7 +1,9 @@
 use clap::Parser;
+use fluvio_connectors_common::de;
 use fluvio_connectors_common::opt::CommonConnectorOpt;
 use humantime::parse_duration;
 use schemars::JsonSchema;
+use serde::Deserialize;
 use std::time::Duration;
 
 #[derive(Parser, Debug, JsonSchema)]
@@ -10,23 +12,26 @@ pub enum TestConnectorSubCmd {
     Metadata,
 }
 
-#[derive(Parser, Debug, JsonSchema)]
+#[derive(Parser, Debug, JsonSchema, Deserialize)]
 pub struct TestConnectorOpts {
     #[clap(flatten)]
     #[schemars(flatten)]
+    #[serde(flatten)]
     pub common: CommonConnectorOpt,
 
     #[clap(long)]
     pub count: Option<i64>,
 
     #[clap(long, parse(try_from_str = parse_duration))]
+    #[serde(default, deserialize_with = "de::option_duration")]
     pub timeout: Option<Duration>,
 
     #[clap(flatten)]
+    #[serde(flatten)]
     pub test_opts: FluvioTestOpts,
 }
 
-#[derive(Parser, Debug, JsonSchema)]
+#[derive(Parser, Debug, JsonSchema, Deserialize)]
 pub struct FluvioTestOpts {
     /// File path to a `fluvio-test` binary
     #[clap(long, default_value = "/usr/local/bin/fluvio-test")]
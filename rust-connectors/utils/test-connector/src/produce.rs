@@ -1,7 +1,5 @@
 use crate::opts::TestConnectorOpts;
-use fluvio_connectors_common::{
-    fluvio::RecordKey, metrics::ConnectorMetrics, monitoring::init_monitoring,
-};
+use fluvio_connectors_common::{fluvio::RecordKey, metrics::ConnectorMetrics};
 use std::{sync::Arc, time::Duration};
 
 pub async fn produce(opts: TestConnectorOpts) -> anyhow::Result<()> {
@@ -12,7 +10,7 @@ pub async fn produce(opts: TestConnectorOpts) -> anyhow::Result<()> {
         .expect("Failed to create producer");
 
     let metrics = Arc::new(ConnectorMetrics::new(producer.metrics()));
-    init_monitoring(metrics);
+    opts.common.init_monitoring(metrics);
 
     let num_records = opts.count.unwrap_or(i64::MAX);
     let timeout = opts.timeout.unwrap_or(Duration::from_millis(1000));